@@ -0,0 +1,41 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use futures::executor::block_on;
+use liccrawler::parse::{find_codes, find_creator, TimeParser};
+
+const EXPIRIES: [&str; 6] = [
+    "Expires: January 26th, 2024",
+    "Expires 1/26/2024 at 8am PST",
+    "Expires 2024-01-26",
+    "Expires end of day",
+    "While supplies last",
+    "no date to be found here at all",
+];
+
+const MESSAGE: &str = "ABCD-EFGH-IJKL
+Creator: foo
+https://www.twitch.tv/foo
+1x :electrumchest:
+Expires: January 26th, 2024";
+
+fn time_parser(c: &mut Criterion) {
+    c.bench_function("TimeParser::new", |b| b.iter(TimeParser::new));
+
+    let parser = TimeParser::new();
+    c.bench_function("TimeParser::parse", |b| {
+        b.iter(|| {
+            for expiry in EXPIRIES {
+                black_box(block_on(parser.parse(black_box(expiry.to_string()), true)));
+            }
+        })
+    });
+}
+
+fn code_extraction(c: &mut Criterion) {
+    c.bench_function("find_codes", |b| b.iter(|| find_codes(black_box(MESSAGE))));
+    c.bench_function("find_creator", |b| {
+        b.iter(|| find_creator(black_box(MESSAGE)))
+    });
+}
+
+criterion_group!(benches, time_parser, code_extraction);
+criterion_main!(benches);
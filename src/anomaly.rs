@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+/// Runs of a source the parse rate is tracked over.
+const HISTORY_RUNS: usize = 20;
+
+/// Latest runs compared against the ones before them.
+const RECENT_RUNS: usize = 3;
+
+/// Items parsed or failed a set of runs needs before its rate means anything.
+const MIN_ITEMS: usize = 10;
+
+/// How much of what a source posted parsed in its recent runs, to notice when its message format changed, e.g. a
+/// relay bot started formatting codes differently and every message fails to parse.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ParseHistory {
+    /// Codes parsed and items that failed to parse per run, oldest first; runs with neither are not kept
+    pub runs: VecDeque<(usize, usize)>,
+    /// Whether the current drop was notified about, until the rate recovers
+    #[serde(default)]
+    pub alerted: bool,
+}
+
+impl ParseHistory {
+    /// Adds the outcome of a run, returning the usual and the recent rate if the rate dropped by `drop` or more
+    /// and that was not notified about yet.
+    pub fn record(&mut self, parsed: usize, failed: usize, drop: f64) -> Option<(f64, f64)> {
+        if parsed + failed == 0 {
+            return None;
+        }
+
+        self.runs.push_back((parsed, failed));
+        while self.runs.len() > HISTORY_RUNS {
+            self.runs.pop_front();
+        }
+
+        if drop <= 0.0 || self.runs.len() <= RECENT_RUNS {
+            return None;
+        }
+
+        let split = self.runs.len() - RECENT_RUNS;
+        let (Some(usual), Some(recent)) = (
+            rate(self.runs.iter().take(split)),
+            rate(self.runs.iter().skip(split)),
+        ) else {
+            return None;
+        };
+
+        if usual - recent < drop {
+            self.alerted = false;
+            return None;
+        }
+        if std::mem::replace(&mut self.alerted, true) {
+            return None;
+        }
+
+        Some((usual, recent))
+    }
+}
+
+fn rate<'a>(runs: impl Iterator<Item = &'a (usize, usize)>) -> Option<f64> {
+    let (parsed, failed) = runs.fold((0, 0), |(p, f), (parsed, failed)| (p + parsed, f + failed));
+
+    match parsed + failed {
+        total if total < MIN_ITEMS => None,
+        total => Some(parsed as f64 / total as f64),
+    }
+}
+
+/// The notification about a drop returned by [`ParseHistory::record`].
+pub fn message(source: &str, usual: f64, recent: f64) -> String {
+    format!(
+        "source={} parses {:.0}% of what it posts lately, down from {:.0}%; the format of its messages (or of the bot relaying them) probably changed",
+        source,
+        recent * 100.0,
+        usual * 100.0
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut history = ParseHistory::default();
+
+        for _ in 0..10 {
+            assert_eq!(history.record(9, 1, 0.5), None);
+        }
+        // runs without anything to parse do not count
+        assert_eq!(history.record(0, 0, 0.5), None);
+        assert_eq!(history.runs.len(), 10);
+
+        // 90% to 63%
+        assert_eq!(history.record(1, 9, 0.5), None);
+
+        let (usual, recent) = history.record(1, 9, 0.5).unwrap();
+        assert!((usual - 0.9).abs() < 1e-9);
+        assert!((recent - 11.0 / 30.0).abs() < 1e-9);
+
+        // only notified once
+        assert_eq!(history.record(1, 9, 0.5), None);
+        assert!(history.alerted);
+
+        for _ in 0..3 {
+            history.record(9, 1, 0.5);
+        }
+        assert!(!history.alerted);
+    }
+
+    #[test]
+    fn test_record_disabled() {
+        let mut history = ParseHistory::default();
+
+        for _ in 0..5 {
+            history.record(10, 0, 0.0);
+        }
+        for _ in 0..3 {
+            assert_eq!(history.record(0, 10, 0.0), None);
+        }
+    }
+
+    #[test]
+    fn test_record_too_few_items() {
+        let mut history = ParseHistory::default();
+
+        for _ in 0..5 {
+            history.record(1, 0, 0.5);
+        }
+        for _ in 0..3 {
+            assert_eq!(history.record(0, 1, 0.5), None);
+        }
+    }
+}
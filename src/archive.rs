@@ -0,0 +1,291 @@
+use crate::config::dir;
+use crate::creator::{Confidence, Derivation};
+use crate::export::Format;
+use crate::output::Metadata;
+use crate::storage;
+use licc::write::{InsertCodeRequest, SourceLookup};
+use std::collections::HashSet;
+use std::io::Write;
+
+/// A code as first seen, appended to `archive.jsonl` and kept after it leaves the cache.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Record {
+    pub code: String,
+    pub source: String,
+    /// Unix timestamp of the run that first found it
+    pub seen_at: u64,
+    pub expires_at: u64,
+    pub creator_name: String,
+    pub creator_url: String,
+    pub submitter_name: Option<String>,
+    pub submitter_url: Option<String>,
+    pub reward: Option<String>,
+    /// The only platform it can be redeemed on
+    pub platform: Option<String>,
+    /// Whether every output accepted it
+    pub stored: bool,
+    /// ID the remote assigned to the code
+    pub api_id: Option<i32>,
+    /// How the creator was worked out, for sources that had to guess
+    #[serde(default)]
+    pub attribution: Option<Derivation>,
+    #[serde(default)]
+    pub confidence: Option<Confidence>,
+}
+
+impl Record {
+    pub fn new(
+        source: &str,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+        stored: bool,
+        api_id: Option<i32>,
+    ) -> Self {
+        Self {
+            code: request.code.clone(),
+            source: source.to_string(),
+            seen_at: time::OffsetDateTime::now_utc().unix_timestamp() as u64,
+            expires_at: request.expires_at,
+            creator_name: request.creator.name.clone(),
+            creator_url: request.creator.url.clone(),
+            submitter_name: request.submitter.as_ref().map(|s| s.name.clone()),
+            submitter_url: request.submitter.as_ref().map(|s| s.url.clone()),
+            reward: metadata.reward.clone(),
+            platform: metadata.platform.clone(),
+            stored,
+            api_id,
+            attribution: metadata.attribution.as_ref().map(|a| a.derivation),
+            confidence: metadata.attribution.as_ref().map(|a| a.confidence),
+        }
+    }
+
+    pub fn to_request(&self) -> InsertCodeRequest {
+        InsertCodeRequest {
+            code: self.code.clone(),
+            expires_at: self.expires_at,
+            creator: SourceLookup {
+                name: self.creator_name.clone(),
+                url: self.creator_url.clone(),
+            },
+            submitter: match (&self.submitter_name, &self.submitter_url) {
+                (Some(name), Some(url)) => Some(SourceLookup {
+                    name: name.clone(),
+                    url: url.clone(),
+                }),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Narrows `liccrawler history` down, every filter is optional.
+#[derive(Debug, Default, clap::Args)]
+pub struct Filter {
+    /// Only codes by creators whose name or url contains this, case insensitive
+    #[arg(long)]
+    pub creator: Option<String>,
+    /// Only codes from this source, e.g. `discord.default`
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Only codes first seen on or after this date, e.g. `2024-01-01`
+    #[arg(long, value_parser = parse_date)]
+    pub since: Option<u64>,
+}
+
+impl Filter {
+    fn matches(&self, record: &Record) -> bool {
+        let creator = self.creator.as_ref().map(|c| c.to_lowercase());
+
+        creator.is_none_or(|creator| {
+            record.creator_name.to_lowercase().contains(&creator)
+                || record.creator_url.to_lowercase().contains(&creator)
+        }) && self.source.as_ref().is_none_or(|s| s == &record.source)
+            && self.since.is_none_or(|since| record.seen_at >= since)
+    }
+}
+
+/// `YYYY-MM-DD` as the unix timestamp of its midnight in UTC.
+fn parse_date(date: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' is not a YYYY-MM-DD date", date);
+
+    let parts: Vec<&str> = date.trim().split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+
+    let month: u8 = month.parse().map_err(|_| invalid())?;
+    let date = time::Date::from_calendar_date(
+        year.parse().map_err(|_| invalid())?,
+        time::Month::try_from(month).map_err(|_| invalid())?,
+        day.parse().map_err(|_| invalid())?,
+    )
+    .map_err(|_| invalid())?;
+
+    Ok(date.midnight().assume_utc().unix_timestamp().max(0) as u64)
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("archive.jsonl")
+}
+
+pub fn read() -> Vec<Record> {
+    let contents = match storage::read_to_string(&file()) {
+        Ok(contents) => contents,
+        Err(_) => return vec![],
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| error!("Skipping unreadable line in archive.jsonl: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+/// Codes in the archive, which are not archived again.
+pub fn codes() -> HashSet<String> {
+    read().into_iter().map(|record| record.code).collect()
+}
+
+pub fn append(records: &[Record]) {
+    if records.is_empty() {
+        return;
+    }
+
+    let lines: String = records
+        .iter()
+        .filter_map(|record| serde_json::to_string(record).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    let written = storage::append(&file(), &lines);
+
+    if let Err(e) = written {
+        error!("Error writing archive.jsonl: {}", e);
+    }
+}
+
+/// Writes the archived codes matching `filter` to stdout, oldest first.
+pub fn run(filter: Filter, format: Format) {
+    let mut records: Vec<Record> = read()
+        .into_iter()
+        .filter(|record| filter.matches(record))
+        .collect();
+    records.sort_by(|a, b| a.seen_at.cmp(&b.seen_at).then(a.code.cmp(&b.code)));
+
+    let out = std::io::stdout();
+    let result = match format {
+        Format::Csv => write_csv(&records, out.lock()),
+        Format::Json => write_json(&records, out.lock()),
+    };
+
+    if let Err(e) = result {
+        error!("Error writing history: {}", e);
+    }
+}
+
+fn write_csv(records: &[Record], out: impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    for record in records {
+        writer.serialize(record)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_json(records: &[Record], mut out: impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    serde_json::to_writer_pretty(&mut out, records)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::output::Payload;
+
+    fn record(code: &str, creator: &str, source: &str, seen_at: u64) -> Record {
+        let request = InsertCodeRequest {
+            code: code.to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: creator.to_string(),
+                url: format!("https://www.twitch.tv/{}", creator.to_lowercase()),
+            },
+            submitter: None,
+        };
+
+        Record {
+            seen_at,
+            ..Record::new(source, &request, &Metadata::default(), true, Some(1))
+        }
+    }
+
+    #[test]
+    fn test_to_request() {
+        let request = InsertCodeRequest {
+            code: "CODE-AAAA-BBBB".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "Foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: Some(SourceLookup {
+                name: "Bar".to_string(),
+                url: "https://discord.com/channels/1/2/3".to_string(),
+            }),
+        };
+
+        let record = Record::new(
+            "discord.default",
+            &request,
+            &Metadata::default(),
+            true,
+            None,
+        );
+        assert_eq!(Payload::from(&record.to_request()), Payload::from(&request));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2024-01-26"), Ok(1706227200));
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("26-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let old = record("CODE-AAAA-BBBB", "Foo", "discord.default", 1704067200);
+        let new = record("CODE-AAAA-CCCC", "Bar", "github.default", 1706227200);
+
+        let filter = Filter {
+            creator: Some("foo".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&old));
+        assert!(!filter.matches(&new));
+
+        let filter = Filter {
+            since: parse_date("2024-01-26").ok(),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&old));
+        assert!(filter.matches(&new));
+
+        let filter = Filter {
+            source: Some("discord.default".to_string()),
+            since: parse_date("2024-01-01").ok(),
+            ..Default::default()
+        };
+        assert!(filter.matches(&old));
+        assert!(!filter.matches(&new));
+    }
+}
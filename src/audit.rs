@@ -0,0 +1,163 @@
+use crate::config::dir;
+use crate::output::{OutputError, Payload};
+use crate::storage;
+use licc::write::InsertCodeRequest;
+
+/// One submission of one code to one sink, appended to `audit.jsonl` and never rewritten.
+#[derive(Debug, serde::Serialize)]
+pub struct Entry<'a> {
+    /// Unix timestamp of the submission
+    pub at: u64,
+    pub source: &'a str,
+    pub sink: &'a str,
+    pub code: &'a str,
+    /// FNV-1a of the JSON payload, to compare submissions at a glance
+    pub payload_hash: String,
+    pub request: Payload,
+    /// ID the remote assigned to the code
+    pub id: Option<i32>,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl<'a> Entry<'a> {
+    pub fn new(
+        source: &'a str,
+        sink: &'a str,
+        request: &'a InsertCodeRequest,
+        result: &Result<Option<i32>, OutputError>,
+    ) -> Self {
+        let payload = Payload::from(request);
+        let bytes = serde_json::to_vec(&payload).unwrap_or_default();
+
+        Self {
+            at: time::OffsetDateTime::now_utc().unix_timestamp() as u64,
+            source,
+            sink,
+            code: &request.code,
+            payload_hash: format!("{:016x}", fnv1a(&bytes)),
+            request: payload,
+            id: result.as_ref().ok().copied().flatten(),
+            status: result.as_ref().err().and_then(OutputError::status),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("audit.jsonl")
+}
+
+/// Appends the entry to the audit log. Failing to do so is logged, but does not stop the submission.
+pub fn record(entry: &Entry) {
+    let line = match serde_json::to_string(entry) {
+        Ok(line) => line + "\n",
+        Err(e) => {
+            error!("Error serializing audit entry for '{}': {}", entry.code, e);
+            return;
+        }
+    };
+
+    let written = storage::append(&file(), &line);
+
+    if let Err(e) = written {
+        error!("Error writing audit entry for '{}': {}", entry.code, e);
+    }
+}
+
+/// The owned side of [`Entry`], only what is needed to submit a code again.
+#[derive(Debug, serde::Deserialize)]
+struct Logged {
+    source: String,
+    request: Payload,
+}
+
+/// The source and request of the last submission of `code` in the audit log.
+pub fn find(code: &str) -> Option<(String, InsertCodeRequest)> {
+    let contents = storage::read_to_string(&file()).ok()?;
+
+    find_in(&contents, code)
+}
+
+fn find_in(contents: &str, code: &str) -> Option<(String, InsertCodeRequest)> {
+    contents
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Logged>(line).ok())
+        .find(|logged| logged.request.code == code)
+        .map(|logged| (logged.source, logged.request.into()))
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_entry() {
+        let request = InsertCodeRequest {
+            code: "CODE-AAAA-BBBB".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+
+        let ok = Entry::new("discord.default", "licc", &request, &Ok(Some(5)));
+        assert_eq!(ok.id, Some(5));
+        assert_eq!(ok.error, None);
+        assert_eq!(ok.payload_hash.len(), 16);
+
+        let failed = Entry::new(
+            "discord.default",
+            "licc",
+            &request,
+            &Err(OutputError::Licc("409 Conflict".to_string())),
+        );
+        assert_eq!(failed.id, None);
+        assert_eq!(failed.error.as_deref(), Some("licc: 409 Conflict"));
+        assert_eq!(failed.payload_hash, ok.payload_hash);
+    }
+
+    #[test]
+    fn test_find_in() {
+        let request = InsertCodeRequest {
+            code: "CODE-AAAA-BBBB".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+        let first = Entry::new("import", "licc", &request, &Ok(None));
+        let mut later = request.clone();
+        later.expires_at = 1706313600;
+        let second = Entry::new("discord.default", "licc", &later, &Ok(Some(5)));
+
+        let contents = [&first, &second]
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (source, found) = find_in(&contents, "CODE-AAAA-BBBB").unwrap();
+        assert_eq!(source, "discord.default");
+        assert_eq!(found.expires_at, 1706313600);
+        assert!(find_in(&contents, "CODE-AAAA-CCCC").is_none());
+    }
+}
@@ -1,68 +1,673 @@
 use crate::config::dir;
+use crate::handler::Listed;
+use crate::migrate;
 
-use once_cell::sync::OnceCell;
-use std::collections::HashMap;
-use std::time::SystemTime;
-const CACHE_LIMIT: u32 = 200;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+/// How long after its expiry a code stays cached, in case sources still post it with a late or skewed expiry.
+const EXPIRY_GRACE: u64 = 60 * 60 * 24;
 
-static NOW: OnceCell<u64> = OnceCell::new();
+static NOW: AtomicU64 = AtomicU64::new(0);
 
-static NEXT_TTL: OnceCell<u64> = OnceCell::new();
+static NEXT_TTL: AtomicU64 = AtomicU64::new(0);
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// Number of codes kept before the oldest are evicted, see `cache.limit` in the config.
+static LIMIT: AtomicUsize = AtomicUsize::new(5000);
+
+/// Set by `--cache-path`, see [`set_path`].
+static PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(Default::default);
+
+/// The cache while it is kept in memory instead of on disk, see [`keep_in_memory`].
+static MEMORY: Lazy<Mutex<Option<Cache>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Cache {
-    pub items: HashMap<String, u64>,
+    /// Schema version, see `migrate`
+    #[serde(default)]
+    pub version: u32,
+    #[serde(deserialize_with = "deserialize_items")]
+    pub items: HashMap<String, CacheEntry>,
+    /// The items as [`read`], so [`write`] only applies what changed since to the cache as it is by then
+    #[serde(skip)]
+    read: Option<HashMap<String, CacheEntry>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CacheEntry {
+    /// Unix timestamp after which the code may be submitted again, see [`Cache::insert`]
+    pub ttl: u64,
+    /// Where the code was found, e.g. `discord` or `import`
+    #[serde(default)]
+    pub source: String,
+    #[serde(default)]
+    pub submitted_at: u64,
+    #[serde(default)]
+    pub expires_at: u64,
+    /// ID the remote assigned to the code, if it told us
+    pub api_id: Option<i32>,
+    /// Whether `expires_at` is the fallback for codes posted without an expiry, to be corrected once one is posted
+    #[serde(default)]
+    pub expiry_guessed: bool,
+    /// Outcome per output, by name, e.g. `licc`. Codes cached without any were stored by every output.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub remotes: BTreeMap<String, RemoteState>,
+    /// Channel and message ID the code was posted in, for sources that can delete messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<(u64, u64)>,
+    /// Whether the message was deleted and the code expired in the outputs because of it
+    #[serde(default)]
+    pub retracted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteStatus {
+    Stored,
+    /// Submitted again next run, to this output only
+    Failed,
 }
 
-fn file() -> std::path::PathBuf {
-    dir().join("cache.toml")
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RemoteState {
+    pub status: RemoteStatus,
+    /// Unix timestamp of the last submission to this output
+    pub at: u64,
+    /// ID this output assigned to the code, if it told us
+    pub api_id: Option<i32>,
+}
+
+impl CacheEntry {
+    /// Whether every output the code was submitted to stored it.
+    pub fn is_stored(&self) -> bool {
+        self.remotes
+            .values()
+            .all(|remote| remote.status == RemoteStatus::Stored)
+    }
+
+    /// The ID `remote` assigned, or any output did if it did not say.
+    pub fn api_id_in(&self, remote: &str) -> Option<i32> {
+        self.remotes
+            .get(remote)
+            .and_then(|state| state.api_id)
+            .or(self.api_id)
+    }
+}
+
+/// Caches written before entries carried any history only stored the TTL per code.
+fn deserialize_items<'de, D>(deserializer: D) -> Result<HashMap<String, CacheEntry>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum StoredEntry {
+        Ttl(u64),
+        Entry(CacheEntry),
+    }
+
+    let stored: HashMap<String, StoredEntry> = serde::Deserialize::deserialize(deserializer)?;
+
+    Ok(stored
+        .into_iter()
+        .map(|(code, entry)| {
+            let entry = match entry {
+                StoredEntry::Ttl(ttl) => CacheEntry {
+                    ttl,
+                    ..Default::default()
+                },
+                StoredEntry::Entry(entry) => entry,
+            };
+
+            (code, entry)
+        })
+        .collect())
+}
+
+fn file() -> PathBuf {
+    PATH.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| dir().join("cache.toml"))
+}
+
+/// Reads and writes the cache at `path` instead of `cache.toml` in the data directory, see `--cache-path`.
+pub fn set_path(path: PathBuf) {
+    *PATH.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+}
+
+/// Never touches the cache on disk, it starts empty and lasts as long as the process, see `--memory-cache`.
+pub fn keep_in_memory() {
+    MEMORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Cache::default);
+}
+
+/// Sets the clock used for TTL calculations, called again at the start of every daemon cycle.
+pub fn init() {
+    let n = now();
+    NOW.store(n, Ordering::Relaxed);
+    NEXT_TTL.store(n + 60 * 60 * 24 * 7, Ordering::Relaxed);
+}
+
+/// Sets the number of codes kept, called whenever the config is (re)loaded.
+pub fn set_limit(limit: usize) {
+    LIMIT.store(limit.max(1), Ordering::Relaxed);
 }
 
 pub fn setup() {
-    NOW.set(now()).unwrap();
-    NEXT_TTL
-        .set(*NOW.get().unwrap() + 60 * 60 * 24 * 7)
-        .unwrap();
+    init();
 
+    if MEMORY.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+        return;
+    }
+
+    // Nobody else is writing a cache that does not exist yet, so there is no lock to wait for.
     let cache = file();
     if !cache.exists() {
-        write(Cache {
-            items: HashMap::new(),
-        });
+        write_locked(Cache::default());
     }
 }
 
+/// Reads the cache, migrating older formats; an unreadable cache is moved aside and replaced by an empty one.
 pub fn read() -> Cache {
-    let cfg = std::fs::read_to_string(file()).unwrap();
-    let cache: Cache = toml::from_str(&cfg).unwrap();
+    let mut cache = stored();
 
+    if !in_memory() {
+        if let Ok(journal) = std::fs::read_to_string(journal_file()) {
+            let replayed = replay(&mut cache, &journal);
+            info!(
+                "Recovered {} codes from the journal of a run that did not finish",
+                replayed
+            );
+        }
+    }
+
+    cache.read = Some(cache.items.clone());
     cache
 }
 
-pub fn write(cache: Cache) {
-    std::fs::write(dir().join("cache.toml"), toml::to_string(&cache).unwrap()).unwrap();
+/// The cache as last written, for queries from outside the run, e.g. `/recent-codes`.
+///
+/// Unlike [`read`] this has no side effects: the journal of a run in progress is not replayed (so its codes show
+/// once the run wrote the cache), the clock is left alone and an unreadable cache is not moved aside.
+pub fn peek() -> Cache {
+    if let Some(cache) = MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return cache.clone();
+    }
+
+    let Ok(contents) = std::fs::read_to_string(file()) else {
+        return Cache::default();
+    };
+
+    parse(&contents).unwrap_or_else(|e| {
+        debug!("Error reading {}: {}", file().display(), e);
+        Cache::default()
+    })
+}
+
+/// The cache as written, in memory or on disk.
+fn stored() -> Cache {
+    if let Some(cache) = MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return cache.clone();
+    }
+
+    let contents = match std::fs::read_to_string(file()) {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!(
+                "Error reading {}, starting with an empty cache: {}",
+                file().display(),
+                e
+            );
+            return Cache::default();
+        }
+    };
+
+    match parse(&contents) {
+        Ok(cache) => cache,
+        Err(e) => {
+            let backup = file().with_extension(format!("toml.{}.bak", now()));
+            error!(
+                "Error reading cache.toml, starting with an empty cache. The old one was moved to {}: {}",
+                backup.display(),
+                e
+            );
+            if let Err(e) = std::fs::rename(file(), &backup) {
+                error!("Error moving cache.toml aside: {}", e);
+            }
+
+            Cache::default()
+        }
+    }
+}
+
+fn journal_file() -> PathBuf {
+    file().with_extension("journal")
+}
+
+/// Appends the entry of `code` to the journal next to the cache, so a code stored during a run is remembered even
+/// if the process dies before the cache is written. The journal is replayed by [`read`] and cleared by [`write`].
+pub fn journal(cache: &Cache, code: &str) {
+    let Some(entry) = cache.items.get(code) else {
+        return;
+    };
+
+    if in_memory() {
+        return;
+    }
+
+    let line = serde_json::to_string(&Journaled {
+        code: code.to_string(),
+        entry: entry.clone(),
+    });
+    let written = line.map_err(|e| e.to_string()).and_then(|line| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(journal_file())
+            .and_then(|mut f| f.write_all(format!("{}\n", line).as_bytes()))
+            .map_err(|e| e.to_string())
+    });
+
+    if let Err(e) = written {
+        error!("Error writing {}: {}", journal_file().display(), e);
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Journaled {
+    code: String,
+    entry: CacheEntry,
+}
+
+/// Applies the journaled entries to `cache`, returning how many; a line cut off by a crash is skipped.
+fn replay(cache: &mut Cache, journal: &str) -> usize {
+    let mut replayed = 0;
+
+    for line in journal.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Journaled>(line) {
+            Ok(journaled) => {
+                cache.items.insert(journaled.code, journaled.entry);
+                replayed += 1;
+            }
+            Err(e) => warn!("Skipping unreadable line in the cache journal: {}", e),
+        }
+    }
+
+    replayed
+}
+
+fn parse(contents: &str) -> Result<Cache, toml::de::Error> {
+    let mut table: toml::Table = toml::from_str(contents)?;
+    migrate::cache(&mut table);
+
+    serde::Deserialize::deserialize(toml::Value::Table(table))
+}
+
+/// Writes the cache to disk, or keeps it in memory for the rest of the process if that fails, e.g. on a read-only
+/// filesystem, so codes are still not submitted twice while running.
+///
+/// Only what changed since the cache was [`read`] is written, anything another process (e.g. `liccrawler dlq retry`
+/// next to the daemon) wrote in the meantime is kept.
+pub async fn write(cache: Cache) {
+    let _lock = match in_memory() {
+        true => None,
+        false => Lock::acquire().await,
+    };
+
+    write_locked(merged(cache));
+}
+
+/// `cache` applied to the cache as it is now, for callers holding the [`Lock`].
+fn merged(mut cache: Cache) -> Cache {
+    let Some(read) = cache.read.take() else {
+        return cache;
+    };
+
+    let mut current = stored();
+    if !in_memory() {
+        // Codes journaled by a process that is still running, which its write would otherwise not bring back.
+        if let Ok(journal) = std::fs::read_to_string(journal_file()) {
+            replay(&mut current, &journal);
+        }
+    }
+
+    current.merge(&read, cache.items);
+    current
+}
+
+/// [`write`], for callers already holding the [`Lock`].
+fn write_locked(mut cache: Cache) {
+    cache.version = migrate::CACHE_VERSION;
+
+    let mut memory = MEMORY.lock().unwrap_or_else(|e| e.into_inner());
+    if memory.is_some() {
+        *memory = Some(cache);
+        return;
+    }
+
+    // Written next to it and moved over it, so readers never see half a cache.
+    let temporary = file().with_extension("toml.tmp");
+    let written = toml::to_string(&cache)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| std::fs::write(&temporary, contents).map_err(|e| e.to_string()))
+        .and_then(|_| std::fs::rename(&temporary, file()).map_err(|e| e.to_string()));
+
+    match written {
+        Ok(()) => {
+            debug!("Cache written to disk");
+            // Everything journaled is in the file now.
+            if let Err(e) = std::fs::remove_file(journal_file()) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!("Error removing {}: {}", journal_file().display(), e);
+                }
+            }
+        }
+        Err(e) => {
+            error!(
+                "Error writing {}, keeping the cache in memory until exit: {}",
+                file().display(),
+                e
+            );
+            *memory = Some(cache);
+        }
+    }
+}
+
+fn in_memory() -> bool {
+    MEMORY.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// How long to wait for another process to finish writing the cache before writing anyway.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A lock file older than this was left behind by a process that died while writing.
+const LOCK_STALE: Duration = Duration::from_secs(30);
+
+/// Held while the cache file is read to merge into and written, so a running daemon and `liccrawler cache compact`
+/// take turns.
+struct Lock(PathBuf);
+
+impl Lock {
+    /// Waits for the lock, `None` if it cannot be taken (e.g. on a read-only filesystem) or it timed out.
+    ///
+    /// Waits asynchronously, so sources and the HTTP server sharing the runtime keep going meanwhile.
+    async fn acquire() -> Option<Lock> {
+        let path = file().with_extension("toml.lock");
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+
+        loop {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Some(Lock(path)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(&path)
+                        .and_then(|meta| meta.modified())
+                        .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > LOCK_STALE));
+
+                    if stale {
+                        warn!("Removing stale lock {}", path.display());
+                        let _ = std::fs::remove_file(&path);
+                    } else if Instant::now() > deadline {
+                        error!(
+                            "Timed out waiting for {}, writing the cache anyway",
+                            path.display()
+                        );
+                        return None;
+                    } else {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                }
+                Err(e) => {
+                    debug!("Cannot lock the cache: {}", e);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Action {
+    /// Drop expired codes and rewrite the cache file, safe to run while the daemon is running as its writes keep
+    /// the codes dropped
+    Compact,
+}
+
+pub async fn run(action: Action) {
+    match action {
+        Action::Compact => compact().await,
+    }
+}
+
+async fn compact() {
+    if in_memory() {
+        println!("The cache is kept in memory, there is nothing to compact.");
+        return;
+    }
+
+    setup();
+    let _lock = Lock::acquire().await;
+
+    let size = || {
+        std::fs::metadata(file())
+            .map(|meta| meta.len())
+            .unwrap_or(0)
+    };
+    let before = size();
 
-    debug!("Cache written to disk");
+    let mut cache = read();
+    let codes = cache.items.len();
+    let removed = cache.compact(now());
+    write_locked(cache);
+
+    println!(
+        "Removed {} of {} codes, {} went from {} to {} bytes.",
+        removed,
+        codes,
+        file().display(),
+        before,
+        size()
+    );
 }
 
 impl Cache {
+    /// Applies the changes from `read` to `items` to this cache: codes removed since are removed, codes added or
+    /// changed since replace whatever this cache has. Codes left as they were keep this cache's entry.
+    fn merge(&mut self, read: &HashMap<String, CacheEntry>, items: HashMap<String, CacheEntry>) {
+        for code in read.keys().filter(|code| !items.contains_key(*code)) {
+            self.items.remove(code);
+        }
+
+        for (code, entry) in items {
+            if read.get(&code) != Some(&entry) {
+                self.items.insert(code, entry);
+            }
+        }
+    }
+
+    /// Drops codes that expired (plus the grace period) or whose TTL passed, returning how many.
+    pub fn compact(&mut self, now: u64) -> usize {
+        let before = self.items.len();
+
+        self.items.retain(|_, entry| {
+            entry.ttl > now
+                && (entry.expires_at == 0 || entry.expires_at.saturating_add(EXPIRY_GRACE) > now)
+        });
+
+        before - self.items.len()
+    }
+
+    /// Whether the code is cached and every output stored it, so it need not be submitted again.
     pub fn has(&self, code: &str) -> bool {
         match self.items.get(code) {
             Some(item) => match self.now() {
-                Some(n) => n.lt(item),
+                Some(n) => n.lt(&item.ttl) && item.is_stored(),
                 None => false,
             },
             None => false,
         }
     }
 
-    pub fn insert(&mut self, code: String) {
-        if self.items.len() as u32 >= CACHE_LIMIT {
-            self.items
-                .remove(&self.items.keys().next().unwrap().to_string());
+    /// Caches the code until `EXPIRY_GRACE` after it expires, and for at least a week.
+    pub fn insert(&mut self, code: String, source: &str, expires_at: u64, api_id: Option<i32>) {
+        // Replacing an entry does not grow the cache, so nothing else has to make room for it.
+        if !self.items.contains_key(&code) {
+            self.evict(LIMIT.load(Ordering::Relaxed).saturating_sub(1));
         }
 
-        self.items.insert(code.clone(), *NEXT_TTL.get().unwrap());
+        self.items.insert(
+            code,
+            CacheEntry {
+                ttl: expires_at
+                    .saturating_add(EXPIRY_GRACE)
+                    .max(NEXT_TTL.load(Ordering::Relaxed)),
+                source: source.to_string(),
+                submitted_at: NOW.load(Ordering::Relaxed),
+                expires_at,
+                api_id,
+                expiry_guessed: false,
+                remotes: BTreeMap::new(),
+                message: None,
+                retracted: false,
+            },
+        );
+    }
+
+    /// Records the outcome of submitting the code to one output, caching it if it is not (any more).
+    pub fn record(
+        &mut self,
+        code: &str,
+        source: &str,
+        expires_at: u64,
+        remote: &str,
+        status: RemoteStatus,
+        api_id: Option<i32>,
+    ) {
+        let live = self
+            .items
+            .get(code)
+            .is_some_and(|entry| self.now().is_some_and(|n| n < entry.ttl));
+        if !live {
+            self.insert(code.to_string(), source, expires_at, None);
+        }
+
+        if let Some(entry) = self.items.get_mut(code) {
+            entry.api_id = entry.api_id.or(api_id);
+            entry.remotes.insert(
+                remote.to_string(),
+                RemoteState {
+                    status,
+                    at: NOW.load(Ordering::Relaxed),
+                    api_id,
+                },
+            );
+        }
+    }
+
+    /// `(code, output)` for every output that stored a cached code, which are skipped when it is submitted again.
+    pub fn stored_remotes(&self) -> HashSet<(String, String)> {
+        self.items
+            .iter()
+            .flat_map(|(code, entry)| {
+                entry
+                    .remotes
+                    .iter()
+                    .filter(|(_, state)| state.status == RemoteStatus::Stored)
+                    .map(|(remote, _)| (code.clone(), remote.clone()))
+            })
+            .collect()
+    }
+
+    pub fn mark_guessed(&mut self, code: &str) {
+        if let Some(entry) = self.items.get_mut(code) {
+            entry.expiry_guessed = true;
+        }
+    }
+
+    /// The entry of `code` if it was stored with a guessed expiry that `expires_at` would correct.
+    pub fn outdated_expiry(&self, code: &str, expires_at: u64) -> Option<&CacheEntry> {
+        self.items
+            .get(code)
+            .filter(|entry| entry.expiry_guessed && entry.expires_at != expires_at)
+    }
+
+    /// Replaces a guessed expiry with the posted one, keeping the code cached until after it.
+    pub fn correct_expiry(&mut self, code: &str, expires_at: u64) {
+        if let Some(entry) = self.items.get_mut(code) {
+            entry.expires_at = expires_at;
+            entry.expiry_guessed = false;
+            entry.ttl = entry.ttl.max(expires_at.saturating_add(EXPIRY_GRACE));
+        }
+    }
+
+    /// Remembers the message `code` was posted in, see [`Cache::deleted`].
+    pub fn set_message(&mut self, code: &str, channel_id: u64, message_id: u64) {
+        if let Some(entry) = self.items.get_mut(code) {
+            entry.message.get_or_insert((channel_id, message_id));
+        }
+    }
+
+    /// Unexpired codes whose message was missing from the messages fetched this run, as it was deleted.
+    pub fn deleted(&self, listed: &HashMap<u64, Listed>, now: u64) -> Vec<String> {
+        let mut deleted: Vec<String> = self
+            .items
+            .iter()
+            .filter(|(_, entry)| !entry.retracted && entry.expires_at > now)
+            .filter(|(_, entry)| {
+                entry.message.is_some_and(|(channel_id, message_id)| {
+                    listed
+                        .get(&channel_id)
+                        .is_some_and(|listed| listed.deleted(message_id))
+                })
+            })
+            .map(|(code, _)| code.clone())
+            .collect();
+        deleted.sort();
+
+        deleted
+    }
+
+    /// Marks `code` as expired at `now` as its message was deleted, keeping it cached so it is not submitted again.
+    pub fn retract(&mut self, code: &str, now: u64) {
+        if let Some(entry) = self.items.get_mut(code) {
+            entry.retracted = true;
+            entry.expires_at = now;
+        }
+    }
+
+    /// Removes the least recently submitted codes until at most `limit` remain.
+    fn evict(&mut self, limit: usize) {
+        if self.items.len() <= limit {
+            return;
+        }
+
+        let mut oldest: Vec<(u64, String)> = self
+            .items
+            .iter()
+            .map(|(code, entry)| (entry.submitted_at, code.clone()))
+            .collect();
+        oldest.sort();
+
+        for (_, code) in oldest.into_iter().take(self.items.len() - limit) {
+            debug!("Evicting '{}' from the cache, it is full", code);
+            self.items.remove(&code);
+        }
     }
 
     pub fn bust(&mut self) {
@@ -72,14 +677,17 @@ impl Cache {
         };
 
         for (key, value) in self.items.clone() {
-            if value.lt(&n) {
+            if value.ttl.lt(&n) {
                 self.items.remove(&key);
             }
         }
     }
 
     fn now(&self) -> Option<u64> {
-        NOW.get().map(|n| *n)
+        match NOW.load(Ordering::Relaxed) {
+            0 => None,
+            n => Some(n),
+        }
     }
 }
 
@@ -89,3 +697,339 @@ fn now() -> u64 {
         .unwrap()
         .as_secs()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Held by tests that set the clock or keep the cache in memory, which parallel tests would see change under them.
+    static GLOBALS: Mutex<()> = Mutex::new(());
+
+    fn globals() -> std::sync::MutexGuard<'static, ()> {
+        GLOBALS.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[test]
+    fn test_insert_ttl_follows_expiry() {
+        let _globals = globals();
+        init();
+        let week = NEXT_TTL.load(Ordering::Relaxed);
+        let mut cache = Cache::default();
+
+        cache.insert("CODE-AAAA-BBBB".to_string(), "test", week + 1000, None);
+        assert_eq!(
+            cache.items["CODE-AAAA-BBBB"].ttl,
+            week + 1000 + EXPIRY_GRACE
+        );
+        assert!(cache.has("CODE-AAAA-BBBB"));
+
+        cache.insert("CODE-AAAA-CCCC".to_string(), "test", 0, None);
+        assert_eq!(cache.items["CODE-AAAA-CCCC"].ttl, week);
+
+        cache.insert("CODE-AAAA-DDDD".to_string(), "test", u64::MAX, None);
+        assert_eq!(cache.items["CODE-AAAA-DDDD"].ttl, u64::MAX);
+    }
+
+    #[test]
+    fn test_correct_expiry() {
+        let _globals = globals();
+        init();
+        let week = NEXT_TTL.load(Ordering::Relaxed);
+        let mut cache = Cache::default();
+
+        cache.insert("CODE-AAAA-BBBB".to_string(), "test", week, Some(1));
+        assert!(cache
+            .outdated_expiry("CODE-AAAA-BBBB", week + 1000)
+            .is_none());
+
+        cache.mark_guessed("CODE-AAAA-BBBB");
+        assert!(cache.outdated_expiry("CODE-AAAA-BBBB", week).is_none());
+        assert_eq!(
+            cache
+                .outdated_expiry("CODE-AAAA-BBBB", week * 2)
+                .and_then(|entry| entry.api_id),
+            Some(1)
+        );
+
+        cache.correct_expiry("CODE-AAAA-BBBB", week * 2);
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].expires_at, week * 2);
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].ttl, week * 2 + EXPIRY_GRACE);
+        assert!(cache.outdated_expiry("CODE-AAAA-BBBB", week * 3).is_none());
+    }
+
+    #[test]
+    fn test_record_per_remote() {
+        let _globals = globals();
+        init();
+        let mut cache = Cache::default();
+
+        cache.record(
+            "CODE-AAAA-BBBB",
+            "discord",
+            0,
+            "licc",
+            RemoteStatus::Stored,
+            Some(5),
+        );
+        cache.record(
+            "CODE-AAAA-BBBB",
+            "discord",
+            0,
+            "webhook",
+            RemoteStatus::Failed,
+            None,
+        );
+        assert!(!cache.has("CODE-AAAA-BBBB"));
+        assert_eq!(
+            cache.stored_remotes(),
+            HashSet::from([("CODE-AAAA-BBBB".to_string(), "licc".to_string())])
+        );
+
+        cache.record(
+            "CODE-AAAA-BBBB",
+            "discord",
+            0,
+            "webhook",
+            RemoteStatus::Stored,
+            None,
+        );
+        assert!(cache.has("CODE-AAAA-BBBB"));
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].api_id, Some(5));
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].api_id_in("webhook"), Some(5));
+    }
+
+    #[test]
+    fn test_evict_oldest_first() {
+        let mut cache = Cache::default();
+        for (code, submitted_at) in [
+            ("NEWEST", 30),
+            ("OLDEST", 10),
+            ("LEGACY", 0),
+            ("MIDDLE", 20),
+        ] {
+            cache.items.insert(
+                code.to_string(),
+                CacheEntry {
+                    submitted_at,
+                    ..Default::default()
+                },
+            );
+        }
+
+        cache.evict(4);
+        assert_eq!(cache.items.len(), 4);
+
+        cache.evict(2);
+        let mut kept: Vec<&str> = cache.items.keys().map(String::as_str).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["MIDDLE", "NEWEST"]);
+
+        cache.evict(1);
+        assert!(cache.items.contains_key("NEWEST"));
+        assert_eq!(cache.items.len(), 1);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut cache = Cache::default();
+        let entry = |ttl, expires_at| CacheEntry {
+            ttl,
+            expires_at,
+            ..Default::default()
+        };
+        cache
+            .items
+            .insert("CODE-AAAA-BBBB".to_string(), entry(200_000, 0));
+        cache
+            .items
+            .insert("CODE-AAAA-CCCC".to_string(), entry(500, 0));
+        cache
+            .items
+            .insert("CODE-AAAA-DDDD".to_string(), entry(200_000, 100));
+        cache
+            .items
+            .insert("CODE-AAAA-EEEE".to_string(), entry(200_000, 150_000));
+
+        assert_eq!(cache.compact(100_000), 2);
+
+        let mut kept: Vec<&str> = cache.items.keys().map(String::as_str).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["CODE-AAAA-BBBB", "CODE-AAAA-EEEE"]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let entry = |ttl| CacheEntry {
+            ttl,
+            ..Default::default()
+        };
+        let read = HashMap::from([
+            ("KEPT".to_string(), entry(1)),
+            ("CHANGED".to_string(), entry(1)),
+            ("REMOVED".to_string(), entry(1)),
+            ("COMPACTED".to_string(), entry(1)),
+        ]);
+
+        // Written by another process since: KEPT changed, OTHER added and COMPACTED removed by `cache compact`.
+        let mut current = Cache::default();
+        current.items = HashMap::from([
+            ("KEPT".to_string(), entry(5)),
+            ("CHANGED".to_string(), entry(1)),
+            ("REMOVED".to_string(), entry(1)),
+            ("OTHER".to_string(), entry(1)),
+        ]);
+
+        let mut items = read.clone();
+        items.insert("CHANGED".to_string(), entry(2));
+        items.remove("REMOVED");
+        items.insert("ADDED".to_string(), entry(3));
+
+        current.merge(&read, items);
+
+        assert_eq!(
+            current.items,
+            HashMap::from([
+                ("KEPT".to_string(), entry(5)),
+                ("CHANGED".to_string(), entry(2)),
+                ("OTHER".to_string(), entry(1)),
+                ("ADDED".to_string(), entry(3)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_deleted() {
+        let mut cache = Cache::default();
+        for (code, message_id) in [
+            ("CODE-AAAA-BBBB", 10),
+            ("CODE-AAAA-CCCC", 20),
+            ("CODE-AAAA-DDDD", 5),
+        ] {
+            cache.insert(code.to_string(), "discord.default", 1706227200, None);
+            cache.set_message(code, 1, message_id);
+        }
+        cache.insert("CODE-AAAA-EEEE".to_string(), "import", 1706227200, None);
+
+        let listed = HashMap::from([(
+            1,
+            Listed {
+                oldest: 8,
+                ids: HashSet::from([8, 20, 30]),
+            },
+        )]);
+
+        // 5 is older than anything fetched, so it may just not have been fetched
+        assert_eq!(cache.deleted(&listed, 1706140800), vec!["CODE-AAAA-BBBB"]);
+        assert!(cache.deleted(&listed, 1706227200).is_empty());
+
+        cache.retract("CODE-AAAA-BBBB", 1706140800);
+        assert!(cache.deleted(&listed, 1706140800).is_empty());
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].expires_at, 1706140800);
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let _globals = globals();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        keep_in_memory();
+        setup();
+
+        let mut cache = read();
+        cache.insert("CODE-AAAA-BBBB".to_string(), "import", 1706227200, None);
+        runtime.block_on(write(cache));
+
+        assert!(read().items.contains_key("CODE-AAAA-BBBB"));
+
+        *MEMORY.lock().unwrap() = None;
+    }
+
+    #[test]
+    fn test_peek() {
+        let _globals = globals();
+        let dir = std::env::temp_dir().join(format!("liccrawler-peek-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        set_path(dir.join("cache.toml"));
+        std::fs::write(file(), "[items]\nCODE-AAAA-BBBB = 1706227200\n").unwrap();
+        let line = serde_json::to_string(&Journaled {
+            code: "CODE-CCCC-DDDD".to_string(),
+            entry: CacheEntry::default(),
+        })
+        .unwrap();
+        std::fs::write(journal_file(), format!("{}\n", line)).unwrap();
+        NOW.store(1, Ordering::Relaxed);
+
+        let cache = peek();
+        assert!(cache.items.contains_key("CODE-AAAA-BBBB"));
+        assert_eq!(cache.items.len(), 1);
+        assert_eq!(NOW.load(Ordering::Relaxed), 1);
+        assert!(journal_file().exists());
+
+        *PATH.lock().unwrap() = None;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut cache = Cache::default();
+        cache.insert("CODE-AAAA-BBBB".to_string(), "import", 1706227200, None);
+
+        let entry = CacheEntry {
+            ttl: 1706313600,
+            source: "discord.default".to_string(),
+            expires_at: 1706227200,
+            api_id: Some(7),
+            ..Default::default()
+        };
+        let line = serde_json::to_string(&Journaled {
+            code: "CODE-CCCC-DDDD".to_string(),
+            entry: entry.clone(),
+        })
+        .unwrap();
+
+        // The last line was cut off mid-write.
+        let journal = format!("{}\n{{\"code\": \"CODE-EEEE", line);
+        assert_eq!(replay(&mut cache, &journal), 1);
+        assert_eq!(cache.items.get("CODE-CCCC-DDDD"), Some(&entry));
+        assert!(cache.items.contains_key("CODE-AAAA-BBBB"));
+    }
+
+    #[test]
+    fn test_parse_migrates() {
+        let cache = parse("[items]\nCODE-AAAA-BBBB = 1706227200\n").unwrap();
+
+        assert_eq!(cache.version, migrate::CACHE_VERSION);
+        assert_eq!(cache.items["CODE-AAAA-BBBB"].ttl, 1706227200);
+        assert!(parse("items = 5").is_err());
+    }
+
+    #[test]
+    fn test_read_legacy_entries() {
+        let cache: Cache = toml::from_str(
+            r#"
+            [items]
+            CODE-AAAA-BBBB = 1706227200
+
+            [items.CODE-AAAA-CCCC]
+            ttl = 1706227200
+            source = "discord"
+            submitted_at = 1705622400
+            expires_at = 1706140800
+            api_id = 5
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            cache.items["CODE-AAAA-BBBB"],
+            CacheEntry {
+                ttl: 1706227200,
+                ..Default::default()
+            }
+        );
+        assert_eq!(cache.items["CODE-AAAA-CCCC"].source, "discord");
+        assert_eq!(cache.items["CODE-AAAA-CCCC"].api_id, Some(5));
+    }
+}
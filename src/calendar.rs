@@ -0,0 +1,93 @@
+use crate::cache::Cache;
+use std::path::Path;
+use time::OffsetDateTime;
+
+/// Renders all cached codes that have yet to expire as an iCalendar, with a reminder a day ahead.
+pub fn render(cache: &Cache) -> String {
+    let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    let mut upcoming: Vec<(&String, &u64)> = cache
+        .items
+        .iter()
+        .filter(|(_, entry)| entry.is_stored())
+        .map(|(code, entry)| (code, &entry.expires_at))
+        .filter(|(_, expires_at)| **expires_at > now)
+        .collect();
+    upcoming.sort_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)));
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Liefland//liccrawler//EN".to_string(),
+        "X-WR-CALNAME:Idle Champions Codes".to_string(),
+    ];
+
+    for (code, expires_at) in upcoming {
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@liccrawler", code),
+            format!("DTSTAMP:{}", format_timestamp(now)),
+            format!("DTSTART:{}", format_timestamp(*expires_at)),
+            format!("DTEND:{}", format_timestamp(*expires_at)),
+            format!("SUMMARY:Idle Champions code {} expires", code),
+            "BEGIN:VALARM".to_string(),
+            "TRIGGER:-P1D".to_string(),
+            "ACTION:DISPLAY".to_string(),
+            format!("DESCRIPTION:Redeem {} before it expires", code),
+            "END:VALARM".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.join("\r\n") + "\r\n"
+}
+
+pub fn write(path: &Path, cache: &Cache) {
+    match std::fs::write(path, render(cache)) {
+        Ok(_) => debug!("Calendar written to {}", path.display()),
+        Err(e) => error!("Error writing calendar to {}: {}", path.display(), e),
+    }
+}
+
+fn format_timestamp(ts: u64) -> String {
+    let dt = OffsetDateTime::from_unix_timestamp(ts as i64).unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        dt.year(),
+        dt.month() as u8,
+        dt.day(),
+        dt.hour(),
+        dt.minute(),
+        dt.second()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::next_week;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(1706227200), "20240126T000000Z");
+    }
+
+    #[test]
+    fn test_render() {
+        crate::cache::init();
+
+        let mut cache = Cache::default();
+        cache.insert("CODE-AAAA-BBBB".to_string(), "discord", next_week(), None);
+        cache.insert("OLDC-ODEA-AAAA".to_string(), "discord", 1706227200, None);
+
+        let ics = render(&cache);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:CODE-AAAA-BBBB@liccrawler"));
+        assert!(!ics.contains("OLDC-ODEA-AAAA"));
+    }
+}
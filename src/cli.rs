@@ -0,0 +1,92 @@
+use crate::{archive, cache, dlq, export, source};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Keep running and poll all sources every `daemon.interval_secs`
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Only poll this source, e.g. `discord.default`, even if disabled with `source disable`; may be repeated
+    #[arg(long = "source", value_name = "SOURCE", conflicts_with = "daemon")]
+    pub sources: Vec<String>,
+
+    /// Skip sources polled within `daemon.interval_secs`, e.g. to catch up on the ones a failed run missed
+    #[arg(long, conflicts_with = "daemon")]
+    pub only_new: bool,
+
+    /// Print the configuration in effect, with defaults filled in and secrets masked, and exit
+    #[arg(long, conflicts_with = "daemon")]
+    pub print_config: bool,
+
+    /// Read the config from this file instead of `config.toml` in the data directory, it is not created if missing
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Keep the cache, queues and other data files in this directory instead of next to the config
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Keep the cache in this file instead of `cache.toml` in the data directory, and the other data files next to it
+    /// unless `--data-dir` is given
+    #[arg(long, global = true, conflicts_with = "memory_cache")]
+    pub cache_path: Option<PathBuf>,
+
+    /// Keep the cache and other data files in memory only, e.g. on a read-only filesystem; they are lost on exit
+    #[arg(long, global = true)]
+    pub memory_cache: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Submit codes from a CSV (with header) or JSON lines file of `code, expires, creator_name, creator_url`
+    Import { file: PathBuf },
+    /// Print the local submission history
+    Export {
+        #[arg(long, value_enum, default_value = "csv")]
+        format: export::Format,
+    },
+    /// Print every code ever found, including those no longer cached
+    History {
+        #[command(flatten)]
+        filter: archive::Filter,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: export::Format,
+    },
+    /// Print the version, and with `--check` whether a newer release is available
+    Version {
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check the credentials and permissions of every enabled source and print a pass/fail matrix
+    Doctor,
+    /// Run a message through the parser of a configured source, e.g. `discord.default`, and print the result
+    TestParse {
+        #[arg(long)]
+        source: String,
+        #[arg(long)]
+        message_file: PathBuf,
+    },
+    /// Submit a code found before again, even though it was stored, e.g. when the remote lost it
+    Resend { code: String },
+    /// List sources, or disable and enable them without editing the config
+    Source {
+        #[command(subcommand)]
+        action: source::Action,
+    },
+    /// Maintain the local cache of stored codes
+    Cache {
+        #[command(subcommand)]
+        action: cache::Action,
+    },
+    /// Manage codes an output rejected for good, which are no longer submitted
+    Dlq {
+        #[command(subcommand)]
+        action: dlq::Action,
+    },
+}
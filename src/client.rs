@@ -13,7 +13,7 @@ impl ClientConfig {
         self.remote_host.clone()
     }
 
-    pub fn client(&self) -> CodesClient {
-        CodesClient::new_full(self.api_key(), self.remote_host(), None)
+    pub fn client(&self, http: reqwest::Client) -> CodesClient {
+        CodesClient::new_full(self.api_key(), self.remote_host(), Some(http))
     }
 }
@@ -1,54 +1,734 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
+use crate::migrate;
+use crate::parse::{ExpiryRounding, Locale};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
-    pub dry_run: bool,
+    /// Schema version, older configs are upgraded in memory when read, see `migrate`
+    #[serde(default)]
+    pub version: u32,
+
+    /// Dry run: Required - `true` to only log what would be submitted, `"staging"` to submit to
+    /// `client.staging_remote_host` instead, keeping production untouched
+    pub dry_run: DryRun,
+
+    /// Repair mangled codes before validating them, and suggest likely intended codes when rejected
+    #[serde(default)]
+    pub fuzzy_codes: bool,
+
+    /// Submit codes found by sources even if they already expired, e.g. when backfilling old messages
+    #[serde(default)]
+    pub submit_expired: bool,
+
+    /// Regex rewrites applied in order to creator urls before they are submitted, see `creator::default_url_rules`
+    #[serde(default = "crate::creator::default_url_rules")]
+    pub creator_url_rules: Vec<UrlRule>,
+
+    /// Submit every creator with the name and url they were first submitted with, however their url is spelled this
+    /// time, see `creator::Creators`
+    #[serde(default)]
+    pub canonical_creators: bool,
+
+    /// Readable names of reward emoji, e.g. `electrumchest = "Electrum Chest"`, on top of `reward::DEFAULTS`
+    #[serde(default)]
+    pub rewards: HashMap<String, String>,
+
+    /// Seconds a source may take to fetch and parse before it is given up on for this run
+    #[serde(default = "default_source_timeout_secs")]
+    pub source_timeout_secs: u64,
+
+    /// Seconds a whole run may take, after which remaining sources are skipped and codes not submitted yet are
+    /// queued in `pending.jsonl` for the next run; unlimited if omitted
+    pub max_run_seconds: Option<u64>,
+
+    /// Number of codes submitted at the same time
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+
+    /// Which of `shard_count` instances this is, counting from 0, each polls a fixed share of the sources
+    #[serde(default)]
+    pub shard_index: u32,
+
+    /// Number of instances sharing this config, 1 polls every source
+    #[serde(default = "default_shard_count")]
+    pub shard_count: u32,
 
     pub client: ClientConfig,
 
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    #[serde(default)]
+    pub cache: CacheConfig,
+
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+
+    #[serde(default)]
+    pub parser: ParserConfig,
+
+    #[serde(default)]
+    pub control: ControlConfig,
+
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    #[serde(default)]
+    pub validation: ValidationConfig,
+
+    /// Where new codes are sent, only the licc API under `[client]` if empty
+    #[serde(default)]
+    pub outputs: Vec<OutputConfig>,
+
     pub discord: HashMap<String, DiscordConfig>,
+
+    /// Files in GitHub repositories to watch for newly added codes
+    #[serde(default)]
+    pub github: HashMap<String, GithubConfig>,
+
+    /// IRC channels to idle in when running with `--daemon`
+    #[serde(default)]
+    pub irc: HashMap<String, IrcConfig>,
+
+    /// Official game API or news feeds, whose expiries are preferred over the ones parsed from other sources
+    #[serde(default)]
+    pub game_api: HashMap<String, GameApiConfig>,
+
+    /// Rhai scripts per source, e.g. `"discord.default" = "filters/default.rhai"`, whose `filter` function may drop
+    /// or change the codes found; requires the `scripting` feature
+    #[serde(default)]
+    pub scripts: HashMap<String, PathBuf>,
+
+    /// Commands that print codes as JSON lines, for scrapers written in any language
+    #[serde(default)]
+    pub exec: HashMap<String, ExecConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UrlRule {
+    /// Pattern: Required - regular expression matched against the url
+    pub pattern: String,
+    /// Replacement: Required - may refer to groups, e.g. `$1`
+    pub replacement: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Number of submitted codes remembered, the least recently submitted are forgotten first
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Max Attempts: Optional - failed submissions of a code before it is moved to the dead-letter queue
+    pub max_attempts: u32,
+    /// Backoff Secs: Optional - seconds before a failed code is submitted again, doubled after every attempt
+    pub backoff_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Seconds to wait between polls of all sources when running with `--daemon`
+    pub interval_secs: u64,
+    /// Days an enabled source may go without yielding a code before a notification is sent, never if unset
+    pub silent_days: Option<u32>,
+    /// Reload `config.toml` before the next poll when it changes on disk
+    pub watch: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParserConfig {
+    /// Days until codes with open-ended expiries ("while supplies last") are assumed to expire
+    pub open_ended_days: u32,
+    /// Days past a week after a message its expiry may be before it is assumed to be a typo and clamped
+    pub safety_net_days: u32,
+    /// Recognise expiries in German, French, Spanish, Portuguese, Italian and Dutch and translate them to English
+    pub translate: bool,
+    /// Program and arguments that print the English translation of the text on their stdin, used before the built-in vocabulary
+    pub translate_command: Vec<String>,
+    /// How parsed expiries are rounded: `exact` (default), up to the `end_of_day` in UTC, or up to the next `noon_pacific`
+    pub expiry_rounding: ExpiryRounding,
+    /// Hour in UTC the game resets at, what expiries "at reset" are taken to mean
+    pub reset_hour: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Enabled: Optional - accept commands by DM to the bot in daemon mode
+    pub enabled: bool,
+    /// Bot Token: Required when enabled - may be the same bot as one of the discord sources
+    pub bot_token: String,
+    /// Admins: User IDs allowed to send commands
+    pub admins: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// User Agent: Optional - sent with every request, except to Discord which requires its own
+    pub user_agent: String,
+    /// Connect Timeout Secs: Optional - give up connecting after this long
+    pub connect_timeout_secs: u64,
+    /// Timeout Secs: Optional - give up on a request (including reading the response) after this long
+    pub timeout_secs: u64,
+    /// Proxy: Optional - e.g. `http://proxy.internal:3128` or `socks5://127.0.0.1:1080`
+    pub proxy: Option<String>,
+    /// CA Bundle: Optional - PEM file with additional root certificates to trust, e.g. for an intercepting proxy
+    pub ca_bundle: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Replace tokens, API keys and webhook urls from the config with `[redacted]` in all log output
+    pub redact: bool,
+    /// Additional regular expressions to redact
+    pub redact_patterns: Vec<String>,
+    /// Log level per module, e.g. `serenity = "warn"` or `"liccrawler::parse" = "debug"`; ignored when `RUST_LOG` is set
+    pub levels: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Webhook: Optional - Discord webhook url to post operator notifications to, they are only logged otherwise
+    pub webhook: Option<String>,
+    /// Parse failures: Optional - Also post newly failing messages with an excerpt after each run, so moderators can fix them
+    pub parse_failures: bool,
+    /// Parse Rate Drop: Optional - notify when the share of a source's items that parse falls this far below its usual
+    /// share, e.g. `0.5` for 90% to 40%, which usually means its message format changed; `0` to never notify
+    pub parse_rate_drop: f64,
+    /// Digest: Optional - post a weekly summary of the codes found and failures instead of a summary after every run
+    pub digest: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    /// Lengths: Optional - lengths a code may have, not counting dashes
+    pub lengths: Vec<usize>,
+    /// Alphanumeric: Optional - reject codes with symbols such as `!@#$%^&*`, which some codes do have
+    pub alphanumeric: bool,
+    /// Sources: Optional - replaces the settings above for a source, e.g. `[validation.sources."discord.events"]`
+    pub sources: HashMap<String, SourceValidation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceValidation {
+    /// Lengths: Optional - see `validation.lengths`
+    pub lengths: Option<Vec<usize>>,
+    /// Alphanumeric: Optional - see `validation.alphanumeric`
+    pub alphanumeric: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Endpoint: Optional - OTLP/HTTP collector to export traces to, e.g. `http://localhost:4318`; no traces if unset
+    pub endpoint: Option<String>,
+    /// Service Name: Optional - the `service.name` spans are reported under
+    pub service_name: String,
+    /// Headers: Optional - sent with every export, e.g. an `Authorization` header required by the collector
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OutputConfig {
+    /// The licc API configured under `[client]`
+    Licc,
+    /// Append to a local file
+    File {
+        path: PathBuf,
+        #[serde(default)]
+        format: FileFormat,
+    },
+    /// POST the insert request as JSON
+    Webhook {
+        url: String,
+        /// JSON body to send instead, with `{{code}}`, `{{expires}}`, `{{creator.name}}` and such, see `output::webhook`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    /// Print as JSON lines
+    Stdout,
+    /// Publish as a retained JSON message to an MQTT broker (requires the `mqtt` feature)
+    Mqtt {
+        host: String,
+        #[serde(default = "default_mqtt_port")]
+        port: u16,
+        /// Topic per code, `{code}` is replaced by the code
+        #[serde(default = "default_mqtt_topic")]
+        topic: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+    },
+    /// Append a row to a Google Sheet shared with the service account (requires the `gsheets` feature)
+    #[serde(rename = "gsheets")]
+    GSheets {
+        spreadsheet_id: String,
+        /// Sheet and columns to append to, e.g. `Codes!A:F`
+        range: String,
+        /// Path to the service account's JSON key
+        service_account_key: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    #[default]
+    Json,
+    Csv,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CalendarConfig {
+    /// Path: Optional - write upcoming code expiries to this .ics file after every crawl
+    pub path: Option<PathBuf>,
+    /// Listen: Optional - serve the calendar at `/calendar.ics` on this address in daemon mode
+    pub listen: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ClientConfig {
     pub remote_host: Option<String>,
     pub api_key: String,
+    /// Staging remote host: Optional - the licc instance `dry_run = "staging"` submits to, required by it
+    pub staging_remote_host: Option<String>,
+}
+
+/// Whether codes are submitted, `dry_run` in the config is `true`, `false` or `"staging"`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "DryRunValue", into = "DryRunValue")]
+pub enum DryRun {
+    /// Submit to every output
+    #[default]
+    Off,
+    /// Only log what would be submitted
+    On,
+    /// Submit for real, but only to the licc instance at `client.staging_remote_host`.
+    /// Discord acknowledgements are skipped like in a dry run, and the cache and other data files live in
+    /// a `staging` directory inside [`dir`] so production runs are unaffected.
+    Staging,
+}
+
+impl DryRun {
+    /// Whether codes are submitted at all, to production or to staging.
+    pub fn submits(self) -> bool {
+        self != DryRun::On
+    }
+
+    /// Whether this is a production run, which is the only kind allowed to act on the sources, e.g. react in discord.
+    pub fn is_live(self) -> bool {
+        self == DryRun::Off
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum DryRunValue {
+    Bool(bool),
+    Mode(StagingMode),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StagingMode {
+    Staging,
+}
+
+impl From<DryRunValue> for DryRun {
+    fn from(value: DryRunValue) -> Self {
+        match value {
+            DryRunValue::Bool(false) => DryRun::Off,
+            DryRunValue::Bool(true) => DryRun::On,
+            DryRunValue::Mode(StagingMode::Staging) => DryRun::Staging,
+        }
+    }
+}
+
+impl From<DryRun> for DryRunValue {
+    fn from(dry_run: DryRun) -> Self {
+        match dry_run {
+            DryRun::Off => DryRunValue::Bool(false),
+            DryRun::On => DryRunValue::Bool(true),
+            DryRun::Staging => DryRunValue::Mode(StagingMode::Staging),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
 pub struct DiscordConfig {
     /// Enabled: Required
     pub enabled: bool,
     /// Send acknowledgements (reactions) to cache remotely and display the bot handled it to others;
-    /// This increases the number of requests to discord by 1 for each message parsed (only the first time)
+    /// This increases the number of requests to discord by 1 for each message parsed (only the first time), skipped in dry run
     pub acknowledge: bool,
+    /// Ack Mode: Optional - `reaction` (default) with 👍, `outcome` to react with `ack_emoji` once its codes are submitted,
+    /// `reply` to the message once its codes are stored, or `thread` to post a note in `ack_thread_id`. Replies and notes
+    /// go out once per code, messages are read again every run
+    #[serde(default)]
+    pub ack_mode: AckMode,
+    /// Ack Emoji: Optional - reactions per outcome for the `outcome` ack mode
+    #[serde(default)]
+    pub ack_emoji: AckEmoji,
+    /// Ack Template: Optional - text per stored code in replies and notes, with `{code}`, `{id}`, `{expires}` and `{message}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_template: Option<String>,
+    /// Ack Thread ID: Required for the `thread` ack mode - thread (or channel) to post notes in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_thread_id: Option<u64>,
+    /// Submitter URL: Optional - link submitted with codes to where they were posted, with `{guild}`, `{channel}` and
+    /// `{message}`; the message itself by default, e.g. `https://discord.com/channels/{guild}/{channel}` to only link the channel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submitter_url: Option<String>,
     /// Application ID: Optional, improved logging
     pub application_id: u64,
-    /// Public Key: Optional
+    /// Public Key: Deprecated - never used, will be removed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub public_key: String,
     /// Bot Token: Required - HTTP request auth
     pub bot_token: String,
     /// Guild ID: Optional (but fallback for good url generation)
     pub guild_id: u64,
+    /// Channel ID: Required unless there are targets - which channel to read
+    #[serde(default)]
+    pub channel_id: u64,
+    /// Max Message Age Hours: Optional - ignore older messages, even if they were never acknowledged
+    pub max_message_age_hours: Option<u64>,
+    /// Min Message Age Secs: Optional - leave messages for a later run until they were posted (or last edited) this long
+    /// ago, giving whoever posted them time to fix a typo
+    #[serde(default)]
+    pub min_message_age_secs: u64,
+    /// API Base: Optional - send Discord API requests to this host instead (proxies, testing)
+    pub api_base: Option<String>,
+    /// Proxy: Optional - replaces `http.proxy` for this source, an empty string connects directly
+    pub proxy: Option<String>,
+    /// Scan Pins: Optional - also parse pinned messages, regardless of max_message_age_hours
+    #[serde(default)]
+    pub scan_pins: bool,
+    /// Scan Topic: Optional - also look for codes and a creator link in the channel topic
+    #[serde(default)]
+    pub scan_topic: bool,
+    /// Request Delay Ms: Optional - wait this long between Discord API calls, to avoid bursts when sources share a bot
+    #[serde(default)]
+    pub request_delay_ms: u64,
+    /// Request Jitter Ms: Optional - wait up to this much longer at random, so the calls follow no fixed rhythm
+    #[serde(default)]
+    pub request_jitter_ms: u64,
+    /// Format: Optional - `relay` (default) for code, creator, url, reward and expiry on their own lines, `official` for announcements
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// Locale: Optional - `en-US`, `en-GB` or `de-DE`, how the source writes dates; otherwise "am"/"pm" decide day/month order
+    pub locale: Option<Locale>,
+    /// Crowd: Optional - go by other users' reactions to skip dead codes or wait for confirmations
+    #[serde(default)]
+    pub crowd: CrowdConfig,
+    /// Targets: Optional - more channels, in any guild, to read with the same bot
+    #[serde(default)]
+    pub targets: Vec<DiscordTarget>,
+}
+
+/// How a discord source acknowledges the messages it handled.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AckMode {
+    /// React with 👍, messages with the reaction are skipped from then on
+    #[default]
+    Reaction,
+    /// React with `ack_emoji` according to what became of the codes, once they are submitted
+    Outcome,
+    /// Reply to the message with the stored codes
+    Reply,
+    /// Post a note about the stored codes in a log thread
+    Thread,
+}
+
+/// Reactions for `ack_mode = "outcome"`, each a unicode emoji or a guild emoji as `<:name:id>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AckEmoji {
+    /// Submitted: Optional - the codes were stored
+    pub submitted: String,
+    /// Duplicate: Optional - the codes were stored before
+    pub duplicate: String,
+    /// Rejected: Optional - the codes parsed, but an output did not take them
+    pub rejected: String,
+}
+
+/// How code messages in a discord channel are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageFormat {
+    /// Code, creator name, creator url, reward and expiry, one per line, as relayed by community servers
+    #[default]
+    Relay,
+    /// Announcements in the official Idle Champions server, with the code in bold in a paragraph
+    Official,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrowdConfig {
+    /// Dead Emoji: Optional - reaction that marks a code as not working
+    pub dead_emoji: String,
+    /// Dead At: Optional - skip messages with this many dead reactions, never if 0
+    pub dead_at: u64,
+    /// Confirm Emoji: Optional - reaction that confirms a code works
+    pub confirm_emoji: String,
+    /// Min Confirmations: Optional - hold messages back until this many users confirmed them
+    pub min_confirmations: u64,
+}
+
+/// A channel read in addition to a discord source's own `channel_id`, sharing its bot and client.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DiscordTarget {
+    /// Guild ID: Optional (but fallback for good url generation)
+    #[serde(default)]
+    pub guild_id: u64,
     /// Channel ID: Required - which channel to read
     pub channel_id: u64,
+    /// Acknowledge: Optional - overrides the source's `acknowledge`
+    pub acknowledge: Option<bool>,
+    /// Format: Optional - overrides the source's `format`
+    pub format: Option<MessageFormat>,
+    /// Fuzzy Codes: Optional - overrides the top-level `fuzzy_codes`
+    pub fuzzy_codes: Option<bool>,
+    /// Max Message Age Hours: Optional - overrides the source's `max_message_age_hours`
+    pub max_message_age_hours: Option<u64>,
 }
 
-pub fn dir() -> PathBuf {
+impl DiscordConfig {
+    /// Every channel to read: the source's own `channel_id` if set, followed by its targets.
+    pub fn channels(&self) -> Vec<DiscordTarget> {
+        let own = DiscordTarget {
+            guild_id: self.guild_id,
+            channel_id: self.channel_id,
+            ..Default::default()
+        };
+
+        std::iter::once(own)
+            .filter(|own| own.channel_id != 0)
+            .chain(self.targets.iter().map(|target| DiscordTarget {
+                guild_id: match target.guild_id {
+                    0 => self.guild_id,
+                    guild_id => guild_id,
+                },
+                ..target.clone()
+            }))
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct GithubConfig {
+    /// Enabled: Required
+    pub enabled: bool,
+    /// Repo: Required - `owner/name`
+    pub repo: String,
+    /// Path: Required - `.json`/`.jsonl` files are read in the `liccrawler import` format, others are scanned for codes
+    pub path: String,
+    /// Branch: Optional - the repository's default branch if omitted
+    pub branch: Option<String>,
+    /// Token: Optional - raises the API rate limit and gives access to private repositories
+    #[serde(default)]
+    pub token: String,
+    /// Creator Name: Optional - who codes are attributed to when their line does not link a creator
+    #[serde(default)]
+    pub creator_name: String,
+    /// Creator URL: Optional - see Creator Name
+    #[serde(default)]
+    pub creator_url: String,
+    /// API Base: Optional - send GitHub API requests to this host instead (GitHub Enterprise, testing)
+    pub api_base: Option<String>,
+    /// Proxy: Optional - replaces `http.proxy` for this source, an empty string connects directly
+    pub proxy: Option<String>,
+    /// Locale: Optional - `en-US`, `en-GB` or `de-DE`, how the source writes dates; otherwise "am"/"pm" decide day/month order
+    pub locale: Option<Locale>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct GameApiConfig {
+    /// Enabled: Required
+    pub enabled: bool,
+    /// URL: Required - a JSON list of codes (`code`, `expires_at`) or news posts with codes in their text
+    pub url: String,
+    /// Creator Name: Optional - who codes are attributed to, the game if omitted
+    pub creator_name: Option<String>,
+    /// Creator URL: Optional - see Creator Name
+    pub creator_url: Option<String>,
+    /// Proxy: Optional - replaces `http.proxy` for this source, an empty string connects directly
+    pub proxy: Option<String>,
+    /// Locale: Optional - `en-US`, `en-GB` or `de-DE`, how the feed writes dates that are not timestamps
+    pub locale: Option<Locale>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ExecConfig {
+    /// Enabled: Required
+    pub enabled: bool,
+    /// Command: Required - program and arguments that print one JSON object per line, with a `code` and optionally
+    /// `expires_at` (unix timestamp or a date), `creator_name` and `creator_url`
+    pub command: Vec<String>,
+    /// Creator Name: Optional - who codes are attributed to when a line names no creator
+    #[serde(default)]
+    pub creator_name: String,
+    /// Creator URL: Optional - see Creator Name
+    #[serde(default)]
+    pub creator_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct IrcConfig {
+    /// Enabled: Required
+    pub enabled: bool,
+    /// Server: Required - host and port of a plain text listener, e.g. `irc.libera.chat:6667`
+    pub server: String,
+    /// Nickname: Required - suffixed with `_` while taken
+    pub nickname: String,
+    /// Channels: Required - channels to idle in, e.g. `#idlechampions`
+    pub channels: Vec<String>,
+    /// Creator Name: Optional - who codes are attributed to when a message does not link a creator
+    #[serde(default)]
+    pub creator_name: String,
+    /// Creator URL: Optional - see Creator Name
+    #[serde(default)]
+    pub creator_url: String,
+    /// Proxy: Optional - a `socks5://host:port` proxy without authentication to connect through
+    pub proxy: Option<String>,
+    /// Locale: Optional - `en-US`, `en-GB` or `de-DE`, how the source writes dates; otherwise "am"/"pm" decide day/month order
+    pub locale: Option<Locale>,
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic() -> String {
+    "liccrawler/codes/{code}".to_string()
+}
+
+fn default_safety_net_days() -> u32 {
+    32
+}
+
+fn default_source_timeout_secs() -> u64 {
+    120
+}
+
+fn default_shard_count() -> u32 {
+    1
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+/// Environment variables starting with this override config keys, `__` separating the keys of nested tables, e.g.
+/// `LICCRAWLER__CLIENT__API_KEY` or `LICCRAWLER__DISCORD__DEFAULT__ENABLED`, see [`overrides`].
+const ENV_PREFIX: &str = "LICCRAWLER__";
+
+/// Set by `--config`, see [`set_path`].
+static PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(Default::default);
+
+/// Set by `--data-dir`, see [`set_dir`].
+static DATA_DIR: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(Default::default);
+
+/// Set once a config with `dry_run = "staging"` is read, see [`dir`].
+static STAGING: AtomicBool = AtomicBool::new(false);
+
+fn base_dir() -> PathBuf {
     directories::ProjectDirs::from("net", "liefland", "liccrawler")
         .unwrap()
         .config_dir()
         .to_path_buf()
 }
 
+/// Where the cache and other data files live, its `staging` directory in staging runs.
+pub fn dir() -> PathBuf {
+    let data_dir = DATA_DIR
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(base_dir);
+
+    match STAGING.load(Ordering::Relaxed) {
+        true => data_dir.join("staging"),
+        false => data_dir,
+    }
+}
+
+/// Keeps the cache and other data files in `path` instead of next to `config.toml`, see `--data-dir`.
+pub fn set_dir(path: PathBuf) {
+    if let Err(e) = std::fs::create_dir_all(&path) {
+        error!("Error creating {}: {}", path.display(), e);
+    }
+
+    *DATA_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+}
+
+/// Reads the config from `path` instead of `config.toml` in [`dir`], which is then never created, see `--config`.
+pub fn set_path(path: PathBuf) {
+    *PATH.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+}
+
+/// The config file, `config.toml` in the data directory unless [`set_path`] was called.
+pub fn file() -> PathBuf {
+    PATH.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| base_dir().join("config.toml"))
+}
+
+/// Creates the data directory with a default config on first run, logging instead of failing when it is read-only.
 fn setup() {
-    let config_dir = dir();
-    if !config_dir.exists() {
-        std::fs::create_dir_all(config_dir).unwrap();
+    let config_dir = base_dir();
+    if config_dir.exists() {
+        return;
+    }
+
+    if let Err(e) = std::fs::create_dir_all(&config_dir) {
+        error!("Error creating {}: {}", config_dir.display(), e);
+        return;
+    }
 
+    if PATH.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
         write(Config::default());
     }
 }
@@ -56,17 +736,368 @@ fn setup() {
 pub fn write(config: Config) {
     setup();
 
-    std::fs::write(dir().join("config.toml"), toml::to_string(&config).unwrap()).unwrap();
+    let file = file();
+    let written = toml::to_string(&config)
+        .map_err(|e| e.to_string())
+        .and_then(|cfg| std::fs::write(&file, cfg).map_err(|e| e.to_string()));
+
+    if let Err(e) = written {
+        error!("Error writing {}: {}", file.display(), e);
+    }
 }
 
 pub fn read() -> Config {
+    try_read().unwrap_or_else(|e| {
+        error!("Invalid config: {}", e);
+        std::process::exit(1)
+    })
+}
+
+/// [`read`], returning an error instead of panicking, for reloads while running.
+pub fn try_read() -> Result<Config, String> {
     setup();
 
-    let cfg = std::fs::read_to_string(dir().join("config.toml")).unwrap();
+    let file = file();
+    let cfg = std::fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+
+    let mut unknown: Vec<String> = vec![];
+    let mut table: toml::Table = toml::from_str(&cfg).map_err(|e| e.to_string())?;
 
-    let config: Config = toml::from_str(&cfg).unwrap();
+    // Only deserialize the migrated table when needed, errors on the original point at line numbers.
+    let migrated = migrate::config(&mut table);
+    let overridden = overrides(&mut table, std::env::vars());
+    let config: Result<Config, toml::de::Error> = match migrated || !overridden.is_empty() {
+        true => serde_ignored::deserialize(toml::Value::Table(table), |path| {
+            unknown.push(path.to_string())
+        }),
+        false => serde_ignored::deserialize(toml::Deserializer::new(&cfg), |path| {
+            unknown.push(path.to_string())
+        }),
+    };
+    let config = config.map_err(|e| e.to_string())?;
 
-    config
+    if migrated {
+        info!(
+            "config.toml was upgraded in memory, set `version = {}` after reviewing it against the README",
+            migrate::CONFIG_VERSION
+        );
+    }
+
+    for key in overridden {
+        info!("{} is set by the environment", key);
+    }
+
+    for key in unknown {
+        warn!("Unknown key '{}' in config.toml, ignoring it", key);
+    }
+
+    for warning in lint(&config) {
+        warn!("config.toml: {}", warning);
+    }
+
+    stage(config)
+}
+
+/// Sets the keys named by `LICCRAWLER__` environment variables in `vars`, returning the keys set.
+///
+/// Keys are lowercased. Values replacing a string stay a string, others are read as TOML and fall back to a string,
+/// so `true`, `600` and `[1, 2]` are a boolean, number and array.
+fn overrides(table: &mut toml::Table, vars: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    let mut overridden = vec![];
+
+    for (name, value) in vars {
+        let Some(path) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let keys: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+        if keys.iter().any(String::is_empty) {
+            warn!("Ignoring {}, it does not name a config key", name);
+            continue;
+        }
+
+        let (key, parents) = keys.split_last().expect("split yields at least one key");
+        let mut target = &mut *table;
+        for parent in parents {
+            let entry = target
+                .entry(parent.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            target = match entry {
+                toml::Value::Table(nested) => nested,
+                other => {
+                    *other = toml::Value::Table(toml::Table::new());
+                    other.as_table_mut().expect("just replaced with a table")
+                }
+            };
+        }
+
+        let value = match target.get(key) {
+            Some(toml::Value::String(_)) => toml::Value::String(value),
+            _ => toml::from_str::<toml::Table>(&format!("value = {}", value))
+                .ok()
+                .and_then(|mut parsed| parsed.remove("value"))
+                .unwrap_or(toml::Value::String(value)),
+        };
+        target.insert(key.clone(), value);
+        overridden.push(keys.join("."));
+    }
+
+    overridden.sort();
+    overridden
+}
+
+/// Points a `dry_run = "staging"` config at the staging remote, with licc as its only output, and moves the
+/// data files to [`dir`]'s `staging` directory.
+fn stage(mut config: Config) -> Result<Config, String> {
+    if config.dry_run != DryRun::Staging {
+        STAGING.store(false, Ordering::Relaxed);
+        return Ok(config);
+    }
+
+    let Some(host) = config.client.staging_remote_host.clone() else {
+        return Err("dry_run = \"staging\" requires client.staging_remote_host".to_string());
+    };
+
+    STAGING.store(true, Ordering::Relaxed);
+    info!("Staging run, submitting to {} only", host);
+    config.client.remote_host = Some(host);
+    config.outputs = vec![];
+
+    if let Err(e) = std::fs::create_dir_all(dir()) {
+        error!("Error creating {}: {}", dir().display(), e);
+    }
+
+    Ok(config)
+}
+
+/// Keys whose values are not logged when they change.
+const SECRET_KEYS: [&str; 5] = ["token", "key", "proxy", "webhook", "url"];
+
+/// What changed between two configs, one line per changed key, e.g. `discord.default.enabled: true -> false`.
+pub fn diff(old: &Config, new: &Config) -> Vec<String> {
+    fn walk(
+        path: &str,
+        old: Option<&toml::Value>,
+        new: Option<&toml::Value>,
+        out: &mut Vec<String>,
+    ) {
+        if let (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) = (old, new) {
+            let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+            for key in keys {
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", path, key),
+                };
+                walk(&path, old.get(key), new.get(key), out);
+            }
+            return;
+        }
+
+        if old == new {
+            return;
+        }
+
+        let secret = SECRET_KEYS
+            .iter()
+            .any(|k| path.rsplit('.').next().unwrap_or(path).contains(k));
+        let show = |value: Option<&toml::Value>| match (value, secret) {
+            (None, _) => "unset".to_string(),
+            (Some(_), true) => "***".to_string(),
+            (Some(value), false) => value.to_string(),
+        };
+
+        out.push(format!("{}: {} -> {}", path, show(old), show(new)));
+    }
+
+    let mut out = vec![];
+    walk(
+        "",
+        toml::Value::try_from(old).ok().as_ref(),
+        toml::Value::try_from(new).ok().as_ref(),
+        &mut out,
+    );
+
+    out
+}
+
+/// `config` as TOML, with the defaults of everything left out filled in and every value holding a secret
+/// replaced by `[redacted]`, whether or not `logging.redact` is set.
+pub fn effective(config: &Config) -> Result<String, String> {
+    fn mask(value: &mut toml::Value, secrets: &[String]) {
+        match value {
+            toml::Value::String(s) if secrets.iter().any(|secret| s.contains(secret.as_str())) => {
+                *s = crate::logging::REDACTED.to_string();
+            }
+            toml::Value::Array(values) => values.iter_mut().for_each(|v| mask(v, secrets)),
+            toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| mask(v, secrets)),
+            _ => {}
+        }
+    }
+
+    let mut value = toml::Value::try_from(config).map_err(|e| e.to_string())?;
+    let secrets: Vec<String> = crate::logging::secrets(config)
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect();
+    mask(&mut value, &secrets);
+
+    toml::to_string_pretty(&value).map_err(|e| e.to_string())
+}
+
+impl Config {
+    /// Every configured source, e.g. `discord.default`, with whether it is enabled.
+    pub fn sources(&self) -> Vec<(String, bool)> {
+        let discord = self
+            .discord
+            .iter()
+            .map(|(name, d)| (format!("discord.{}", name), d.enabled));
+        let github = self
+            .github
+            .iter()
+            .map(|(name, g)| (format!("github.{}", name), g.enabled));
+        let irc = self
+            .irc
+            .iter()
+            .map(|(name, i)| (format!("irc.{}", name), i.enabled));
+        let game_api = self
+            .game_api
+            .iter()
+            .map(|(name, g)| (format!("game_api.{}", name), g.enabled));
+
+        let exec = self
+            .exec
+            .iter()
+            .map(|(name, e)| (format!("exec.{}", name), e.enabled));
+
+        let mut sources: Vec<(String, bool)> = discord
+            .chain(github)
+            .chain(irc)
+            .chain(game_api)
+            .chain(exec)
+            .collect();
+        sources.sort();
+
+        sources
+    }
+
+    /// Whether this instance polls `source`, e.g. `discord.default`, when sharded.
+    ///
+    /// Sources are assigned by a hash of their name, so every instance agrees without talking to each other.
+    pub fn owns(&self, source: &str) -> bool {
+        self.shard_count <= 1
+            || crate::audit::fnv1a(source.as_bytes()) % self.shard_count as u64
+                == self.shard_index as u64
+    }
+}
+
+/// Flags values that parse fine but are deprecated or are unlikely to do what the user intended.
+pub fn lint(config: &Config) -> Vec<String> {
+    let mut warnings = vec![];
+
+    if config.dry_run.submits() && config.client.api_key.is_empty() {
+        warnings.push("client.api_key is empty, submissions will likely be rejected".to_string());
+    }
+
+    if config.daemon.interval_secs < 60 {
+        warnings.push(format!(
+            "daemon.interval_secs = {} is very low, consider polling less often",
+            config.daemon.interval_secs
+        ));
+    }
+
+    if config.shard_index >= config.shard_count.max(1) {
+        warnings.push(format!(
+            "shard_index = {} is not below shard_count = {}, this instance polls no sources",
+            config.shard_index, config.shard_count
+        ));
+    }
+
+    if config.parser.reset_hour > 23 {
+        warnings.push(format!(
+            "parser.reset_hour = {} is not an hour of the day, expiries \"at reset\" will be a day or more late",
+            config.parser.reset_hour
+        ));
+    }
+
+    if config.cache.limit == 0 {
+        warnings
+            .push("cache.limit is 0, only the last code submitted will be remembered".to_string());
+    }
+
+    if config.control.enabled && config.control.admins.is_empty() {
+        warnings.push("control is enabled without any admins, it will ignore everyone".to_string());
+    }
+
+    for (name, discord) in &config.discord {
+        if !discord.public_key.is_empty() {
+            warnings.push(format!(
+                "discord.{}.public_key is deprecated and unused, remove it",
+                name
+            ));
+        }
+
+        if !discord.enabled {
+            continue;
+        }
+
+        if discord.bot_token.is_empty() {
+            warnings.push(format!("discord.{}.bot_token is empty", name));
+        }
+
+        if discord.channel_id == 0 && discord.targets.is_empty() {
+            warnings.push(format!("discord.{}.channel_id is 0", name));
+        }
+
+        for (i, target) in discord.targets.iter().enumerate() {
+            if target.channel_id == 0 {
+                warnings.push(format!("discord.{}.targets[{}].channel_id is 0", name, i));
+            }
+        }
+
+        if discord.guild_id == 0 {
+            warnings.push(format!(
+                "discord.{}.guild_id is 0, submitter urls may be incomplete",
+                name
+            ));
+        }
+
+        if discord.max_message_age_hours == Some(0) {
+            warnings.push(format!(
+                "discord.{}.max_message_age_hours is 0, every message will be ignored",
+                name
+            ));
+        }
+    }
+
+    for (name, github) in config.github.iter().filter(|(_, github)| github.enabled) {
+        if github.repo.is_empty() || github.path.is_empty() {
+            warnings.push(format!("github.{}.repo and path are required", name));
+        }
+    }
+
+    for (name, game_api) in config.game_api.iter().filter(|(_, g)| g.enabled) {
+        if game_api.url.is_empty() {
+            warnings.push(format!("game_api.{}.url is required", name));
+        }
+    }
+
+    for (name, exec) in config.exec.iter().filter(|(_, e)| e.enabled) {
+        if exec.command.is_empty() {
+            warnings.push(format!("exec.{}.command is required", name));
+        }
+    }
+
+    for (name, irc) in config.irc.iter().filter(|(_, irc)| irc.enabled) {
+        if irc.server.is_empty() || irc.nickname.is_empty() {
+            warnings.push(format!("irc.{}.server and nickname are required", name));
+        }
+
+        if irc.channels.is_empty() {
+            warnings.push(format!("irc.{}.channels is empty", name));
+        }
+    }
+
+    warnings
 }
 
 impl Default for Config {
@@ -75,9 +1106,395 @@ impl Default for Config {
         d.insert("default".to_string(), DiscordConfig::default());
 
         Self {
-            dry_run: false,
+            version: migrate::CONFIG_VERSION,
+            dry_run: DryRun::Off,
+            fuzzy_codes: false,
+            submit_expired: false,
+            concurrency: default_concurrency(),
+            creator_url_rules: crate::creator::default_url_rules(),
+            canonical_creators: false,
+            rewards: HashMap::new(),
+            source_timeout_secs: default_source_timeout_secs(),
+            max_run_seconds: None,
+            shard_index: 0,
+            shard_count: default_shard_count(),
             client: ClientConfig::default(),
+            http: HttpConfig::default(),
+            cache: CacheConfig::default(),
+            retry: RetryConfig::default(),
+            daemon: DaemonConfig::default(),
+            calendar: CalendarConfig::default(),
+            parser: ParserConfig::default(),
+            control: ControlConfig::default(),
+            logging: LoggingConfig::default(),
+            notify: NotifyConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            validation: ValidationConfig::default(),
+            outputs: vec![],
             discord: d,
+            github: HashMap::new(),
+            irc: HashMap::new(),
+            game_api: HashMap::new(),
+            scripts: HashMap::new(),
+            exec: HashMap::new(),
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: concat!("liccrawler/", env!("CARGO_PKG_VERSION")).to_string(),
+            connect_timeout_secs: 10,
+            timeout_secs: 30,
+            proxy: None,
+            ca_bundle: None,
+        }
+    }
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook: None,
+            parse_failures: false,
+            parse_rate_drop: 0.5,
+            digest: false,
+        }
+    }
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            lengths: crate::validate::Length::default().0,
+            alphanumeric: false,
+            sources: HashMap::new(),
         }
     }
 }
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            service_name: "liccrawler".to_string(),
+            headers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            redact: true,
+            redact_patterns: vec![],
+            levels: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            open_ended_days: 7,
+            safety_net_days: default_safety_net_days(),
+            translate: false,
+            translate_command: vec![],
+            expiry_rounding: ExpiryRounding::Exact,
+            reset_hour: 0,
+        }
+    }
+}
+
+impl Default for AckEmoji {
+    fn default() -> Self {
+        Self {
+            submitted: "✅".to_string(),
+            duplicate: "♻️".to_string(),
+            rejected: "⚠️".to_string(),
+        }
+    }
+}
+
+impl Default for CrowdConfig {
+    fn default() -> Self {
+        Self {
+            dead_emoji: "❌".to_string(),
+            dead_at: 0,
+            confirm_emoji: "✅".to_string(),
+            min_confirmations: 0,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { limit: 5000 }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff_secs: 300,
+        }
+    }
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            silent_days: None,
+            watch: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let mut config = Config::default();
+        config.client.api_key = "key".to_string();
+
+        let discord = config.discord.get_mut("default").unwrap();
+        discord.public_key = "deprecated".to_string();
+        assert_eq!(
+            lint(&config),
+            vec!["discord.default.public_key is deprecated and unused, remove it"]
+        );
+
+        let discord = config.discord.get_mut("default").unwrap();
+        discord.public_key = String::new();
+        discord.enabled = true;
+        discord.bot_token = "token".to_string();
+        discord.guild_id = 1;
+        assert_eq!(lint(&config), vec!["discord.default.channel_id is 0"]);
+
+        config.discord.get_mut("default").unwrap().channel_id = 1;
+        config.parser.reset_hour = 24;
+        assert_eq!(
+            lint(&config),
+            vec!["parser.reset_hour = 24 is not an hour of the day, expiries \"at reset\" will be a day or more late"]
+        );
+    }
+
+    #[test]
+    fn test_dry_run() {
+        #[derive(Deserialize)]
+        struct Table {
+            dry_run: DryRun,
+        }
+
+        let parse = |value: &str| {
+            toml::from_str::<Table>(&format!("dry_run = {}", value))
+                .ok()
+                .map(|table| table.dry_run)
+        };
+        assert_eq!(parse("false"), Some(DryRun::Off));
+        assert_eq!(parse("true"), Some(DryRun::On));
+        assert_eq!(parse("\"staging\""), Some(DryRun::Staging));
+        assert_eq!(parse("\"production\""), None);
+
+        assert!(!DryRun::On.submits());
+        assert!(DryRun::Staging.submits() && !DryRun::Staging.is_live());
+
+        let config = Config {
+            dry_run: DryRun::Staging,
+            ..Default::default()
+        };
+        assert!(stage(config).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Config::default();
+        let mut new = Config::default();
+        new.daemon.interval_secs = 600;
+        new.client.api_key = "secret".to_string();
+        let discord = new.discord.get_mut("default").unwrap();
+        discord.enabled = true;
+        discord.scan_pins = true;
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                "client.api_key: *** -> ***",
+                "daemon.interval_secs: 300 -> 600",
+                "discord.default.enabled: false -> true",
+                "discord.default.scan_pins: false -> true",
+            ]
+        );
+        assert!(diff(&old, &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut table: toml::Table = toml::from_str(
+            r#"
+            [client]
+            api_key = "from-file"
+
+            [discord.default]
+            enabled = false
+            channel_id = 1
+            "#,
+        )
+        .unwrap();
+
+        let vars = [
+            ("LICCRAWLER__CLIENT__API_KEY", "1234"),
+            ("LICCRAWLER__DISCORD__DEFAULT__ENABLED", "true"),
+            ("LICCRAWLER__DAEMON__INTERVAL_SECS", "600"),
+            ("LICCRAWLER__PARSER__TRANSLATE_COMMAND", "not toml"),
+            ("LICCRAWLER____", "ignored"),
+            ("HOME", "/root"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        assert_eq!(
+            overrides(&mut table, vars.into_iter()),
+            vec![
+                "client.api_key",
+                "daemon.interval_secs",
+                "discord.default.enabled",
+                "parser.translate_command",
+            ]
+        );
+        assert_eq!(table["client"]["api_key"].as_str(), Some("1234"));
+        assert_eq!(table["discord"]["default"]["enabled"].as_bool(), Some(true));
+        assert_eq!(
+            table["discord"]["default"]["channel_id"].as_integer(),
+            Some(1)
+        );
+        assert_eq!(table["daemon"]["interval_secs"].as_integer(), Some(600));
+        assert_eq!(
+            table["parser"]["translate_command"].as_str(),
+            Some("not toml")
+        );
+    }
+
+    #[test]
+    fn test_effective() {
+        let mut config = Config::default();
+        config.client.api_key = "apikey123456".to_string();
+        config.outputs = vec![OutputConfig::Webhook {
+            url: "https://example.com/hook?key=apikey123456".to_string(),
+            template: None,
+        }];
+
+        let effective = effective(&config).unwrap();
+
+        assert!(!effective.contains("apikey123456"));
+        assert!(effective.contains("api_key = \"[redacted]\""));
+        assert!(effective.contains("source_timeout_secs = 120"));
+    }
+
+    #[test]
+    fn test_owns() {
+        let mut config = Config::default();
+        assert!(config.owns("discord.default"));
+
+        config.shard_count = 3;
+        for source in [
+            "discord.default",
+            "discord.other",
+            "github.default",
+            "irc.libera",
+        ] {
+            let owners = (0..3)
+                .filter(|index| {
+                    config.shard_index = *index;
+                    config.owns(source)
+                })
+                .count();
+            assert_eq!(owners, 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_discord_channels() {
+        let discord: DiscordConfig = toml::from_str(
+            r#"
+            enabled = true
+            acknowledge = true
+            application_id = 0
+            bot_token = "token"
+            guild_id = 1
+            channel_id = 10
+
+            [[targets]]
+            channel_id = 20
+            acknowledge = false
+
+            [[targets]]
+            guild_id = 2
+            channel_id = 30
+            fuzzy_codes = true
+            "#,
+        )
+        .unwrap();
+
+        let channels: Vec<_> = discord
+            .channels()
+            .iter()
+            .map(|c| (c.guild_id, c.channel_id, c.acknowledge, c.fuzzy_codes))
+            .collect();
+        assert_eq!(
+            channels,
+            vec![
+                (1, 10, None, None),
+                (1, 20, Some(false), None),
+                (2, 30, None, Some(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_parser_config() {
+        let parser: ParserConfig = toml::from_str("translate = true").unwrap();
+
+        assert!(parser.translate);
+        assert_eq!(
+            parser.open_ended_days,
+            ParserConfig::default().open_ended_days
+        );
+        assert_eq!(parser.safety_net_days, default_safety_net_days());
+    }
+
+    #[test]
+    fn test_partial_cache_and_daemon_config() {
+        let cache: CacheConfig = toml::from_str("").unwrap();
+        assert_eq!(cache.limit, CacheConfig::default().limit);
+
+        let daemon: DaemonConfig = toml::from_str("silent_days = 3").unwrap();
+        assert_eq!(daemon.silent_days, Some(3));
+        assert_eq!(daemon.interval_secs, DaemonConfig::default().interval_secs);
+        assert!(daemon.watch);
+    }
+
+    #[test]
+    fn test_unknown_fields_in_discord_config_are_rejected() {
+        let cfg = r#"
+            dry_run = true
+
+            [client]
+            api_key = ""
+
+            [discord.default]
+            enabled = true
+            acknowledge = false
+            application_id = 0
+            bot_token = "token"
+            guild_id = 0
+            channel_Id = 1
+        "#;
+
+        assert!(toml::from_str::<Config>(cfg).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::config::{ControlConfig, HttpConfig};
+use crate::daemon::{Outcome, Task};
+use crate::parse::{next_week, validate_code, TimeParser};
+use crate::{cache, state};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use serenity::all::{Context, EventHandler, GatewayIntents, HttpBuilder, Message};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+
+const HELP: &str = "Commands:
+`status` - last run, paused sources and cache size
+`recent` - the most recently submitted codes
+`submit CODE CREATOR_URL [EXPIRY]` - submit a code by hand
+`pause SOURCE` / `resume SOURCE` - e.g. `pause discord.default`";
+
+const RECENT_LIMIT: usize = 10;
+
+const STOPPED: &str = "The daemon stopped, try again after a restart.";
+
+struct Control {
+    admins: Vec<u64>,
+    timeparser: TimeParser,
+    tasks: UnboundedSender<Task>,
+}
+
+/// Connects to the gateway and answers commands sent by DM from `control.admins`, until the connection fails.
+///
+/// Commands that change the cache or state are handed to the daemon loop through `tasks`, and answered once it
+/// ran them between cycles.
+pub async fn run(
+    cfg: ControlConfig,
+    http: HttpConfig,
+    timeparser: TimeParser,
+    tasks: UnboundedSender<Task>,
+) {
+    let intents = GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+    let http = HttpBuilder::new(&cfg.bot_token)
+        .client(crate::http::client(&http))
+        .build();
+
+    let client = serenity::client::ClientBuilder::new_with_http(http, intents)
+        .event_handler(Control {
+            admins: cfg.admins,
+            timeparser,
+            tasks,
+        })
+        .await;
+
+    match client {
+        Ok(mut client) => {
+            if let Err(e) = client.start().await {
+                error!("Control interface stopped: {}", e);
+            }
+        }
+        Err(e) => error!("Error creating control client: {}", e),
+    }
+}
+
+#[serenity::async_trait]
+impl EventHandler for Control {
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.guild_id.is_some() || msg.author.bot {
+            return;
+        }
+
+        if !self.admins.contains(&msg.author.id.get()) {
+            debug!("Ignoring DM from non-admin {}", msg.author.id);
+            return;
+        }
+
+        info!("Control command from {}: {}", msg.author.name, msg.content);
+
+        let reply = self.command(&msg).await;
+
+        if let Err(e) = msg.reply(&ctx, reply).await {
+            error!("Error replying to control command: {}", e);
+        }
+    }
+}
+
+impl Control {
+    async fn command(&self, msg: &Message) -> String {
+        let mut words = msg.content.split_whitespace();
+
+        match words.next().map(|w| w.to_lowercase()).as_deref() {
+            Some("status") => status(),
+            Some("recent") => recent(),
+            Some("pause") => self.pause(words.next(), true).await,
+            Some("resume") => self.pause(words.next(), false).await,
+            Some("submit") => self.submit(msg, words.collect()).await,
+            _ => HELP.to_string(),
+        }
+    }
+
+    async fn pause(&self, source: Option<&str>, paused: bool) -> String {
+        let source = match source {
+            Some(s) => s.to_string(),
+            None => return "Which source? e.g. `discord.default`".to_string(),
+        };
+
+        let (reply, changed) = oneshot::channel();
+        let task = Task::Pause {
+            source: source.clone(),
+            paused,
+            reply,
+        };
+        if self.tasks.send(task).is_err() {
+            return STOPPED.to_string();
+        }
+        let Ok(changed) = changed.await else {
+            return STOPPED.to_string();
+        };
+
+        match (changed, paused) {
+            (true, true) => format!("Paused {}.", source),
+            (true, false) => format!("Resumed {}.", source),
+            (false, true) => format!("{} was already paused.", source),
+            (false, false) => format!("{} was not paused.", source),
+        }
+    }
+
+    async fn submit(&self, msg: &Message, args: Vec<&str>) -> String {
+        let (code, creator_url) = match args.as_slice() {
+            [code, creator_url, ..] => (code.to_uppercase(), creator_url.to_string()),
+            _ => return "Usage: `submit CODE CREATOR_URL [EXPIRY]`".to_string(),
+        };
+
+        if !validate_code(&code) {
+            return format!("`{}` does not look like a valid code.", code);
+        }
+
+        let expiry = args[2..].join(" ");
+        let expires_at = self
+            .timeparser
+            .parse(expiry, true)
+            .await
+            .unwrap_or_else(next_week);
+
+        let request = InsertCodeRequest {
+            code: code.clone(),
+            expires_at,
+            creator: SourceLookup {
+                name: creator_url
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or("Unknown")
+                    .to_string(),
+                url: creator_url,
+            },
+            submitter: Some(SourceLookup {
+                name: msg
+                    .author
+                    .global_name
+                    .clone()
+                    .unwrap_or_else(|| msg.author.name.clone()),
+                url: format!("https://discord.com/users/{}", msg.author.id),
+            }),
+        };
+
+        let (reply, outcome) = oneshot::channel();
+        let task = Task::Submit {
+            source: "discord.control".to_string(),
+            requests: vec![request],
+            reply: Some(reply),
+        };
+        if self.tasks.send(task).is_err() {
+            return STOPPED.to_string();
+        }
+        let Ok(Outcome { responses, dry_run }) = outcome.await else {
+            return STOPPED.to_string();
+        };
+
+        match responses.get(&code).map(|r| r.id) {
+            Some(Some(id)) => format!("Stored `{}`: {}", code, id),
+            Some(None) if dry_run => format!("Dry run, not storing `{}`.", code),
+            Some(None) => format!("Failed to store `{}`, check the logs.", code),
+            None => format!("`{}` is already stored.", code),
+        }
+    }
+}
+
+fn status() -> String {
+    let state = state::read();
+    let cache = cache::peek();
+
+    let last_run = match state.last_run {
+        Some(ts) => format!("<t:{}:R>", ts),
+        None => "never".to_string(),
+    };
+    let paused = match state.paused.is_empty() {
+        true => "none".to_string(),
+        false => state.paused.into_iter().collect::<Vec<_>>().join(", "),
+    };
+
+    format!(
+        "Last run: {}\nPaused: {}\nCached codes: {}",
+        last_run,
+        paused,
+        cache.items.len()
+    )
+}
+
+fn recent() -> String {
+    let cache = cache::peek();
+
+    let mut entries: Vec<_> = cache
+        .items
+        .iter()
+        .filter(|(_, entry)| entry.is_stored())
+        .collect();
+    entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.submitted_at));
+
+    if entries.is_empty() {
+        return "No codes cached.".to_string();
+    }
+
+    entries
+        .into_iter()
+        .take(RECENT_LIMIT)
+        .map(|(code, entry)| {
+            format!(
+                "`{}` from {}, expires <t:{}:R>",
+                code, entry.source, entry.expires_at
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,1674 @@
+use crate::anomaly;
+use crate::archive;
+use crate::audit;
+use crate::cache::{self, Cache, RemoteStatus};
+use crate::calendar;
+#[cfg(feature = "discord")]
+use crate::config::AckMode;
+use crate::config::Config;
+use crate::creator::{CreatorAttribution, Creators, UrlRules};
+use crate::digest;
+use crate::dlq;
+#[cfg(feature = "discord")]
+use crate::handler::discord;
+use crate::handler::{exec, game_api, github, Failure, Handled, Listed};
+use crate::http::Validators;
+use crate::intent;
+use crate::metrics;
+use crate::notify;
+use crate::output::{self, Metadata, OutputSink};
+use crate::parse::{confusion_variants, normalize_code};
+use crate::pending;
+use crate::progress;
+use crate::retry;
+use crate::script;
+use crate::source;
+use crate::state;
+use crate::telemetry;
+use crate::validate;
+use futures::FutureExt;
+use licc::write::InsertCodeRequest;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// A single full crawl: reads the cache, runs all sources, reports and writes the cache back.
+pub async fn once(config: &Config) {
+    cache::setup();
+    telemetry::enable(&config.telemetry);
+    let mut cache = cache::read();
+
+    let responses = run(config, &mut cache).await;
+    report(config, responses);
+    metrics::write();
+    telemetry::flush(config).await;
+
+    cache.bust();
+
+    if let Some(path) = &config.calendar.path {
+        calendar::write(path, &cache);
+    }
+
+    cache::write(cache).await;
+
+    let mut state = state::read();
+    state.last_run = Some(time::OffsetDateTime::now_utc().unix_timestamp() as u64);
+    state::write(&state);
+}
+
+/// Runs a source, catching a panic in it, e.g. an `unwrap` on something a scraper did not expect, so it fails that
+/// source rather than the whole run.
+async fn isolated<T>(future: impl Future<Output = T>) -> Result<T, String> {
+    AssertUnwindSafe(future)
+        .catch_unwind()
+        .await
+        .map_err(|panic| {
+            panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown cause".to_string())
+        })
+}
+
+/// A message to acknowledge once its codes are stored, by guild, channel and message ID, see `Handled::replies`.
+#[cfg(feature = "discord")]
+type Reply = (u64, u64, u64);
+
+/// Polls the sources of one run, with what every kind of source shares: the checks whether to poll it at all, the
+/// time limit, isolation from panics, telemetry and the summary stats.
+struct Poller<'a> {
+    config: &'a Config,
+    state: &'a state::State,
+    started_at: u64,
+}
+
+impl Poller<'_> {
+    /// Runs `handle` for `source`, e.g. `discord.default`, unless it is to be skipped, and records its stats.
+    ///
+    /// Returns what it found, validated and scripted, or `None` if it was skipped or failed. Progress on what was
+    /// fetched is left to the caller, as some sources report it as they go.
+    async fn poll<E: std::fmt::Display>(
+        &self,
+        budget: &Budget,
+        stats: &mut HashMap<String, SourceStats>,
+        source: &str,
+        enabled: bool,
+        handle: impl Future<Output = Result<Handled, E>>,
+    ) -> Option<Handled> {
+        let (kind, name) = source.split_once('.').unwrap_or((source, source));
+
+        if !self.config.owns(source) {
+            debug!("Skipping {} '{}', polled by another shard", kind, name);
+            return None;
+        } else if let Some(reason) =
+            source::skipped(self.config, self.state, source, self.started_at)
+        {
+            info!("Skipping {} '{}', {}", kind, name, reason);
+            return None;
+        } else if self.state.is_paused(source) && !source::named(source) {
+            info!("Skipping {} '{}', paused", kind, name);
+            return None;
+        } else if budget.exceeded() {
+            info!("Skipping {} '{}', out of max_run_seconds", kind, name);
+            return None;
+        } else if !enabled {
+            info!("Skipping {} '{}', not enabled", kind, name);
+            return None;
+        }
+
+        progress::start(source);
+        let mut span = telemetry::source(source);
+        let started = std::time::Instant::now();
+        let failed = |failures: Vec<Failure>| SourceStats {
+            errors: 1,
+            duration_ms: started.elapsed().as_millis(),
+            failures,
+            ..Default::default()
+        };
+
+        let out = match isolated(tokio::time::timeout(budget.timeout(self.config), handle)).await {
+            Ok(Ok(Ok(mut out))) => {
+                validate::retain(source, &mut out);
+                script::apply(source, &mut out);
+                progress::parsed(source, out.codes.len());
+                span.attribute("liccrawler.fetched", out.fetched);
+                span.attribute("liccrawler.parsed", out.codes.len());
+                stats.insert(
+                    source.to_string(),
+                    SourceStats {
+                        fetched: out.fetched,
+                        parsed: out.codes.len(),
+                        errors: out.failures.len(),
+                        duration_ms: started.elapsed().as_millis(),
+                        failures: std::mem::take(&mut out.failures),
+                        ..Default::default()
+                    },
+                );
+
+                Some(out)
+            }
+            Ok(Ok(Err(err))) => {
+                error!("Error handling {} '{}': {}", kind, name, err);
+                span.fail(err.to_string());
+                stats.insert(source.to_string(), failed(vec![]));
+                None
+            }
+            Ok(Err(_)) => {
+                error!(
+                    "Gave up on {} '{}' after source_timeout_secs = {}",
+                    kind, name, self.config.source_timeout_secs
+                );
+                span.fail("timed out");
+                stats.insert(source.to_string(), failed(vec![]));
+                None
+            }
+            Err(panic) => {
+                error!("Handling {} '{}' panicked: {}", kind, name, panic);
+                span.fail(format!("panicked: {}", panic));
+                stats.insert(
+                    source.to_string(),
+                    failed(vec![Failure {
+                        location: source.to_string(),
+                        reason: format!("panicked: {}", panic),
+                        content: String::new(),
+                    }]),
+                );
+                None
+            }
+        };
+        span.end();
+
+        out
+    }
+}
+
+/// Seconds before a parse failure that keeps occurring is logged as a warning again.
+const FAILURE_LOG_COOLDOWN: u64 = 24 * 60 * 60;
+
+/// Counters for one source during one run, logged as a single summary line at the end of the run.
+#[derive(Debug, Default)]
+pub struct SourceStats {
+    /// Messages (or other items) fetched from the source
+    pub fetched: usize,
+    /// Codes parsed from what was fetched
+    pub parsed: usize,
+    /// Codes stored, or that would be stored in dry run
+    pub new: usize,
+    /// Codes skipped as they were already stored
+    pub cached: usize,
+    /// Items that failed to parse, plus codes that failed to submit
+    pub errors: usize,
+    pub duration_ms: u128,
+    /// Where the parse errors are, listed below the summary line
+    pub failures: Vec<Failure>,
+}
+
+impl std::fmt::Display for SourceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "fetched={} parsed={} new={} cached={} errors={} duration_ms={}",
+            self.fetched, self.parsed, self.new, self.cached, self.errors, self.duration_ms
+        )
+    }
+}
+
+/// Runs every enabled source once and submits the codes found, returning the outcome per code.
+///
+/// Skips paused sources. Reading and writing the cache is left to the caller, the state, queues and other data files
+/// in `config::dir` are read and written as the run goes.
+pub async fn run(config: &Config, cache: &mut Cache) -> HashMap<String, Response> {
+    let mut stats: HashMap<String, SourceStats> = HashMap::new();
+    let state = state::read();
+    let mut budget = Budget::new(config);
+    reconcile(config, cache).await;
+    let deferred = pending::read();
+    let started_at = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    let poller = Poller {
+        config,
+        state: &state,
+        started_at,
+    };
+    let mut requests: BTreeMap<&str, Vec<InsertCodeRequest>> = BTreeMap::new();
+    let mut metadata: HashMap<String, Metadata> = HashMap::new();
+    let mut guessed: HashSet<String> = HashSet::new();
+    let mut origins: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut listed: HashMap<u64, Listed> = HashMap::new();
+    let mut yielded: Vec<&str> = vec![];
+    #[cfg(feature = "discord")]
+    let mut replies: Vec<(&crate::config::DiscordConfig, Vec<Reply>)> = vec![];
+
+    #[cfg(feature = "discord")]
+    let discords: Vec<(String, &String, &crate::config::DiscordConfig)> = config
+        .discord
+        .iter()
+        .map(|(name, discord)| (format!("discord.{}", name), name, discord))
+        .collect();
+
+    #[cfg(feature = "discord")]
+    for (source, name, discord) in &discords {
+        let handle = discord::handle(config, source, discord);
+        let Some(out) = poller
+            .poll(&budget, &mut stats, source, discord.enabled, handle)
+            .await
+        else {
+            continue;
+        };
+
+        if !out.codes.is_empty() {
+            yielded.push(source);
+        }
+        metadata.extend(out.metadata);
+        guessed.extend(out.guessed);
+        origins.extend(out.origins);
+        listed.extend(out.listed);
+        if !out.replies.is_empty() {
+            replies.push((*discord, out.replies));
+        }
+        requests.insert(source, without_expired(config, out.codes));
+
+        info!(
+            "Handled discord '{}' (Application ID: {})",
+            name, discord.application_id
+        );
+        if out.would_acknowledge > 0 {
+            info!(
+                "Dry run, would acknowledge {} messages in discord '{}'",
+                out.would_acknowledge, name
+            );
+        }
+    }
+
+    let githubs: Vec<(String, &String, &crate::config::GithubConfig)> = config
+        .github
+        .iter()
+        .map(|(name, github)| (format!("github.{}", name), name, github))
+        .collect();
+    let mut found: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut validated: Vec<(&str, Validators, Vec<String>)> = vec![];
+
+    for (source, name, github) in &githubs {
+        let handle = github::handle(config, github, state.validators.get(source.as_str()));
+        let Some(out) = poller
+            .poll(&budget, &mut stats, source, github.enabled, handle)
+            .await
+        else {
+            continue;
+        };
+
+        let seen = state.seen.get(source.as_str());
+        let new: Vec<InsertCodeRequest> = out
+            .codes
+            .into_iter()
+            .filter(|request| !seen.is_some_and(|seen| seen.contains(&request.code)))
+            .collect();
+
+        if !new.is_empty() {
+            yielded.push(source);
+        }
+        progress::fetched(source, out.fetched);
+        found.insert(source, codes_of(&new));
+        guessed.extend(out.guessed);
+        metadata.extend(out.metadata);
+        origins.extend(out.origins);
+        listed.extend(out.listed);
+        let new = without_expired(config, new);
+        if let Some(validators) = out.validators {
+            validated.push((source, validators, codes_of(&new)));
+        }
+        requests.insert(source, new);
+
+        info!(
+            "Handled github '{}' ({}/{})",
+            name, github.repo, github.path
+        );
+    }
+
+    let game_apis: Vec<(String, &String, &crate::config::GameApiConfig)> = config
+        .game_api
+        .iter()
+        .map(|(name, game_api)| (format!("game_api.{}", name), name, game_api))
+        .collect();
+    let mut authoritative: HashMap<String, u64> = HashMap::new();
+
+    for (source, name, game_api) in &game_apis {
+        let handle = game_api::handle(config, game_api, state.validators.get(source.as_str()));
+        let Some(out) = poller
+            .poll(&budget, &mut stats, source, game_api.enabled, handle)
+            .await
+        else {
+            continue;
+        };
+
+        if !out.codes.is_empty() {
+            yielded.push(source);
+        }
+        progress::fetched(source, out.fetched);
+        authoritative.extend(
+            out.codes
+                .iter()
+                .filter(|request| !out.guessed.contains(&request.code))
+                .map(|request| (request.code.clone(), request.expires_at)),
+        );
+        guessed.extend(out.guessed);
+        metadata.extend(out.metadata);
+        let codes = without_expired(config, out.codes);
+        if let Some(validators) = out.validators {
+            validated.push((source, validators, codes_of(&codes)));
+        }
+        requests.insert(source, codes);
+
+        info!("Handled game_api '{}'", name);
+    }
+
+    let execs: Vec<(String, &String, &crate::config::ExecConfig)> = config
+        .exec
+        .iter()
+        .map(|(name, exec)| (format!("exec.{}", name), name, exec))
+        .collect();
+
+    for (source, name, exec) in &execs {
+        let handle = exec::handle(config, exec);
+        let Some(out) = poller
+            .poll(&budget, &mut stats, source, exec.enabled, handle)
+            .await
+        else {
+            continue;
+        };
+
+        if !out.codes.is_empty() {
+            yielded.push(source);
+        }
+        progress::fetched(source, out.fetched);
+        guessed.extend(out.guessed);
+        metadata.extend(out.metadata);
+        requests.insert(source, without_expired(config, out.codes));
+
+        info!("Handled exec '{}'", name);
+    }
+
+    if !deferred.is_empty() {
+        info!(
+            "Submitting {} codes left over by the last run",
+            deferred.len()
+        );
+    }
+    for d in &deferred {
+        requests
+            .entry(d.source.as_str())
+            .or_default()
+            .push(d.to_request());
+    }
+
+    let retries = due_retries(config, &mut requests, started_at);
+    for r in &retries {
+        requests
+            .entry(r.request.source.as_str())
+            .or_default()
+            .push(r.request.to_request());
+    }
+
+    prefer_expiries(&mut requests, &mut guessed, &authoritative);
+
+    // Submitted in the order they were posted, message IDs grow over time.
+    for codes in requests.values_mut() {
+        codes.sort_by_key(|request| {
+            origins
+                .get(&request.code)
+                .map(|&(_, message_id)| message_id)
+        });
+    }
+
+    let responses = submit_counted(
+        config,
+        cache,
+        requests,
+        &metadata,
+        Some(&guessed),
+        &mut stats,
+        &mut budget,
+    )
+    .await;
+
+    // Everything that was pending went into this run, only what it deferred in turn is kept.
+    if config.dry_run.submits() {
+        if !budget.deferred.is_empty() {
+            warn!(
+                "Ran out of max_run_seconds, {} codes are left for the next run",
+                budget.deferred.len()
+            );
+        }
+        pending::write(&budget.deferred);
+    }
+
+    remember(cache, found);
+    revalidate(cache, validated);
+    heard(&yielded);
+
+    for (code, &(channel_id, message_id)) in &origins {
+        cache.set_message(code, channel_id, message_id);
+    }
+    retract(config, cache, &listed).await;
+
+    #[cfg(feature = "discord")]
+    if !replies.is_empty() {
+        let stored: HashMap<String, Response> = responses
+            .iter()
+            .filter(|(code, _)| !config.dry_run.submits() || cache.has(code))
+            .map(|(code, response)| (code.clone(), response.clone()))
+            .collect();
+
+        let outcomes: HashMap<String, discord::Outcome> = origins
+            .keys()
+            .filter_map(|code| {
+                let outcome = match responses.contains_key(code) {
+                    true if !config.dry_run.submits() || cache.has(code) => {
+                        discord::Outcome::Submitted
+                    }
+                    true => discord::Outcome::Rejected,
+                    false if cache.has(code) => discord::Outcome::Duplicate,
+                    false => return None,
+                };
+
+                Some((code.clone(), outcome))
+            })
+            .collect();
+
+        for (discord, messages) in replies {
+            match discord.ack_mode {
+                AckMode::Outcome => {
+                    discord::react(config, discord, &messages, &origins, &outcomes).await
+                }
+                _ => discord::reply(config, discord, &messages, &origins, &stored).await,
+            }
+        }
+    }
+
+    let mut sources: Vec<_> = stats.into_iter().collect();
+    sources.sort_by(|a, b| a.0.cmp(&b.0));
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let mut state = state::read();
+    state
+        .failures_logged
+        .retain(|_, logged| now.saturating_sub(*logged) < FAILURE_LOG_COOLDOWN);
+
+    let mut new_failures: Vec<(&str, &Failure)> = vec![];
+    let mut format_changes: Vec<String> = vec![];
+
+    for (source, stats) in &sources {
+        info!("source={} {}", source, stats);
+        state.last_polled.insert(source.clone(), now);
+
+        let history = state.parse_rates.entry(source.clone()).or_default();
+        if let Some((usual, recent)) = history.record(
+            stats.parsed,
+            stats.failures.len(),
+            config.notify.parse_rate_drop,
+        ) {
+            format_changes.push(anomaly::message(source, usual, recent));
+        }
+
+        for failure in &stats.failures {
+            if log_failure(source, failure, &mut state.failures_logged, now) {
+                new_failures.push((source.as_str(), failure));
+
+                if config.fuzzy_codes {
+                    if let Some(code) = invalid_code(failure) {
+                        suggest(&code);
+                    }
+                }
+            }
+        }
+    }
+
+    state::write(&state);
+    progress::finish(&sources);
+
+    if config.notify.digest {
+        let run = sources
+            .iter()
+            .map(|(source, stats)| (source.as_str(), stats.new, stats.errors));
+        if let Some(message) = digest::record(run, now) {
+            notify::send(config, &message).await;
+        }
+    } else if config.notify.parse_failures && !new_failures.is_empty() {
+        notify::send(config, &failure_summary(&new_failures)).await;
+    }
+
+    for message in format_changes {
+        notify::send(config, &message).await;
+    }
+
+    responses
+}
+
+/// Replaces the expiries other sources parsed for the codes in `authoritative`, e.g. from the game's own API, with its
+/// expiries, as those are exact where text is parsed on a best-effort basis.
+fn prefer_expiries(
+    requests: &mut BTreeMap<&str, Vec<InsertCodeRequest>>,
+    guessed: &mut HashSet<String>,
+    authoritative: &HashMap<String, u64>,
+) {
+    for request in requests.values_mut().flatten() {
+        let Some(&expires_at) = authoritative.get(&request.code) else {
+            continue;
+        };
+
+        if request.expires_at != expires_at {
+            debug!(
+                "Using the expiry {} of '{}' from the game API over {}",
+                expires_at, request.code, request.expires_at
+            );
+            request.expires_at = expires_at;
+        }
+        guessed.remove(&request.code);
+    }
+}
+
+/// Failures listed in a notification, the rest are counted, as Discord messages are limited to 2000 characters.
+const NOTIFIED_FAILURES: usize = 10;
+
+/// Characters of the first line of a failed item quoted in a notification.
+const EXCERPT_LEN: usize = 80;
+
+/// A notification listing newly failing items with the first line of each, to fix them without reading the logs.
+fn failure_summary(failures: &[(&str, &Failure)]) -> String {
+    let mut lines = vec![format!("{} new items could not be parsed:", failures.len())];
+
+    for (source, failure) in failures.iter().take(NOTIFIED_FAILURES) {
+        let mut line = format!("- {} {}: {}", source, failure.location, failure.reason);
+
+        if let Some(first) = failure.content.lines().find(|l| !l.trim().is_empty()) {
+            let first = first.trim();
+            let excerpt: String = first.chars().take(EXCERPT_LEN).collect();
+            let ellipsis = if excerpt.len() < first.len() {
+                "…"
+            } else {
+                ""
+            };
+
+            line.push_str(&format!(" `{}{}`", excerpt.replace('`', "'"), ellipsis));
+        }
+
+        lines.push(line);
+    }
+
+    if failures.len() > NOTIFIED_FAILURES {
+        lines.push(format!("and {} more", failures.len() - NOTIFIED_FAILURES));
+    }
+
+    lines.join("\n")
+}
+
+/// Warns about a parse failure, or only debug logs it if it was already warned about within the cooldown.
+///
+/// Returns whether it was warned about.
+fn log_failure(
+    source: &str,
+    failure: &Failure,
+    logged: &mut BTreeMap<String, u64>,
+    now: u64,
+) -> bool {
+    let key = failure.key();
+
+    if logged.contains_key(&key) {
+        debug!(
+            "source={} could not parse {} (again): {}",
+            source, failure.location, failure.reason
+        );
+        return false;
+    }
+
+    warn!(
+        "source={} could not parse {}: {}",
+        source, failure.location, failure.reason
+    );
+    if !failure.content.is_empty() {
+        debug!("Content: {}", failure.content);
+    }
+    logged.insert(key, now);
+
+    true
+}
+
+/// `max_run_seconds`: once it runs out, sources not polled yet are skipped and codes not submitted yet are deferred to
+/// the next run through `pending.jsonl`.
+struct Budget {
+    deadline: Option<Instant>,
+    deferred: Vec<pending::Deferred>,
+}
+
+impl Budget {
+    fn new(config: &Config) -> Self {
+        Self {
+            deadline: config
+                .max_run_seconds
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+            deferred: vec![],
+        }
+    }
+
+    fn unlimited() -> Self {
+        Self {
+            deadline: None,
+            deferred: vec![],
+        }
+    }
+
+    fn exceeded(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// [`source_timeout`], cut short to the time left.
+    fn timeout(&self, config: &Config) -> Duration {
+        match self.deadline {
+            Some(deadline) => {
+                source_timeout(config).min(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => source_timeout(config),
+        }
+    }
+}
+
+/// How long a source may take to fetch and parse, after which its handler is dropped, cancelling requests in flight.
+fn source_timeout(config: &Config) -> std::time::Duration {
+    std::time::Duration::from_secs(config.source_timeout_secs.max(1))
+}
+
+/// Marks the new codes of diffed sources as seen once they are stored, so failed codes are retried next run.
+fn remember(cache: &Cache, found: HashMap<&str, Vec<String>>) {
+    if found.is_empty() {
+        return;
+    }
+
+    let mut state = state::read();
+
+    for (source, codes) in found {
+        state
+            .seen
+            .entry(source.to_string())
+            .or_default()
+            .extend(codes.into_iter().filter(|code| cache.has(code)));
+    }
+
+    state::write(&state);
+}
+
+fn codes_of(requests: &[InsertCodeRequest]) -> Vec<String> {
+    requests.iter().map(|r| r.code.clone()).collect()
+}
+
+/// Keeps the validators of sources whose codes were all stored (or dead-lettered), so they are only fetched in
+/// full again once changed; a source with codes left to submit is fetched in full next run.
+fn revalidate(cache: &Cache, validated: Vec<(&str, Validators, Vec<String>)>) {
+    if validated.is_empty() {
+        return;
+    }
+
+    let dead = dlq::codes();
+    let mut state = state::read();
+
+    for (source, validators, codes) in validated {
+        match codes
+            .iter()
+            .all(|code| cache.has(code) || dead.contains(code))
+        {
+            true => state.validators.insert(source.to_string(), validators),
+            false => state.validators.remove(source),
+        };
+    }
+
+    state::write(&state);
+}
+
+/// Records that `sources` yielded codes just now, for the silence watchdog in daemon mode.
+pub fn heard(sources: &[&str]) {
+    if sources.is_empty() {
+        return;
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let mut state = state::read();
+
+    for source in sources {
+        state.heard(source, now);
+    }
+
+    state::write(&state);
+}
+
+fn without_expired(config: &Config, requests: Vec<InsertCodeRequest>) -> Vec<InsertCodeRequest> {
+    if config.submit_expired {
+        return requests;
+    }
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    requests
+        .into_iter()
+        .filter(|request| {
+            let expired = request.expires_at < now;
+            if expired {
+                info!(
+                    "Skipping '{}', expired at {} (see submit_expired)",
+                    request.code, request.expires_at
+                );
+            }
+
+            !expired
+        })
+        .collect()
+}
+
+/// Submits codes not yet in the cache, or only logs them in dry run, returning the outcome per code.
+pub async fn submit(
+    config: &Config,
+    cache: &mut Cache,
+    requests: BTreeMap<&str, Vec<InsertCodeRequest>>,
+) -> HashMap<String, Response> {
+    submit_counted(
+        config,
+        cache,
+        requests,
+        &HashMap::new(),
+        None,
+        &mut HashMap::new(),
+        &mut Budget::unlimited(),
+    )
+    .await
+}
+
+/// [`submit`], counting new, cached and failed codes in the stats of the source they came from.
+///
+/// With `guessed`, the codes whose expiry was not posted, cached codes submitted with a guessed expiry are
+/// updated when one of the other codes carries the posted expiry.
+async fn submit_counted(
+    config: &Config,
+    cache: &mut Cache,
+    mut requests: BTreeMap<&str, Vec<InsertCodeRequest>>,
+    metadata: &HashMap<String, Metadata>,
+    guessed: Option<&HashSet<String>>,
+    stats: &mut HashMap<String, SourceStats>,
+    budget: &mut Budget,
+) -> HashMap<String, Response> {
+    let mut responses: HashMap<String, Response> = HashMap::new();
+    let corrects = |cache: &Cache, request: &InsertCodeRequest| {
+        guessed.is_some_and(|guessed| !guessed.contains(&request.code))
+            && cache
+                .outdated_expiry(&request.code, request.expires_at)
+                .is_some()
+    };
+
+    let rules = UrlRules::new(&config.creator_url_rules);
+    let mut creators = match config.canonical_creators {
+        true => Some(Creators::read()),
+        false => None,
+    };
+    for request in requests.values_mut().flatten() {
+        let url = rules.apply(&request.creator.url);
+        if url != request.creator.url {
+            debug!("Rewrote creator url {} to {}", request.creator.url, url);
+            request.creator.url = url;
+        }
+        if let Some(creators) = creators.as_mut() {
+            creators.canonicalize(&mut request.creator);
+        }
+    }
+    if let Some(creators) = creators {
+        creators.write();
+    }
+
+    if !config.dry_run.submits() {
+        info!("Dry run enabled, not sending requests.");
+
+        for (from, value) in requests {
+            let stats = stats.entry(from.to_string()).or_default();
+
+            for request in value {
+                if cache.has(&request.code) {
+                    if corrects(cache, &request) {
+                        info!(
+                            "Would update the expiry of '{}' to {}",
+                            request.code, request.expires_at
+                        );
+                    }
+                    debug!("Skipping '{}', already stored.", &request.code);
+                    stats.cached += 1;
+                    continue;
+                }
+
+                stats.new += 1;
+                progress::submitted(from, 1);
+                responses.insert(
+                    request.code.clone(),
+                    Response {
+                        source: from.to_string(),
+                        id: None,
+                        expires_at: request.expires_at,
+                        platform: metadata
+                            .get(&request.code)
+                            .and_then(|meta| meta.platform.clone()),
+                        attribution: metadata
+                            .get(&request.code)
+                            .and_then(|meta| meta.attribution.clone()),
+                        message_ts: metadata.get(&request.code).and_then(|meta| meta.posted_at),
+                    },
+                );
+            }
+        }
+    } else {
+        let concurrency = config.concurrency.max(1);
+        let mut lanes: Vec<Vec<(&str, InsertCodeRequest, Metadata)>> =
+            (0..concurrency).map(|_| vec![]).collect();
+        let mut seen: HashSet<String> = HashSet::new();
+        let dead = dlq::codes();
+        let stored = cache.stored_remotes();
+        let mut corrections: Vec<InsertCodeRequest> = vec![];
+        let archived = archive::codes();
+        let mut records: Vec<archive::Record> = vec![];
+
+        for (from, value) in requests {
+            for request in value {
+                if cache.has(&request.code) {
+                    info!("Skipping '{}' from {}, already stored.", request.code, from);
+                    stats.entry(from.to_string()).or_default().cached += 1;
+                    if corrects(cache, &request)
+                        && !corrections.iter().any(|c| c.code == request.code)
+                    {
+                        corrections.push(request);
+                    }
+                    continue;
+                }
+
+                if dead.contains(&request.code) {
+                    debug!(
+                        "Skipping '{}' from {}, in the dead-letter queue.",
+                        request.code, from
+                    );
+                    continue;
+                }
+
+                if !seen.insert(request.code.clone()) {
+                    debug!("Skipping '{}' from {}, already queued.", request.code, from);
+                    continue;
+                }
+
+                let meta = metadata.get(&request.code).cloned().unwrap_or_default();
+                lanes[(seen.len() - 1) % concurrency].push((from, request, meta));
+            }
+        }
+
+        let deadline = budget.deadline;
+        let sinks = output::sinks(config);
+        // Each code is journaled as soon as its lane is done with it, not once the whole lane is.
+        let (results, mut submitted) = mpsc::unbounded_channel();
+        let lanes = futures::future::join_all(
+            lanes
+                .into_iter()
+                .filter(|lane| !lane.is_empty())
+                .map(|lane| submit_lane(&sinks, lane, &stored, deadline, results.clone())),
+        );
+        drop(results);
+        tokio::pin!(lanes);
+        let mut lanes_done = false;
+
+        loop {
+            let s = tokio::select! {
+                _ = &mut lanes, if !lanes_done => {
+                    lanes_done = true;
+                    continue;
+                }
+                s = submitted.recv() => match s {
+                    Some(s) => s,
+                    None => break,
+                },
+            };
+
+            if s.deferred {
+                budget
+                    .deferred
+                    .push(pending::Deferred::new(s.from, &s.request));
+                continue;
+            }
+
+            if !archived.contains(&s.request.code) {
+                records.push(archive::Record::new(
+                    s.from,
+                    &s.request,
+                    &s.metadata,
+                    !s.failed,
+                    s.response,
+                ));
+            }
+
+            responses.insert(
+                s.request.code.clone(),
+                Response {
+                    source: s.from.to_string(),
+                    id: s.response,
+                    expires_at: s.request.expires_at,
+                    platform: s.metadata.platform.clone(),
+                    attribution: s.metadata.attribution.clone(),
+                    message_ts: s.metadata.posted_at,
+                },
+            );
+
+            let stats = stats.entry(s.from.to_string()).or_default();
+            match s.failed {
+                true => stats.errors += 1,
+                false => {
+                    stats.new += 1;
+                    progress::submitted(s.from, 1);
+                }
+            }
+
+            // Failed outputs are retried next run, the others are not sent the code again.
+            for (sink, status, api_id) in &s.outcomes {
+                cache.record(
+                    &s.request.code,
+                    s.from,
+                    s.request.expires_at,
+                    sink,
+                    *status,
+                    *api_id,
+                );
+            }
+
+            if !s.failed {
+                if let Some(reward) = &s.metadata.reward {
+                    info!("'{}' from {} rewards {}", s.request.code, s.from, reward);
+                }
+                if let Some(platform) = &s.metadata.platform {
+                    info!(
+                        "'{}' from {} can only be redeemed on {}",
+                        s.request.code, s.from, platform
+                    );
+                }
+                if guessed.is_some_and(|guessed| guessed.contains(&s.request.code)) {
+                    cache.mark_guessed(&s.request.code);
+                }
+            } else if let Some((sink, reason)) = &s.rejected {
+                warn!(
+                    "'{}' from {} was rejected by {} for good, moved to the dead-letter queue: {}",
+                    s.request.code, s.from, sink, reason
+                );
+                dlq::add(&dlq::Letter::new(s.from, sink, &s.request, reason));
+            }
+
+            if config.dry_run.submits() {
+                match (s.failed, &s.rejected) {
+                    (true, None) => reschedule(config, &s),
+                    _ => retry::forget(&s.request.code),
+                }
+            }
+
+            cache::journal(cache, &s.request.code);
+            intent::finish(&s.request.code);
+
+            if s.failed && config.fuzzy_codes {
+                suggest(&s.request.code);
+            }
+        }
+
+        archive::append(&records);
+        update_expiries(config, cache, corrections).await;
+    }
+
+    responses
+}
+
+/// The codes in `retries.jsonl` whose backoff is over, which are submitted this run. The others are held back even
+/// if a source posted them again, and the expired ones are forgotten.
+fn due_retries(
+    config: &Config,
+    requests: &mut BTreeMap<&str, Vec<InsertCodeRequest>>,
+    now: u64,
+) -> Vec<retry::Retry> {
+    let retries = retry::read();
+    if retries.is_empty() || !config.dry_run.submits() {
+        return vec![];
+    }
+
+    let (live, expired): (Vec<_>, Vec<_>) = retries
+        .into_iter()
+        .partition(|r| config.submit_expired || r.request.expires_at > now);
+    if !expired.is_empty() {
+        info!(
+            "Giving up on {} failed codes that expired before they could be retried",
+            expired.len()
+        );
+        retry::write(&live);
+    }
+
+    let (due, waiting): (Vec<_>, Vec<_>) = live.into_iter().partition(|r| r.is_due(now));
+    for r in due.iter().chain(&waiting) {
+        for value in requests.values_mut() {
+            value.retain(|request| request.code != r.request.code);
+        }
+    }
+    for r in &waiting {
+        debug!(
+            "Holding back '{}' until {}, after {} failed attempts",
+            r.request.code, r.next_at, r.attempts
+        );
+    }
+    if !due.is_empty() {
+        info!("Retrying {} codes that failed before", due.len());
+    }
+
+    due
+}
+
+/// Settles the submissions an earlier run died in the middle of: sent to an output, but with the outcome never
+/// written to the cache. Outputs that have the code are recorded as having stored it, the others are sent it again
+/// this run; an output that cannot be asked is sent it again as well.
+async fn reconcile(config: &Config, cache: &mut Cache) {
+    let intents = intent::in_doubt();
+    if intents.is_empty() || !config.dry_run.submits() {
+        return;
+    }
+
+    let sinks = output::sinks(config);
+    let mut deferred = pending::read();
+    let mut unsettled = vec![];
+
+    for intent in intents {
+        let code = intent.request.code.clone();
+        let stored = cache
+            .items
+            .get(&code)
+            .and_then(|entry| entry.remotes.get(&intent.sink))
+            .is_some_and(|remote| remote.status == RemoteStatus::Stored);
+        if stored {
+            continue;
+        }
+
+        let Some(sink) = sinks.iter().find(|sink| sink.name() == intent.sink) else {
+            warn!(
+                "'{}' was being submitted to {} when a run stopped, which is no longer an output",
+                code, intent.sink
+            );
+            continue;
+        };
+
+        match sink.lookup(&intent.request.to_request()).await {
+            Ok(Some(true)) => {
+                info!(
+                    "'{}' reached {} before the last run stopped, caching it",
+                    code, intent.sink
+                );
+                cache.record(
+                    &code,
+                    &intent.request.source,
+                    intent.request.expires_at,
+                    &intent.sink,
+                    RemoteStatus::Stored,
+                    None,
+                );
+                cache::journal(cache, &code);
+            }
+            Ok(found) => {
+                match found {
+                    Some(_) => info!(
+                        "'{}' did not reach {} before the last run stopped, submitting it again",
+                        code, intent.sink
+                    ),
+                    None => warn!(
+                        "'{}' may or may not have reached {} before the last run stopped, which cannot be asked, submitting it again",
+                        code, intent.sink
+                    ),
+                }
+                if !deferred.iter().any(|d| d.code == code) {
+                    deferred.push(intent.request.clone());
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Error asking {} whether it has '{}', trying again next run: {}",
+                    intent.sink, code, e
+                );
+                unsettled.push(intent);
+            }
+        }
+    }
+
+    pending::write(&deferred);
+    intent::write(&unsettled);
+}
+
+/// Sends the posted expiry of codes that were submitted with a guessed one to every sink that can update codes.
+///
+/// The guess is only forgotten once every sink took the update, or cannot update at all.
+async fn update_expiries(config: &Config, cache: &mut Cache, corrections: Vec<InsertCodeRequest>) {
+    if corrections.is_empty() {
+        return;
+    }
+
+    let sinks = output::sinks(config);
+
+    for request in corrections {
+        let entry = cache.items.get(&request.code);
+        let mut failed = false;
+
+        for sink in sinks.iter() {
+            let api_id = entry.and_then(|entry| entry.api_id_in(sink.name()));
+            match sink.update_expiry(&request, api_id).await {
+                Ok(true) => info!(
+                    "Updated the expiry of '{}' to {} in {}",
+                    request.code,
+                    request.expires_at,
+                    sink.name()
+                ),
+                Ok(false) => warn!(
+                    "{} cannot update codes, '{}' (ID {:?}) expires at {} rather than the guessed expiry",
+                    sink.name(),
+                    request.code,
+                    api_id,
+                    request.expires_at
+                ),
+                Err(e) => {
+                    failed = true;
+                    error!(
+                        "Error updating the expiry of '{}' in {}: {}",
+                        request.code,
+                        sink.name(),
+                        e
+                    );
+                }
+            }
+        }
+
+        if !failed {
+            cache.correct_expiry(&request.code, request.expires_at);
+        }
+    }
+}
+
+/// Expires codes whose message was deleted since they were found, e.g. a typo retracted by whoever posted it.
+async fn retract(config: &Config, cache: &mut Cache, listed: &HashMap<u64, Listed>) {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let deleted = cache.deleted(listed, now);
+    if deleted.is_empty() {
+        return;
+    }
+
+    let archived: HashMap<String, archive::Record> = archive::read()
+        .into_iter()
+        .map(|record| (record.code.clone(), record))
+        .collect();
+    let sinks = output::sinks(config);
+
+    for code in deleted {
+        if !config.dry_run.submits() {
+            info!("Would retract '{}', its message was deleted", code);
+            continue;
+        }
+
+        let Some(record) = archived.get(&code) else {
+            warn!(
+                "The message of '{}' was deleted, but it is not archived to retract it, expire it by hand",
+                code
+            );
+            cache.retract(&code, now);
+            continue;
+        };
+
+        let mut request = record.to_request();
+        request.expires_at = now;
+        let entry = cache.items.get(&code);
+        let mut failed = false;
+
+        for sink in sinks.iter() {
+            let api_id = entry.and_then(|entry| entry.api_id_in(sink.name()));
+            match sink.update_expiry(&request, api_id).await {
+                Ok(true) => info!(
+                    "Retracted '{}' in {}, its message was deleted",
+                    code,
+                    sink.name()
+                ),
+                Ok(false) => warn!(
+                    "{} cannot update codes, expire '{}' (ID {:?}) by hand, its message was deleted",
+                    sink.name(),
+                    code,
+                    api_id
+                ),
+                Err(e) => {
+                    failed = true;
+                    error!("Error retracting '{}' in {}: {}", code, sink.name(), e);
+                }
+            }
+        }
+
+        if !failed {
+            cache.retract(&code, now);
+        }
+    }
+}
+
+/// The outcome of submitting one code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Source the code was submitted from, e.g. `discord.default`
+    pub source: String,
+    /// ID the licc API assigned, `None` in dry run or when it failed
+    pub id: Option<i32>,
+    pub expires_at: u64,
+    /// The only platform the code can be redeemed on
+    pub platform: Option<String>,
+    /// Who the code was attributed to and how sure that is, for sources that had to guess
+    pub attribution: Option<CreatorAttribution>,
+    /// When the message the code was found in was posted, for sources that tell
+    pub message_ts: Option<u64>,
+}
+
+struct Submitted<'a> {
+    from: &'a str,
+    request: InsertCodeRequest,
+    response: Option<i32>,
+    failed: bool,
+    /// The sink and error, if every sink that failed did so permanently
+    rejected: Option<(String, String)>,
+    metadata: Metadata,
+    /// Status and ID per sink the code was sent to in this attempt
+    outcomes: Vec<(String, RemoteStatus, Option<i32>)>,
+    /// Not sent as the run is out of time, see [`Budget`]
+    deferred: bool,
+}
+
+/// Submits the codes one after another, sending each to `results` once every sink had it. Lanes run side by side
+/// sharing the sinks of the run.
+async fn submit_lane<'a>(
+    sinks: &[Box<dyn OutputSink>],
+    lane: Vec<(&'a str, InsertCodeRequest, Metadata)>,
+    stored: &HashSet<(String, String)>,
+    deadline: Option<Instant>,
+    results: mpsc::UnboundedSender<Submitted<'a>>,
+) {
+    for (from, request, mut metadata) in lane {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let _ = results.send(Submitted {
+                from,
+                request,
+                response: None,
+                failed: false,
+                rejected: None,
+                metadata,
+                outcomes: vec![],
+                deferred: true,
+            });
+            continue;
+        }
+
+        let mut response: Option<i32> = None;
+        let mut failed = false;
+        let mut transient = false;
+        let mut rejected: Option<(String, String)> = None;
+        let mut outcomes = vec![];
+        let mut span = telemetry::code(from, &request.code);
+
+        for sink in sinks {
+            if stored.contains(&(request.code.clone(), sink.name().to_string())) {
+                debug!(
+                    "Skipping '{}' for {}, it stored it in an earlier run",
+                    request.code,
+                    sink.name()
+                );
+                continue;
+            }
+
+            let mut insert = span.client(&format!("insert {}", sink.name()));
+            metadata.trace = telemetry::enabled().then(|| insert.traceparent());
+
+            intent::begin(from, sink.name(), &request);
+            let result = sink.submit_with_metadata(&request, &metadata).await;
+            audit::record(&audit::Entry::new(from, sink.name(), &request, &result));
+            if let Err(e) = &result {
+                insert.fail(e);
+            }
+            insert.end();
+
+            match result {
+                Ok(id) => {
+                    response = response.or(id);
+                    outcomes.push((sink.name().to_string(), RemoteStatus::Stored, id));
+                }
+                Err(e) => {
+                    failed = true;
+                    outcomes.push((sink.name().to_string(), RemoteStatus::Failed, None));
+                    match e.is_permanent() {
+                        true => {
+                            rejected.get_or_insert((sink.name().to_string(), e.to_string()));
+                        }
+                        false => transient = true,
+                    }
+                    error!(
+                        "Error ({}: {} -> {}): {}",
+                        from,
+                        request.code,
+                        sink.name(),
+                        e
+                    );
+                }
+            }
+        }
+
+        metadata.trace = None;
+        if failed {
+            span.fail("not stored by every output");
+        }
+        span.end();
+
+        let _ = results.send(Submitted {
+            from,
+            request,
+            response,
+            failed,
+            rejected: rejected.filter(|_| !transient),
+            metadata,
+            outcomes,
+            deferred: false,
+        });
+    }
+}
+
+/// Schedules another attempt at a code an output failed to store, or moves it to the dead-letter queue once
+/// `retry.max_attempts` ran out.
+fn reschedule(config: &Config, s: &Submitted) {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let Some(attempts) = retry::failed(&config.retry, s.from, &s.request, now) else {
+        return;
+    };
+
+    let sink = s
+        .outcomes
+        .iter()
+        .find(|(_, status, _)| *status == RemoteStatus::Failed)
+        .map(|(sink, _, _)| sink.as_str())
+        .unwrap_or_default();
+    let reason = format!("gave up after {} failed attempts", attempts);
+    warn!(
+        "'{}' from {} failed to submit to {} {} times, moved to the dead-letter queue",
+        s.request.code, s.from, sink, attempts
+    );
+    dlq::add(&dlq::Letter::new(s.from, sink, &s.request, &reason));
+}
+
+/// Logs the outcome per code, by source and then by when it was posted, so the reports of consecutive runs can be
+/// diffed.
+pub fn report(config: &Config, responses: HashMap<String, Response>) {
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    for (code, response) in in_report_order(responses) {
+        let mut expires = describe_time(response.expires_at, now);
+        if let Some(platform) = &response.platform {
+            expires = format!("{}, {} only", expires, platform);
+        }
+        if let Some(attribution) = &response.attribution {
+            expires = format!("{}, by {}", expires, attribution);
+        }
+
+        match response.id {
+            Some(num) => {
+                info!("Stored '{}': {}, expires {}", code, num, expires);
+            }
+            None => {
+                if !config.dry_run.submits() {
+                    info!("Stored '{}': No, expires {}", code, expires);
+                } else {
+                    warn!("Stored '{}': No, expires {}", code, expires);
+
+                    if config.fuzzy_codes {
+                        suggest(&code);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `responses` by source, then by message timestamp, with the code breaking ties and codes without a timestamp first.
+fn in_report_order(responses: HashMap<String, Response>) -> Vec<(String, Response)> {
+    let mut responses: Vec<(String, Response)> = responses.into_iter().collect();
+    responses.sort_by(|(a, ra), (b, rb)| {
+        (&ra.source, ra.message_ts, a).cmp(&(&rb.source, rb.message_ts, b))
+    });
+
+    responses
+}
+
+/// e.g. `2024-01-26T00:00:00Z (in 5 days)`, so bad date parses stand out without converting timestamps by hand.
+pub(crate) fn describe_time(ts: u64, now: u64) -> String {
+    let rfc3339 = match time::OffsetDateTime::from_unix_timestamp(ts as i64) {
+        Ok(t) => format!(
+            "{}T{:02}:{:02}:{:02}Z",
+            t.date(),
+            t.hour(),
+            t.minute(),
+            t.second()
+        ),
+        Err(_) => ts.to_string(),
+    };
+
+    let (amount, unit) = match ts.abs_diff(now) {
+        secs if secs < 60 * 60 => (secs / 60, "minute"),
+        secs if secs < 24 * 60 * 60 => (secs / (60 * 60), "hour"),
+        secs => (secs / (24 * 60 * 60), "day"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+
+    match ts >= now {
+        true => format!("{} (in {} {}{})", rfc3339, amount, unit, plural),
+        false => format!("{} ({} {}{} ago)", rfc3339, amount, unit, plural),
+    }
+}
+
+/// The code `failure` is about if it was rejected as invalid, e.g. for its length, as the first line of its content.
+fn invalid_code(failure: &Failure) -> Option<String> {
+    if !failure.reason.to_lowercase().starts_with("invalid code") {
+        return None;
+    }
+
+    failure
+        .content
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .map(normalize_code)
+}
+
+fn suggest(code: &str) {
+    let variants = confusion_variants(code);
+
+    if !variants.is_empty() {
+        warn!(
+            "'{}' was rejected, it may have been mistyped as one of: {}",
+            code,
+            variants.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_invalid_code() {
+        let mut failure = Failure {
+            location: "discord.default".to_string(),
+            reason: "invalid code, 'ABCD-EFGH-IJK' has 11 characters".to_string(),
+            content: "abcd-efgh-ijk".to_string(),
+        };
+        assert_eq!(invalid_code(&failure), Some("ABCD-EFGH-IJK".to_string()));
+
+        failure.reason = "Invalid code length".to_string();
+        failure.content = "\n**ABCD-EFGH-IJK**\nfoo\nhttps://www.twitch.tv/foo".to_string();
+        assert_eq!(invalid_code(&failure), Some("ABCD-EFGH-IJK".to_string()));
+
+        failure.reason = "Missing creator URL".to_string();
+        assert_eq!(invalid_code(&failure), None);
+    }
+
+    #[test]
+    fn test_log_failure_once() {
+        let mut logged = BTreeMap::new();
+        let mut failure = Failure {
+            location: "https://discord.com/channels/1/2/3".to_string(),
+            reason: "Invalid code".to_string(),
+            content: "not a code".to_string(),
+        };
+
+        assert!(log_failure("discord.default", &failure, &mut logged, 0));
+        assert!(!log_failure("discord.default", &failure, &mut logged, 60));
+
+        failure.content = "still not a code".to_string();
+        assert!(log_failure("discord.default", &failure, &mut logged, 120));
+        assert_eq!(logged.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_isolated() {
+        assert_eq!(isolated(async { 1 }).await, Ok(1));
+        assert_eq!(
+            isolated(async { panic!("bad unwrap") }).await,
+            Err::<(), _>("bad unwrap".to_string())
+        );
+        assert_eq!(
+            isolated(async { panic!("{} bad unwraps", 2) }).await,
+            Err::<(), _>("2 bad unwraps".to_string())
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut config = Config::default();
+        assert!(!Budget::new(&config).exceeded());
+        assert_eq!(
+            Budget::new(&config).timeout(&config),
+            source_timeout(&config)
+        );
+
+        config.max_run_seconds = Some(0);
+        let budget = Budget::new(&config);
+        assert!(budget.exceeded());
+        assert_eq!(budget.timeout(&config), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_prefer_expiries() {
+        let request = |code: &str, expires_at: u64| InsertCodeRequest {
+            code: code.to_string(),
+            expires_at,
+            creator: licc::write::SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+
+        let mut requests: BTreeMap<&str, Vec<InsertCodeRequest>> = BTreeMap::new();
+        requests.insert(
+            "discord.default",
+            vec![
+                request("ABCD-EFGH-IJKL", 1706227200),
+                request("MNOP-QRST-UVWX", 1706227200),
+            ],
+        );
+        let mut guessed = HashSet::from(["ABCD-EFGH-IJKL".to_string()]);
+        let authoritative = HashMap::from([("ABCD-EFGH-IJKL".to_string(), 1706400000)]);
+
+        prefer_expiries(&mut requests, &mut guessed, &authoritative);
+
+        assert_eq!(requests["discord.default"][0].expires_at, 1706400000);
+        assert_eq!(requests["discord.default"][1].expires_at, 1706227200);
+        assert!(guessed.is_empty());
+    }
+
+    #[test]
+    fn test_failure_summary() {
+        let failure = Failure {
+            location: "https://discord.com/channels/1/2/3".to_string(),
+            reason: "Invalid code".to_string(),
+            content: format!("\n  New code: `{}`\nsecond line", "A".repeat(100)),
+        };
+        let failures: Vec<(&str, &Failure)> =
+            (0..12).map(|_| ("discord.default", &failure)).collect();
+
+        let summary = failure_summary(&failures);
+        let lines: Vec<&str> = summary.lines().collect();
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "12 new items could not be parsed:");
+        assert_eq!(
+            lines[1],
+            format!(
+                "- discord.default https://discord.com/channels/1/2/3: Invalid code `New code: '{}…`",
+                "A".repeat(69)
+            )
+        );
+        assert_eq!(lines[11], "and 2 more");
+        assert!(!summary.contains("second line"));
+    }
+
+    #[test]
+    fn test_in_report_order() {
+        let responses = [
+            ("AAAA-AAAA-AAAA", "github.default", None),
+            ("ZZZZ-ZZZZ-ZZZZ", "discord.default", Some(100)),
+            ("BBBB-BBBB-BBBB", "discord.default", Some(200)),
+            ("YYYY-YYYY-YYYY", "discord.default", Some(100)),
+            ("CCCC-CCCC-CCCC", "discord.default", None),
+        ]
+        .into_iter()
+        .map(|(code, source, message_ts)| {
+            let response = Response {
+                source: source.to_string(),
+                id: None,
+                expires_at: 1706227200,
+                platform: None,
+                attribution: None,
+                message_ts,
+            };
+            (code.to_string(), response)
+        })
+        .collect();
+
+        let order: Vec<String> = in_report_order(responses)
+            .into_iter()
+            .map(|(code, _)| code)
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                "CCCC-CCCC-CCCC",
+                "YYYY-YYYY-YYYY",
+                "ZZZZ-ZZZZ-ZZZZ",
+                "BBBB-BBBB-BBBB",
+                "AAAA-AAAA-AAAA",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_time() {
+        // 2024-01-15
+        let now = 1705276800;
+
+        assert_eq!(
+            describe_time(now + 5 * 24 * 60 * 60, now),
+            "2024-01-20T00:00:00Z (in 5 days)"
+        );
+        assert_eq!(
+            describe_time(now + 60 * 60, now),
+            "2024-01-15T01:00:00Z (in 1 hour)"
+        );
+        assert_eq!(
+            describe_time(now - 90, now),
+            "2024-01-14T23:58:30Z (1 minute ago)"
+        );
+    }
+}
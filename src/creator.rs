@@ -0,0 +1,429 @@
+use crate::config::{dir, UrlRule};
+use crate::storage;
+use licc::write::SourceLookup;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// Rules applied to creator urls when none are configured: https only, one spelling of twitch, no youtube share tracking.
+pub fn default_url_rules() -> Vec<UrlRule> {
+    [
+        (r"^http://", "https://"),
+        (
+            r"(?i)^https://(?:www\.)?twitch\.tv/",
+            "https://www.twitch.tv/",
+        ),
+        (
+            r"^(https://(?:www\.)?(?:youtu\.be|youtube\.com)/[^#]*?)[?&]si=[^&#]*$",
+            "$1",
+        ),
+        (
+            r"^(https://(?:www\.)?(?:youtu\.be|youtube\.com)/[^#]*?[?&])si=[^&#]*&",
+            "$1",
+        ),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| UrlRule {
+        pattern: pattern.to_string(),
+        replacement: replacement.to_string(),
+    })
+    .collect()
+}
+
+/// The compiled `creator_url_rules`, each applied in turn to every creator url before it is submitted.
+pub struct UrlRules {
+    rules: Vec<(Regex, String)>,
+}
+
+impl UrlRules {
+    pub fn new(rules: &[UrlRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(re) => Some((re, rule.replacement.clone())),
+                Err(e) => {
+                    error!("Invalid creator url rule '{}': {}", rule.pattern, e);
+                    None
+                }
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn apply(&self, url: &str) -> String {
+        let mut url = url.to_string();
+
+        for (pattern, replacement) in &self.rules {
+            url = pattern.replace_all(&url, replacement.as_str()).to_string();
+        }
+
+        url
+    }
+}
+
+/// Path segments of links to a single video or stream rather than to a creator, e.g. `watch` of
+/// `https://www.youtube.com/watch?v=abc123`.
+const VIDEO_PATHS: [&str; 5] = ["watch", "shorts", "live", "videos", "clip"];
+
+/// Identifies a creator however their url is spelled: without scheme, `www.`, query or trailing slash. Links to a
+/// single video say nothing about whose it is, so those creators are told apart by name instead.
+pub fn creator_key(name: &str, url: &str) -> String {
+    let url = url.to_lowercase();
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let url = url.strip_prefix("www.").unwrap_or(url);
+    let url = url.strip_prefix("m.").unwrap_or(url).trim_end_matches('/');
+
+    let mut segments = url.split('/');
+    let host = segments.next().unwrap_or_default();
+    let is_video = host == "youtu.be"
+        || segments
+            .next()
+            .is_some_and(|segment| VIDEO_PATHS.contains(&segment));
+
+    match is_video || url.is_empty() {
+        true => format!("name:{}", name.trim().to_lowercase()),
+        false => url.to_string(),
+    }
+}
+
+/// The name and url each creator was first submitted with, by [`creator_key`]; kept in `creators.toml` so a creator
+/// is submitted the same way every time, see `canonical_creators`.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Creators {
+    #[serde(default)]
+    pub known: BTreeMap<String, Creator>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub url: String,
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("creators.toml")
+}
+
+impl Creators {
+    /// Reads `creators.toml`, starting afresh if there is none.
+    pub fn read() -> Self {
+        storage::read_to_string(&file())
+            .ok()
+            .and_then(|s| {
+                toml::from_str(&s)
+                    .inspect_err(|e| error!("Error reading creators.toml, starting afresh: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self) {
+        let written = toml::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| storage::write(&file(), contents).map_err(|e| e.to_string()));
+
+        if let Err(e) = written {
+            error!("Error writing creators.toml: {}", e);
+        }
+    }
+
+    /// Replaces `creator` with how the same creator was submitted before, or remembers it if it is new.
+    pub fn canonicalize(&mut self, creator: &mut SourceLookup) {
+        let key = creator_key(&creator.name, &creator.url);
+
+        match self.known.get(&key) {
+            Some(known) if known.name != creator.name || known.url != creator.url => {
+                debug!(
+                    "Submitting creator {} ({}) as {} ({})",
+                    creator.name, creator.url, known.name, known.url
+                );
+                creator.name = known.name.clone();
+                creator.url = known.url.clone();
+            }
+            Some(_) => {}
+            None => {
+                self.known.insert(
+                    key,
+                    Creator {
+                        name: creator.name.clone(),
+                        url: creator.url.clone(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// How the name of a code's creator was worked out, most reliable first.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Derivation {
+    /// Posted by the game itself
+    Official,
+    /// The last path segment of the creator url, e.g. `foo` of `https://www.twitch.tv/foo`
+    UrlSlug,
+    /// The name line of a code message, when the url has no usable slug
+    FallbackLine,
+    /// The `creator_name` configured for the source, when the text links no creator
+    ConfigAlias,
+    /// Nothing to go on
+    Unresolved,
+}
+
+impl Derivation {
+    pub fn confidence(self) -> Confidence {
+        match self {
+            Derivation::Official | Derivation::UrlSlug => Confidence::High,
+            Derivation::FallbackLine => Confidence::Medium,
+            Derivation::ConfigAlias | Derivation::Unresolved => Confidence::Low,
+        }
+    }
+}
+
+impl std::fmt::Display for Derivation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Derivation::Official => "official",
+            Derivation::UrlSlug => "url_slug",
+            Derivation::FallbackLine => "fallback_line",
+            Derivation::ConfigAlias => "config_alias",
+            Derivation::Unresolved => "unresolved",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl std::fmt::Display for Confidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Who a code is attributed to and how that was decided, recorded in the report and the archive so bad attributions
+/// in the database can be traced back to the heuristic that made them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreatorAttribution {
+    pub name: String,
+    pub url: String,
+    pub derivation: Derivation,
+    pub confidence: Confidence,
+}
+
+impl CreatorAttribution {
+    pub fn new(name: String, url: String, derivation: Derivation) -> Self {
+        Self {
+            name,
+            url,
+            derivation,
+            confidence: derivation.confidence(),
+        }
+    }
+
+    /// The creator of a code message: the slug of `url`, then the message's name line, then unresolved.
+    pub fn from_message(url: &str, fallback_line: Option<&str>) -> Self {
+        // https://twitch.tv/foo -> foo, a `?` means it is likely a youtube link with no name in it
+        let slug = url
+            .split('/')
+            .next_back()
+            .map(str::to_lowercase)
+            .filter(|slug| !slug.contains('?'));
+
+        let (name, derivation) = match (slug, fallback_line) {
+            (Some(slug), _) => (slug, Derivation::UrlSlug),
+            (None, Some(line)) => {
+                debug!("Creator name looks fishy, using fallback: {}", line);
+                (line.to_string(), Derivation::FallbackLine)
+            }
+            (None, None) => ("Unknown".to_string(), Derivation::Unresolved),
+        };
+
+        Self::new(name, url.to_string(), derivation)
+    }
+
+    /// The creator of free text: the first link in it, then the source's configured creator, then unresolved.
+    pub fn from_text(text: &str, configured_name: &str, configured_url: &str) -> Self {
+        if let Some((name, url)) = crate::parse::find_creator(text) {
+            return Self::new(name, url, Derivation::UrlSlug);
+        }
+
+        let derivation = match configured_name.is_empty() {
+            true => Derivation::Unresolved,
+            false => Derivation::ConfigAlias,
+        };
+
+        Self::new(
+            configured_name.to_string(),
+            configured_url.to_string(),
+            derivation,
+        )
+    }
+}
+
+impl std::fmt::Display for CreatorAttribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {} confidence)",
+            self.name, self.derivation, self.confidence
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_url_rules() {
+        let rules = UrlRules::new(&default_url_rules());
+
+        assert_eq!(
+            rules.apply("http://www.twitch.tv/foo"),
+            "https://www.twitch.tv/foo"
+        );
+        assert_eq!(
+            rules.apply("https://Twitch.TV/foo"),
+            "https://www.twitch.tv/foo"
+        );
+        assert_eq!(
+            rules.apply("https://youtu.be/abc123?si=tracking"),
+            "https://youtu.be/abc123"
+        );
+        assert_eq!(
+            rules.apply("https://www.youtube.com/watch?v=abc123&si=tracking"),
+            "https://www.youtube.com/watch?v=abc123"
+        );
+        assert_eq!(
+            rules.apply("https://youtu.be/abc123?si=tracking&t=10"),
+            "https://youtu.be/abc123?t=10"
+        );
+        assert_eq!(
+            rules.apply("https://example.com/?si=kept"),
+            "https://example.com/?si=kept"
+        );
+    }
+
+    #[test]
+    fn test_creator_key() {
+        assert_eq!(
+            creator_key("foo", "https://www.twitch.tv/Foo/"),
+            "twitch.tv/foo"
+        );
+        assert_eq!(creator_key("foo", "http://twitch.tv/foo"), "twitch.tv/foo");
+        assert_eq!(
+            creator_key("Gina Darling", "https://www.youtube.com/watch?v=abc123"),
+            "name:gina darling"
+        );
+        assert_eq!(
+            creator_key("Gina Darling ", "https://youtu.be/def456"),
+            "name:gina darling"
+        );
+        assert_eq!(
+            creator_key("gina", "https://m.youtube.com/@gina?si=tracking"),
+            "youtube.com/@gina"
+        );
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut creators = Creators::default();
+        let lookup = |name: &str, url: &str| SourceLookup {
+            name: name.to_string(),
+            url: url.to_string(),
+        };
+
+        let mut first = lookup("Gina Darling", "https://www.youtube.com/watch?v=abc123");
+        creators.canonicalize(&mut first);
+        assert_eq!(first.url, "https://www.youtube.com/watch?v=abc123");
+
+        let mut next_week = lookup("Gina Darling", "https://www.youtube.com/watch?v=def456");
+        creators.canonicalize(&mut next_week);
+        assert_eq!(next_week.url, "https://www.youtube.com/watch?v=abc123");
+
+        let mut other = lookup("foo", "https://twitch.tv/foo/");
+        creators.canonicalize(&mut other);
+        assert_eq!(other.url, "https://twitch.tv/foo/");
+        assert_eq!(creators.known.len(), 2);
+    }
+
+    #[test]
+    fn test_attribution_from_message() {
+        let slug = CreatorAttribution::from_message("https://www.twitch.tv/Foo", Some("Foo"));
+        assert_eq!(slug.name, "foo");
+        assert_eq!(slug.derivation, Derivation::UrlSlug);
+        assert_eq!(slug.confidence, Confidence::High);
+
+        let fallback = CreatorAttribution::from_message(
+            "https://www.youtube.com/watch?v=abc123",
+            Some("Gina Darling"),
+        );
+        assert_eq!(fallback.name, "Gina Darling");
+        assert_eq!(fallback.derivation, Derivation::FallbackLine);
+        assert_eq!(fallback.confidence, Confidence::Medium);
+
+        let unresolved =
+            CreatorAttribution::from_message("https://www.youtube.com/watch?v=1", None);
+        assert_eq!(unresolved.name, "Unknown");
+        assert_eq!(unresolved.confidence, Confidence::Low);
+    }
+
+    #[test]
+    fn test_attribution_from_text() {
+        let linked = CreatorAttribution::from_text(
+            "ABCD-EFGH-IJKL via https://www.twitch.tv/foo",
+            "community",
+            "https://example.com",
+        );
+        assert_eq!(linked.name, "foo");
+        assert_eq!(linked.derivation, Derivation::UrlSlug);
+
+        let configured =
+            CreatorAttribution::from_text("ABCD-EFGH-IJKL", "community", "https://example.com");
+        assert_eq!(configured.name, "community");
+        assert_eq!(configured.derivation, Derivation::ConfigAlias);
+        assert_eq!(
+            configured.to_string(),
+            "community (config_alias, low confidence)"
+        );
+
+        let unresolved = CreatorAttribution::from_text("ABCD-EFGH-IJKL", "", "");
+        assert_eq!(unresolved.derivation, Derivation::Unresolved);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_skipped() {
+        let rules = UrlRules::new(&[
+            UrlRule {
+                pattern: "(".to_string(),
+                replacement: String::new(),
+            },
+            UrlRule {
+                pattern: "/$".to_string(),
+                replacement: String::new(),
+            },
+        ]);
+
+        assert_eq!(
+            rules.apply("https://example.com/foo/"),
+            "https://example.com/foo"
+        );
+    }
+}
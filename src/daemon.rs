@@ -0,0 +1,397 @@
+use crate::config::{self, Config};
+use crate::crawl::Response;
+use crate::parse::TimeParser;
+use crate::state::State;
+use crate::{cache, crawl, handler, logging, notify, script, serve, state, validate};
+use ::notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use licc::write::InsertCodeRequest;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+#[cfg_attr(not(unix), allow(dead_code))]
+enum Wake {
+    Interval,
+    Reload,
+    Poll,
+    Task(Task),
+}
+
+/// Work the tasks spawned by the daemon hand to its loop, which runs it between cycles.
+///
+/// Only the loop reads and writes the cache, a cycle writes back the cache it read when it started and would
+/// drop whatever a task stored in the meantime. Queries that only look at it, e.g. `status`, use [`cache::peek`].
+pub enum Task {
+    /// Codes a source yielded outside of a cycle, e.g. posted on IRC. Reported like a cycle's without a `reply`.
+    Submit {
+        source: String,
+        requests: Vec<InsertCodeRequest>,
+        reply: Option<oneshot::Sender<Outcome>>,
+    },
+    /// Pauses or resumes a source, replying whether that changed anything
+    Pause {
+        source: String,
+        paused: bool,
+        reply: oneshot::Sender<bool>,
+    },
+}
+
+/// What became of the codes of a [`Task::Submit`].
+pub struct Outcome {
+    pub responses: HashMap<String, Response>,
+    /// Whether the codes were only logged, as `dry_run` is set
+    pub dry_run: bool,
+}
+
+/// Polls all sources every `daemon.interval_secs` until the process is stopped.
+///
+/// On unix, SIGHUP reloads `config.toml` and SIGUSR1 polls immediately. Signals are only acted upon
+/// between cycles, after the cache of the previous cycle has been written to disk.
+///
+/// With `daemon.watch`, changes to `config.toml` are applied the same way before the next poll, logging what changed.
+///
+/// With `daemon.silent_days` set, a notification is sent once for every enabled source that has not
+/// yielded a code in that many days, as the channel may have moved, changed format or the bot lost access.
+///
+/// Changes to `calendar.listen`, `control` (including its `http` settings) and `irc` require a restart, IRC channels are only idled in while in daemon mode.
+/// Both parse expiries with the `parser` settings they started with, and hand codes to the loop as a [`Task`].
+pub async fn run(mut config: Config) {
+    let mut signals = Signals::new();
+    let (tasks, mut queue) = mpsc::unbounded_channel();
+    let changed = Arc::new(AtomicBool::new(false));
+    let _watcher = match config.daemon.watch {
+        true => watch(changed.clone()),
+        false => None,
+    };
+
+    let sources = Arc::new(RwLock::new(config.sources()));
+    if let Some(addr) = config.calendar.listen.clone() {
+        cache::setup();
+        tokio::spawn(serve::listen(addr, sources.clone()));
+    }
+
+    let state = state::read();
+    for (name, irc) in &config.irc {
+        let source = format!("irc.{}", name);
+        if irc.enabled && config.owns(&source) && !state.is_paused(&source) {
+            let timeparser = TimeParser::from_config(&config.parser).locale(irc.locale);
+            tokio::spawn(handler::irc::run(
+                name.clone(),
+                irc.clone(),
+                timeparser,
+                tasks.clone(),
+            ));
+        }
+    }
+
+    #[cfg(feature = "discord")]
+    if config.control.enabled {
+        tokio::spawn(crate::control::run(
+            config.control.clone(),
+            config.http.clone(),
+            TimeParser::from_config(&config.parser),
+            tasks.clone(),
+        ));
+    }
+
+    loop {
+        if changed.swap(false, Ordering::Relaxed) {
+            info!("config.toml changed, reloading configuration");
+            reload(&mut config, &sources);
+        }
+
+        crawl::once(&config).await;
+        watchdog(&config).await;
+
+        let mut next = Instant::now() + Duration::from_secs(config.daemon.interval_secs);
+
+        loop {
+            match signals.wait(next, &mut queue).await {
+                Wake::Interval => break,
+                Wake::Poll => {
+                    info!("Received SIGUSR1, polling all sources");
+                    break;
+                }
+                Wake::Reload => {
+                    info!("Received SIGHUP, reloading configuration");
+                    reload(&mut config, &sources);
+                    next = Instant::now() + Duration::from_secs(config.daemon.interval_secs);
+                }
+                Wake::Task(task) => handle(&config, task).await,
+            }
+        }
+    }
+}
+
+/// Runs `task` with the current `config`, reading and writing the cache around it like a cycle does.
+async fn handle(config: &Config, task: Task) {
+    match task {
+        Task::Submit {
+            source,
+            requests,
+            reply,
+        } => {
+            crawl::heard(&[&source]);
+            cache::setup();
+            let mut cache = cache::read();
+
+            let responses = crawl::submit(
+                config,
+                &mut cache,
+                BTreeMap::from([(source.as_str(), requests)]),
+            )
+            .await;
+
+            cache::write(cache).await;
+
+            match reply {
+                Some(reply) => {
+                    let _ = reply.send(Outcome {
+                        responses,
+                        dry_run: !config.dry_run.submits(),
+                    });
+                }
+                None => crawl::report(config, responses),
+            }
+        }
+        Task::Pause {
+            source,
+            paused,
+            reply,
+        } => {
+            let mut state = state::read();
+            let changed = state.set_paused(&source, paused);
+            state::write(&state);
+
+            let _ = reply.send(changed);
+        }
+    }
+}
+
+/// Replaces `config` with a freshly read `config.toml` and logs the differences, keeping it if the new one is invalid.
+///
+/// `sources` are those `/sources` lists, updated to the new config.
+fn reload(config: &mut Config, sources: &RwLock<Vec<(String, bool)>>) {
+    let new = match config::try_read() {
+        Ok(new) => new,
+        Err(e) => {
+            error!("Keeping the current configuration: {}", e);
+            return;
+        }
+    };
+
+    let changes = config::diff(config, &new);
+    if changes.is_empty() {
+        info!("Configuration unchanged");
+    }
+    for change in changes {
+        info!("Configuration changed: {}", change);
+    }
+
+    *config = new;
+    logging::configure(config);
+    cache::set_limit(config.cache.limit);
+    validate::configure(&config.validation);
+    script::configure(&config.scripts);
+    *sources.write().unwrap_or_else(|e| e.into_inner()) = config.sources();
+}
+
+/// Sets `changed` whenever the config file is written, the watcher stops when dropped.
+fn watch(changed: Arc<AtomicBool>) -> Option<RecommendedWatcher> {
+    let file = config::file();
+    let name = file.file_name()?.to_os_string();
+    let parent = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => std::path::PathBuf::from("."),
+    };
+
+    let watcher = ::notify::recommended_watcher(move |event: ::notify::Result<::notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+
+        if !event.kind.is_access()
+            && event
+                .paths
+                .iter()
+                .any(|path| path.file_name().is_some_and(|n| n == name))
+        {
+            changed.store(true, Ordering::Relaxed);
+        }
+    });
+
+    // The directory is watched rather than the file, editors often replace it instead of writing to it.
+    let watched = watcher.and_then(|mut watcher| {
+        watcher
+            .watch(&parent, RecursiveMode::NonRecursive)
+            .map(|_| watcher)
+    });
+
+    match watched {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            error!("Not watching {} for changes: {}", file.display(), e);
+            None
+        }
+    }
+}
+
+/// Notifies about sources that went silent for longer than `daemon.silent_days`.
+async fn watchdog(config: &Config) {
+    let Some(days) = config.daemon.silent_days else {
+        return;
+    };
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let mut state = state::read();
+    let sources = watched(config, &state);
+    let silent = silent(&mut state, &sources, days, now);
+    state::write(&state);
+
+    for (source, since) in silent {
+        notify::send(
+            config,
+            &format!(
+                "{} has not yielded a code in {} days, check whether it moved, changed format or revoked access",
+                source,
+                (now - since) / 86400
+            ),
+        )
+        .await;
+    }
+}
+
+/// Enabled and unpaused sources polled by this shard.
+fn watched(config: &Config, state: &State) -> Vec<String> {
+    let discord = config
+        .discord
+        .iter()
+        .filter(|(_, d)| d.enabled)
+        .map(|(name, _)| format!("discord.{}", name));
+    let github = config
+        .github
+        .iter()
+        .filter(|(_, g)| g.enabled)
+        .map(|(name, _)| format!("github.{}", name));
+    let irc = config
+        .irc
+        .iter()
+        .filter(|(_, i)| i.enabled)
+        .map(|(name, _)| format!("irc.{}", name));
+    let game_api = config
+        .game_api
+        .iter()
+        .filter(|(_, g)| g.enabled)
+        .map(|(name, _)| format!("game_api.{}", name));
+    let exec = config
+        .exec
+        .iter()
+        .filter(|(_, e)| e.enabled)
+        .map(|(name, _)| format!("exec.{}", name));
+
+    discord
+        .chain(github)
+        .chain(irc)
+        .chain(game_api)
+        .chain(exec)
+        .filter(|source| config.owns(source) && !state.is_paused(source))
+        .collect()
+}
+
+/// Sources silent for longer than `days` that were not reported yet, with when they last yielded a code.
+///
+/// Sources watched for the first time start counting from `now`.
+fn silent(state: &mut State, sources: &[String], days: u32, now: u64) -> Vec<(String, u64)> {
+    let mut silent = vec![];
+
+    for source in sources {
+        let since = *state.last_code.entry(source.clone()).or_insert(now);
+
+        if now.saturating_sub(since) > days as u64 * 86400 && state.silent.insert(source.clone()) {
+            silent.push((source.clone(), since));
+        }
+    }
+
+    silent
+}
+
+#[cfg(unix)]
+struct Signals {
+    hangup: tokio::signal::unix::Signal,
+    user1: tokio::signal::unix::Signal,
+}
+
+#[cfg(unix)]
+impl Signals {
+    fn new() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Self {
+            hangup: signal(SignalKind::hangup()).expect("Error registering SIGHUP handler"),
+            user1: signal(SignalKind::user_defined1()).expect("Error registering SIGUSR1 handler"),
+        }
+    }
+
+    /// Waits until `until`, a signal or a task in `queue`.
+    async fn wait(&mut self, until: Instant, queue: &mut mpsc::UnboundedReceiver<Task>) -> Wake {
+        tokio::select! {
+            _ = tokio::time::sleep_until(until) => Wake::Interval,
+            Some(task) = queue.recv() => Wake::Task(task),
+            _ = self.hangup.recv() => Wake::Reload,
+            _ = self.user1.recv() => Wake::Poll,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct Signals;
+
+#[cfg(not(unix))]
+impl Signals {
+    fn new() -> Self {
+        Self
+    }
+
+    async fn wait(&mut self, until: Instant, queue: &mut mpsc::UnboundedReceiver<Task>) -> Wake {
+        tokio::select! {
+            _ = tokio::time::sleep_until(until) => Wake::Interval,
+            Some(task) = queue.recv() => Wake::Task(task),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_silent() {
+        let mut state = State::default();
+        let sources = vec!["discord.default".to_string(), "github.default".to_string()];
+        let day = 86400;
+
+        assert!(silent(&mut state, &sources, 3, 0).is_empty());
+        state.heard("github.default", 2 * day);
+
+        assert_eq!(
+            silent(&mut state, &sources, 3, 4 * day),
+            vec![("discord.default".to_string(), 0)]
+        );
+        // only notified once
+        assert!(silent(&mut state, &sources, 3, 5 * day).is_empty());
+        assert_eq!(
+            silent(&mut state, &sources, 3, 6 * day),
+            vec![("github.default".to_string(), 2 * day)]
+        );
+
+        state.heard("discord.default", 6 * day);
+        assert!(!state.silent.contains("discord.default"));
+        assert_eq!(
+            silent(&mut state, &sources, 3, 10 * day),
+            vec![("discord.default".to_string(), 6 * day)]
+        );
+    }
+}
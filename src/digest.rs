@@ -0,0 +1,163 @@
+use crate::config::dir;
+use crate::storage;
+use std::collections::BTreeMap;
+
+/// How long runs are accumulated before the digest is posted.
+const PERIOD: u64 = 7 * 24 * 60 * 60;
+
+/// Sources listed by name in the digest, the rest are only counted.
+const TOP_SOURCES: usize = 5;
+
+/// What the runs since the last digest found, kept in `digest.toml` when `notify.digest` is set.
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Digest {
+    /// Unix timestamp of the first run accumulated
+    pub since: Option<u64>,
+    pub runs: usize,
+    /// Codes stored per source
+    #[serde(default)]
+    pub codes: BTreeMap<String, usize>,
+    /// Parse failures and failed submissions per source
+    #[serde(default)]
+    pub failures: BTreeMap<String, usize>,
+}
+
+impl Digest {
+    /// Adds the new codes and errors per source of a run at `now`.
+    pub fn add<'a>(&mut self, run: impl IntoIterator<Item = (&'a str, usize, usize)>, now: u64) {
+        self.since.get_or_insert(now);
+        self.runs += 1;
+
+        for (source, new, errors) in run {
+            if new > 0 {
+                *self.codes.entry(source.to_string()).or_default() += new;
+            }
+            if errors > 0 {
+                *self.failures.entry(source.to_string()).or_default() += errors;
+            }
+        }
+    }
+
+    /// Whether a week passed since the first run accumulated.
+    pub fn is_due(&self, now: u64) -> bool {
+        self.since
+            .is_some_and(|since| now.saturating_sub(since) >= PERIOD)
+    }
+
+    pub fn message(&self) -> String {
+        let since = self
+            .since
+            .and_then(|since| time::OffsetDateTime::from_unix_timestamp(since as i64).ok())
+            .map(|since| since.date().to_string())
+            .unwrap_or_default();
+        let found: usize = self.codes.values().sum();
+
+        let mut top: Vec<(&String, &usize)> = self.codes.iter().collect();
+        top.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let top: Vec<String> = top
+            .into_iter()
+            .take(TOP_SOURCES)
+            .map(|(source, codes)| format!("{} ({})", source, codes))
+            .collect();
+
+        let mut lines = vec![format!(
+            "Weekly digest: {} codes found in {} runs since {}",
+            found, self.runs, since
+        )];
+        if !top.is_empty() {
+            lines.push(format!("Top sources: {}", top.join(", ")));
+        }
+        lines.push(match self.failures.is_empty() {
+            true => "Failures: none".to_string(),
+            false => format!(
+                "Failures: {}",
+                self.failures
+                    .iter()
+                    .map(|(source, failures)| format!("{} ({})", source, failures))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        });
+
+        lines.join("\n")
+    }
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("digest.toml")
+}
+
+pub fn read() -> Digest {
+    match storage::read_to_string(&file()) {
+        Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+            error!("Error reading digest.toml, starting afresh: {}", e);
+            Digest::default()
+        }),
+        Err(_) => Digest::default(),
+    }
+}
+
+pub fn write(digest: &Digest) {
+    let written = toml::to_string(digest)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| storage::write(&file(), contents).map_err(|e| e.to_string()));
+
+    if let Err(e) = written {
+        error!("Error writing digest.toml: {}", e);
+    }
+}
+
+/// Adds a run to the digest, returning the digest to post if a week passed, which starts the next one.
+pub fn record<'a>(
+    run: impl IntoIterator<Item = (&'a str, usize, usize)>,
+    now: u64,
+) -> Option<String> {
+    let mut digest = read();
+    digest.add(run, now);
+
+    let message = digest.is_due(now).then(|| digest.message());
+    if message.is_some() {
+        digest = Digest::default();
+    }
+    write(&digest);
+
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        let mut digest = Digest::default();
+        let start = 1706227200;
+
+        digest.add([("discord.default", 2, 0), ("github.default", 0, 1)], start);
+        assert!(!digest.is_due(start + PERIOD - 1));
+
+        digest.add(
+            [("discord.default", 1, 2), ("game_api.official", 3, 0)],
+            start + PERIOD,
+        );
+        assert!(digest.is_due(start + PERIOD));
+
+        assert_eq!(
+            digest.message(),
+            "Weekly digest: 6 codes found in 2 runs since 2024-01-26\n\
+             Top sources: discord.default (3), game_api.official (3)\n\
+             Failures: discord.default (2), github.default (1)"
+        );
+    }
+
+    #[test]
+    fn test_digest_quiet_week() {
+        let mut digest = Digest::default();
+        digest.add([("discord.default", 0, 0)], 1706227200);
+
+        assert_eq!(
+            digest.message(),
+            "Weekly digest: 0 codes found in 1 runs since 2024-01-26\nFailures: none"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::config::{dir, Config};
+use crate::storage;
+use crate::{cache, crawl};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use std::collections::{BTreeMap, HashSet};
+
+/// A code an output rejected for good, kept in `dlq.jsonl` instead of being retried every run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Letter {
+    pub code: String,
+    pub source: String,
+    /// Unix timestamp of the rejection
+    pub rejected_at: u64,
+    pub sink: String,
+    pub reason: String,
+    pub expires_at: u64,
+    pub creator_name: String,
+    pub creator_url: String,
+    pub submitter_name: Option<String>,
+    pub submitter_url: Option<String>,
+}
+
+impl Letter {
+    pub fn new(source: &str, sink: &str, request: &InsertCodeRequest, reason: &str) -> Self {
+        Self {
+            code: request.code.clone(),
+            source: source.to_string(),
+            rejected_at: time::OffsetDateTime::now_utc().unix_timestamp() as u64,
+            sink: sink.to_string(),
+            reason: reason.to_string(),
+            expires_at: request.expires_at,
+            creator_name: request.creator.name.clone(),
+            creator_url: request.creator.url.clone(),
+            submitter_name: request.submitter.as_ref().map(|s| s.name.clone()),
+            submitter_url: request.submitter.as_ref().map(|s| s.url.clone()),
+        }
+    }
+
+    pub fn to_request(&self) -> InsertCodeRequest {
+        InsertCodeRequest {
+            code: self.code.clone(),
+            expires_at: self.expires_at,
+            creator: SourceLookup {
+                name: self.creator_name.clone(),
+                url: self.creator_url.clone(),
+            },
+            submitter: match (&self.submitter_name, &self.submitter_url) {
+                (Some(name), Some(url)) => Some(SourceLookup {
+                    name: name.clone(),
+                    url: url.clone(),
+                }),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Action {
+    /// Print every rejected code and why
+    List,
+    /// Submit rejected codes again, all of them unless a code is given
+    Retry { code: Option<String> },
+    /// Forget rejected codes, all of them unless a code is given
+    Purge { code: Option<String> },
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("dlq.jsonl")
+}
+
+pub fn read() -> Vec<Letter> {
+    let contents = match storage::read_to_string(&file()) {
+        Ok(contents) => contents,
+        Err(_) => return vec![],
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| error!("Skipping unreadable line in dlq.jsonl: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+pub fn write(letters: &[Letter]) {
+    let contents: String = letters
+        .iter()
+        .filter_map(|letter| serde_json::to_string(letter).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    if let Err(e) = storage::write(&file(), contents) {
+        error!("Error writing dlq.jsonl: {}", e);
+    }
+}
+
+/// Appends a rejected code, it is skipped by every run from now on.
+pub fn add(letter: &Letter) {
+    let written = serde_json::to_string(letter)
+        .map_err(std::io::Error::from)
+        .and_then(|line| storage::append(&file(), &(line + "\n")));
+
+    if let Err(e) = written {
+        error!("Error adding '{}' to dlq.jsonl: {}", letter.code, e);
+    }
+}
+
+/// Codes in the dead-letter queue, which are not submitted until retried.
+pub fn codes() -> HashSet<String> {
+    read().into_iter().map(|letter| letter.code).collect()
+}
+
+pub async fn run(config: &Config, action: Action) {
+    let letters = read();
+
+    match action {
+        Action::List => {
+            if letters.is_empty() {
+                println!("The dead-letter queue is empty.");
+            }
+
+            for letter in &letters {
+                println!(
+                    "{} from {} rejected by {} at {}: {}",
+                    letter.code, letter.source, letter.sink, letter.rejected_at, letter.reason
+                );
+            }
+        }
+        Action::Retry { code } => {
+            let (retry, keep): (Vec<Letter>, Vec<Letter>) =
+                letters.into_iter().partition(|letter| {
+                    code.is_none() || code.as_deref() == Some(letter.code.as_str())
+                });
+
+            // Codes rejected again are added back by the submission, which does not happen in dry run.
+            if config.dry_run.submits() {
+                write(&keep);
+            }
+
+            let mut requests: BTreeMap<&str, Vec<InsertCodeRequest>> = BTreeMap::new();
+            for letter in &retry {
+                requests
+                    .entry(letter.source.as_str())
+                    .or_default()
+                    .push(letter.to_request());
+            }
+
+            info!("Retrying {} codes from the dead-letter queue", retry.len());
+
+            cache::setup();
+            let mut cache = cache::read();
+            let responses = crawl::submit(config, &mut cache, requests).await;
+            crawl::report(config, responses);
+            cache::write(cache).await;
+        }
+        Action::Purge { code } => {
+            let before = letters.len();
+            let keep: Vec<Letter> = letters
+                .into_iter()
+                .filter(|letter| code.as_ref().is_some_and(|c| c != &letter.code))
+                .collect();
+
+            write(&keep);
+            info!(
+                "Purged {} codes from the dead-letter queue",
+                before - keep.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_letter_round_trip() {
+        let request = InsertCodeRequest {
+            code: "ABCD-EFGH-IJKL".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: Some(SourceLookup {
+                name: "bar".to_string(),
+                url: "https://discord.com/channels/1/2".to_string(),
+            }),
+        };
+
+        let letter = Letter::new("discord.default", "licc", &request, "unknown creator");
+        let line = serde_json::to_string(&letter).unwrap();
+        let read: Letter = serde_json::from_str(&line).unwrap();
+        assert_eq!(read, letter);
+
+        let retried = read.to_request();
+        assert_eq!(retried.code, request.code);
+        assert_eq!(retried.expires_at, request.expires_at);
+        assert_eq!(retried.creator.url, request.creator.url);
+        assert_eq!(retried.submitter.map(|s| s.name), Some("bar".to_string()));
+    }
+}
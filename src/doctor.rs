@@ -0,0 +1,150 @@
+use crate::config::{Config, OutputConfig};
+#[cfg(feature = "discord")]
+use crate::handler::discord;
+use crate::handler::{exec, game_api, github, irc};
+
+/// One line of the `liccrawler doctor` matrix.
+struct Check {
+    source: String,
+    check: String,
+    outcome: Result<(), String>,
+}
+
+/// Checks the credentials and permissions of every enabled source and of the licc API, printing a pass/fail matrix.
+///
+/// Exits with status 1 if any check failed, so it can gate a deployment.
+pub async fn run(config: &Config) {
+    let mut checks = vec![];
+    let mut push = |source: &str, results: Vec<(String, Result<(), String>)>| {
+        checks.extend(results.into_iter().map(|(check, outcome)| Check {
+            source: source.to_string(),
+            check,
+            outcome,
+        }))
+    };
+
+    if config.outputs.is_empty()
+        || config
+            .outputs
+            .iter()
+            .any(|output| matches!(output, OutputConfig::Licc))
+    {
+        push("client", licc(config).await);
+    }
+
+    #[cfg(feature = "discord")]
+    for (name, cfg) in config.discord.iter().filter(|(_, cfg)| cfg.enabled) {
+        push(
+            &format!("discord.{}", name),
+            discord::doctor(config, cfg).await,
+        );
+    }
+
+    for (name, cfg) in config.github.iter().filter(|(_, cfg)| cfg.enabled) {
+        push(
+            &format!("github.{}", name),
+            vec![("fetch file".to_string(), github::check(config, cfg).await)],
+        );
+    }
+
+    for (name, cfg) in config.game_api.iter().filter(|(_, cfg)| cfg.enabled) {
+        push(
+            &format!("game_api.{}", name),
+            vec![("fetch feed".to_string(), game_api::check(config, cfg).await)],
+        );
+    }
+
+    for (name, cfg) in config.exec.iter().filter(|(_, cfg)| cfg.enabled) {
+        push(
+            &format!("exec.{}", name),
+            vec![("run command".to_string(), exec::check(cfg).await)],
+        );
+    }
+
+    for (name, cfg) in config.irc.iter().filter(|(_, cfg)| cfg.enabled) {
+        let connected = irc::connect(cfg)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        push(
+            &format!("irc.{}", name),
+            vec![(format!("connect to {}", cfg.server), connected)],
+        );
+    }
+
+    print!("{}", render(&checks));
+
+    if checks.iter().any(|check| check.outcome.is_err()) {
+        std::process::exit(1);
+    }
+}
+
+/// The API key cannot be verified without submitting a code, only that it is set and the remote answers.
+async fn licc(config: &Config) -> Vec<(String, Result<(), String>)> {
+    let mut checks = vec![(
+        "api key set".to_string(),
+        match config.client.api_key.is_empty() {
+            true => Err("client.api_key is empty".to_string()),
+            false => Ok(()),
+        },
+    )];
+
+    if let Some(remote) = &config.client.remote_host {
+        let reachable = crate::http::client(&config.http)
+            .get(remote)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        checks.push((format!("reach {}", remote), reachable));
+    }
+
+    checks
+}
+
+fn render(checks: &[Check]) -> String {
+    let source_width = checks.iter().map(|c| c.source.len()).max().unwrap_or(0);
+    let check_width = checks.iter().map(|c| c.check.len()).max().unwrap_or(0);
+
+    checks
+        .iter()
+        .map(|c| {
+            let outcome = match &c.outcome {
+                Ok(()) => "pass".to_string(),
+                Err(e) => format!("FAIL {}", e),
+            };
+
+            format!(
+                "{:source_width$}  {:check_width$}  {}\n",
+                c.source, c.check, outcome
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let checks = vec![
+            Check {
+                source: "client".to_string(),
+                check: "api key set".to_string(),
+                outcome: Ok(()),
+            },
+            Check {
+                source: "discord.default".to_string(),
+                check: "read channel 2".to_string(),
+                outcome: Err("Missing Access".to_string()),
+            },
+        ];
+
+        assert_eq!(
+            render(&checks),
+            "client           api key set     pass\n\
+             discord.default  read channel 2  FAIL Missing Access\n"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::cache::{self, Cache};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ExportRow<'a> {
+    pub code: &'a str,
+    pub source: &'a str,
+    pub submitted_at: u64,
+    pub expires_at: u64,
+    pub api_id: Option<i32>,
+}
+
+/// Writes the submission history in the cache to stdout.
+pub fn run(format: Format) {
+    cache::setup();
+    let cache = cache::read();
+
+    let out = std::io::stdout();
+    let result = match format {
+        Format::Csv => write_csv(&cache, out.lock()),
+        Format::Json => write_json(&cache, out.lock()),
+    };
+
+    if let Err(e) = result {
+        error!("Error exporting cache: {}", e);
+    }
+}
+
+fn rows(cache: &Cache) -> Vec<ExportRow<'_>> {
+    let mut rows: Vec<ExportRow> = cache
+        .items
+        .iter()
+        .filter(|(_, entry)| entry.is_stored())
+        .map(|(code, entry)| ExportRow {
+            code,
+            source: &entry.source,
+            submitted_at: entry.submitted_at,
+            expires_at: entry.expires_at,
+            api_id: entry.api_id,
+        })
+        .collect();
+    rows.sort_by(|a, b| a.submitted_at.cmp(&b.submitted_at).then(a.code.cmp(b.code)));
+
+    rows
+}
+
+fn write_csv(cache: &Cache, out: impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    for row in rows(cache) {
+        writer.serialize(row)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_json(cache: &Cache, mut out: impl Write) -> Result<(), Box<dyn std::error::Error>> {
+    serde_json::to_writer_pretty(&mut out, &rows(cache))?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cache() -> Cache {
+        cache::init();
+
+        let mut cache = Cache::default();
+        cache.insert("CODE-AAAA-BBBB".to_string(), "discord", 1706227200, Some(5));
+
+        cache
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut out = vec![];
+        write_csv(&cache(), &mut out).unwrap();
+
+        let csv = String::from_utf8(out).unwrap();
+        let mut lines = csv.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some("code,source,submitted_at,expires_at,api_id")
+        );
+        assert!(lines.next().unwrap().starts_with("CODE-AAAA-BBBB,discord,"));
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut out = vec![];
+        write_json(&cache(), &mut out).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        assert_eq!(json[0]["code"], "CODE-AAAA-BBBB");
+        assert_eq!(json[0]["api_id"], 5);
+    }
+}
@@ -1,85 +1,578 @@
-use crate::config::DiscordConfig;
-use crate::parse::{next_week, validate_code, TimeParser};
+use crate::audit::fnv1a;
+use crate::config::{AckEmoji, AckMode, Config, CrowdConfig, DiscordConfig, MessageFormat};
+use crate::crawl::Response;
+use crate::creator::{CreatorAttribution, Derivation};
+use crate::handler::{Failure, Handled, Listed, OFFICIAL_CREATOR_NAME, OFFICIAL_CREATOR_URL};
+use crate::parse::{
+    find_codes, find_creator, find_platform, normalize_code, validate_code, week_after, TimeParser,
+};
+use crate::progress;
+use crate::reward::Rewards;
+use crate::state;
 use licc::write::{InsertCodeRequest, SourceLookup};
-use serenity::all::{ChannelId, GatewayIntents, MessageId, ReactionType};
+use serenity::all::{
+    ApplicationFlags, ChannelId, CreateAllowedMentions, CreateMessage, MessageFlags, MessageId,
+    MessageReaction, MessageReference, Permissions, ReactionType, UserId,
+};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum DiscordError {
     MissingConfig,
+    /// Every message arrived without content, as the bot is not allowed to read it
+    MissingMessageContentIntent,
+    /// The bot cannot read a configured channel, and what to do about it
+    Preflight(String),
     Serenity(serenity::Error),
 }
 
-pub async fn handle(cfg: &DiscordConfig) -> Result<Vec<InsertCodeRequest>, DiscordError> {
-    if !cfg.enabled || cfg.bot_token.is_empty() || cfg.channel_id == 0 {
+impl std::fmt::Display for DiscordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscordError::MissingConfig => write!(f, "bot_token and channel_id are required"),
+            DiscordError::MissingMessageContentIntent => write!(
+                f,
+                "all messages are empty, enable the Message Content Intent under Bot > Privileged Gateway Intents in the Discord Developer Portal"
+            ),
+            DiscordError::Preflight(reason) => write!(f, "{}", reason),
+            DiscordError::Serenity(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub async fn handle(
+    config: &Config,
+    source: &str,
+    cfg: &DiscordConfig,
+) -> Result<Handled, DiscordError> {
+    let channels = cfg.channels();
+    if !cfg.enabled || cfg.bot_token.is_empty() || channels.is_empty() {
         return Err(DiscordError::MissingConfig);
     }
 
-    let channel_id = ChannelId::new(cfg.channel_id);
-    let client: serenity::Client = client(cfg).await;
+    // One client for every channel, however many guilds they are in.
+    let http = http(config, cfg);
 
-    let auth = client
-        .http
+    let auth = http
         .get_current_user()
         .await
         .map_err(DiscordError::Serenity)?;
 
     debug!("Logged in as: {}", auth.name);
 
-    let messages = client
-        .http
-        .get_messages(channel_id, None, Some(25))
-        .await
-        .map_err(DiscordError::Serenity)?;
+    let mut handled = Handled::default();
+    let mut acks: Vec<(ChannelId, MessageId)> = vec![];
+    let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+    let rewards = Rewards::new(&config.rewards);
 
-    let mut codes: Vec<InsertCodeRequest> = vec![];
-    let ack = cfg.acknowledge;
-    let mut acks: Vec<MessageId> = vec![];
-    let timeparser = TimeParser::new();
-
-    for message in messages {
-        if message.reactions.iter().any(|r| r.me) {
-            trace!("Skipping message with existing reaction from self");
-            continue;
-        }
-
-        let guild_id = message.guild_id.map(|g| g.get()).unwrap_or(cfg.guild_id);
-        let channel_id = message.channel_id.get();
-        let (code, expires_at, creator_name, creator_url) = match parse(
-            message.content.clone(),
-            message.timestamp.timestamp() as u64,
-            &timeparser,
-        ) {
-            Ok(parsed) => parsed,
-            Err(err) => {
-                error!("Error parsing message {}: {}", message.id, err);
-                error!("Message: {}", message.content);
-                continue;
+    for target in &channels {
+        let channel_id = ChannelId::new(target.channel_id);
+
+        pause(cfg).await;
+        let mut messages = match http.get_messages(channel_id, None, Some(25)).await {
+            Ok(messages) => messages,
+            // Find out why, e.g. a missing permission, rather than surfacing a bare 403.
+            Err(e) => {
+                preflight(&http, channel_id, auth.id).await?;
+                return Err(DiscordError::Serenity(e));
             }
         };
 
-        codes.push(InsertCodeRequest {
+        let oldest_id = messages.iter().map(|m| m.id.get()).min();
+
+        if cfg.scan_pins {
+            pause(cfg).await;
+            let pins = http
+                .get_pins(channel_id)
+                .await
+                .map_err(DiscordError::Serenity)?;
+            let recent: HashSet<MessageId> = messages.iter().map(|m| m.id).collect();
+
+            messages.extend(pins.into_iter().filter(|pin| !recent.contains(&pin.id)));
+        }
+
+        if all_empty(messages.iter().map(|m| m.content.as_str()))
+            && !content_intent_granted(&http).await
+        {
+            return Err(DiscordError::MissingMessageContentIntent);
+        }
+
+        handled.fetched += messages.len();
+        progress::fetched(source, messages.len());
+        if let Some(oldest) = oldest_id {
+            handled.listed.insert(
+                channel_id.get(),
+                Listed {
+                    oldest,
+                    ids: messages.iter().map(|m| m.id.get()).collect(),
+                },
+            );
+        }
+        let ack = target.acknowledge.unwrap_or(cfg.acknowledge);
+        let fuzzy_codes = target.fuzzy_codes.unwrap_or(config.fuzzy_codes);
+        let format = target.format.unwrap_or(cfg.format);
+        let now = time::OffsetDateTime::now_utc().unix_timestamp();
+        let oldest = target
+            .max_message_age_hours
+            .or(cfg.max_message_age_hours)
+            .map(|hours| age_cutoff(now, hours));
+
+        for message in messages {
+            if message.reactions.iter().any(|r| r.me) {
+                trace!("Skipping message with existing reaction from self");
+                continue;
+            }
+
+            // Pins are usually old, that is why they are pinned.
+            if !message.pinned
+                && oldest.is_some_and(|oldest| message.timestamp.timestamp() < oldest)
+            {
+                trace!(
+                    "Skipping message {} older than max_message_age_hours",
+                    message.id
+                );
+                continue;
+            }
+
+            let changed = message.edited_timestamp.unwrap_or(message.timestamp);
+            if cfg.min_message_age_secs > 0
+                && changed.timestamp() + cfg.min_message_age_secs as i64 > now
+            {
+                debug!(
+                    "Holding back message {} in {}, younger than min_message_age_secs",
+                    message.id, source
+                );
+                continue;
+            }
+
+            match crowd_verdict(&cfg.crowd, &reaction_counts(&message.reactions)) {
+                Verdict::Accept => {}
+                Verdict::Dead(count) => {
+                    info!(
+                        "Skipping message {} in {}, {} users reacted with {}",
+                        message.id, source, count, cfg.crowd.dead_emoji
+                    );
+                    continue;
+                }
+                Verdict::Unconfirmed(count) => {
+                    // Not acknowledged, so it is looked at again next run.
+                    debug!(
+                        "Holding back message {} in {}, confirmed by {} of {} users",
+                        message.id, source, count, cfg.crowd.min_confirmations
+                    );
+                    continue;
+                }
+            }
+
+            let guild_id = message.guild_id.map(|g| g.get()).unwrap_or(target.guild_id);
+            let channel_id = message.channel_id.get();
+
+            // Crossposts can lose embeds and context, the original is read instead if the bot can see it.
+            let origin = crosspost_origin(message.flags, message.message_reference.as_ref());
+            let original = match origin {
+                Some((_, origin_channel, origin_message)) => {
+                    pause(cfg).await;
+                    http.get_message(
+                        ChannelId::new(origin_channel),
+                        MessageId::new(origin_message),
+                    )
+                    .await
+                    .inspect_err(|e| {
+                        debug!(
+                            "Cannot read the original of crosspost {}, using the copy: {}",
+                            message.id, e
+                        )
+                    })
+                    .ok()
+                    .filter(|original| !original.content.trim().is_empty())
+                }
+                None => None,
+            };
+            let (submitter_guild, submitter_channel, submitter_message) = match origin {
+                Some(origin) => origin,
+                None => (guild_id, channel_id, message.id.get()),
+            };
+
+            let content = match (&original, &message.referenced_message) {
+                (Some(original), _) => original.content.clone(),
+                (None, Some(referenced)) => merge_referenced(&message.content, &referenced.content),
+                (None, None) => message.content.clone(),
+            };
+            let content = match fuzzy_codes {
+                true => normalize_first_line(&content),
+                false => content,
+            };
+            let message_ts = message.timestamp.timestamp() as u64;
+            let submitter = SourceLookup {
+                name: message.author.global_name.unwrap_or(message.author.name),
+                url: submitter_url(
+                    cfg.submitter_url
+                        .as_deref()
+                        .unwrap_or(DEFAULT_SUBMITTER_URL),
+                    submitter_guild,
+                    submitter_channel,
+                    submitter_message,
+                ),
+            };
+
+            let before = handled.codes.len();
+            if let Err(err) = parse_message(
+                &content,
+                message_ts,
+                format,
+                &timeparser,
+                &rewards,
+                &submitter,
+                &mut handled,
+            )
+            .await
+            {
+                let location = permalink(guild_id, channel_id, message.id.get());
+                // Logged once per failure by crawl::run, messages stay broken until edited.
+                debug!(
+                    "Error parsing message in {} ({}): {}",
+                    source, location, err
+                );
+                handled.failures.push(Failure {
+                    location,
+                    reason: err.to_string(),
+                    content,
+                });
+                continue;
+            }
+            for request in &handled.codes[before..] {
+                handled
+                    .origins
+                    .insert(request.code.clone(), (channel_id, message.id.get()));
+            }
+
+            if ack && cfg.ack_mode == AckMode::Reaction {
+                acks.push((message.channel_id, message.id));
+            } else if ack {
+                handled
+                    .replies
+                    .push((guild_id, channel_id, message.id.get()));
+            }
+        }
+
+        if cfg.scan_topic {
+            pause(cfg).await;
+            let topic = http
+                .get_channel(channel_id)
+                .await
+                .map_err(DiscordError::Serenity)?
+                .guild()
+                .and_then(|channel| channel.topic);
+
+            if let Some(topic) = topic {
+                handled.fetched += 1;
+                progress::fetched(source, 1);
+                let channel_url = format!(
+                    "https://discord.com/channels/{}/{}",
+                    target.guild_id, target.channel_id
+                );
+                // Topics have no timestamp, so expiries count from when this one was first seen.
+                let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+                let mut state = state::read();
+                let seen = state.topic_seen(target.channel_id, &topic, now);
+                state::write(&state);
+
+                match parse_topic(&topic, &channel_url, &timeparser, seen).await {
+                    Ok(None) => {}
+                    Ok(Some((codes, creator))) => {
+                        for code in &codes {
+                            let metadata = handled.metadata.entry(code.code.clone()).or_default();
+                            metadata.attribution = Some(creator.clone());
+                            metadata.message = Some(topic.clone());
+                        }
+                        handled.guessed.extend(
+                            codes
+                                .iter()
+                                .filter(|code| code.expires_at == week_after(seen))
+                                .map(|code| code.code.clone()),
+                        );
+                        handled.codes.extend(codes);
+                    }
+                    Err(err) => {
+                        debug!("Error parsing topic of {}: {}", channel_url, err);
+                        handled.failures.push(Failure {
+                            location: format!("{} (topic)", channel_url),
+                            reason: err.to_string(),
+                            content: topic,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Reacting is a write visible to everyone in the channel, so dry run must not do it.
+    if !config.dry_run.is_live() {
+        handled.would_acknowledge = acks.len();
+        return Ok(handled);
+    }
+
+    for (channel_id, message_id) in acks {
+        pause(cfg).await;
+        acknowledge(http.clone(), channel_id, message_id).await;
+    }
+
+    Ok(handled)
+}
+
+/// Scans a channel topic for codes, attributed to the creator it links to as topics follow no format.
+///
+/// `seen` is when the topic was first seen, what its expiry is relative to.
+async fn parse_topic(
+    topic: &str,
+    channel_url: &str,
+    timeparser: &TimeParser,
+    seen: u64,
+) -> Result<Option<(Vec<InsertCodeRequest>, CreatorAttribution)>, &'static str> {
+    let codes = find_codes(topic);
+    if codes.is_empty() {
+        return Ok(None);
+    }
+
+    let (_, url) = find_creator(topic).ok_or("Topic has codes but no link to a creator")?;
+    let creator = CreatorAttribution::from_message(&url, None);
+
+    let mut rest = topic.to_string();
+    for code in &codes {
+        rest = rest.replace(code.as_str(), "");
+    }
+    let expires_at = timeparser
+        .parse_at(rest, true, seen)
+        .await
+        .unwrap_or_else(|| week_after(seen));
+
+    let requests = codes
+        .into_iter()
+        .map(|code| InsertCodeRequest {
             code,
             expires_at,
             creator: SourceLookup {
-                name: creator_name,
-                url: creator_url,
+                name: creator.name.clone(),
+                url: creator.url.clone(),
             },
             submitter: Some(SourceLookup {
-                name: message.author.global_name.unwrap_or(message.author.name),
-                url: format!("https://discord.com/channels/{guild_id}/{channel_id}"),
+                name: "Channel topic".to_string(),
+                url: channel_url.to_string(),
             }),
-        });
-        if ack {
-            acks.push(message.id);
+        })
+        .collect();
+
+    Ok(Some((requests, creator)))
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    Accept,
+    /// Enough users reacted with `crowd.dead_emoji`
+    Dead(u64),
+    /// Fewer than `crowd.min_confirmations` users reacted with `crowd.confirm_emoji`
+    Unconfirmed(u64),
+}
+
+/// Reactions by emoji (unicode, or the name of a custom emoji), not counting our own.
+fn reaction_counts(reactions: &[MessageReaction]) -> Vec<(String, u64)> {
+    reactions
+        .iter()
+        .map(|r| {
+            let emoji = match &r.reaction_type {
+                ReactionType::Unicode(emoji) => emoji.clone(),
+                ReactionType::Custom { name, .. } => name.clone().unwrap_or_default(),
+                _ => String::new(),
+            };
+
+            (emoji, r.count.saturating_sub(r.me as u64))
+        })
+        .collect()
+}
+
+/// Whether the community considers a code live, going by the reactions on its message.
+fn crowd_verdict(crowd: &CrowdConfig, reactions: &[(String, u64)]) -> Verdict {
+    let count = |emoji: &str| {
+        reactions
+            .iter()
+            .filter(|(e, _)| e == emoji)
+            .map(|(_, count)| count)
+            .sum::<u64>()
+    };
+
+    let dead = count(&crowd.dead_emoji);
+    if crowd.dead_at > 0 && dead >= crowd.dead_at {
+        return Verdict::Dead(dead);
+    }
+
+    let confirmed = count(&crowd.confirm_emoji);
+    if confirmed < crowd.min_confirmations {
+        return Verdict::Unconfirmed(confirmed);
+    }
+
+    Verdict::Accept
+}
+
+/// Unix timestamp of the oldest message looked at, `hours` before `now`; any number of hours is accepted.
+fn age_cutoff(now: i64, hours: u64) -> i64 {
+    let secs = hours.saturating_mul(60 * 60).min(i64::MAX as u64) as i64;
+
+    now.saturating_sub(secs)
+}
+
+/// Without the message content intent every message arrives with empty content.
+fn all_empty<'a>(mut contents: impl ExactSizeIterator<Item = &'a str>) -> bool {
+    contents.len() > 0 && contents.all(str::is_empty)
+}
+
+/// Whether the application may read message content, assumed not if that cannot be checked.
+async fn content_intent_granted(http: &serenity::http::Http) -> bool {
+    match http.get_current_application_info().await {
+        Ok(info) => info.flags.is_some_and(|flags| {
+            flags.intersects(
+                ApplicationFlags::GATEWAY_MESSAGE_CONTENT
+                    | ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED,
+            )
+        }),
+        Err(e) => {
+            debug!("Error fetching application info: {}", e);
+            false
+        }
+    }
+}
+
+/// Checks the bot token, intents and channel permissions `handle` relies on, for `liccrawler doctor`.
+pub async fn doctor(config: &Config, cfg: &DiscordConfig) -> Vec<(String, Result<(), String>)> {
+    if cfg.bot_token.is_empty() {
+        return vec![(
+            "bot token".to_string(),
+            Err("bot_token is empty".to_string()),
+        )];
+    }
+
+    let http = http(config, cfg);
+    let me = match http.get_current_user().await {
+        Ok(me) => me,
+        Err(e) => return vec![("bot token".to_string(), Err(e.to_string()))],
+    };
+
+    let mut checks = vec![
+        ("bot token".to_string(), Ok(())),
+        (
+            "message content intent".to_string(),
+            match content_intent_granted(&http).await {
+                true => Ok(()),
+                false => Err(DiscordError::MissingMessageContentIntent.to_string()),
+            },
+        ),
+    ];
+
+    for target in cfg.channels() {
+        let channel_id = ChannelId::new(target.channel_id);
+        let ack = target.acknowledge.unwrap_or(cfg.acknowledge);
+
+        let read = match preflight(&http, channel_id, me.id).await {
+            Ok(()) => http
+                .get_messages(channel_id, None, Some(1))
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        checks.push((format!("read channel {}", target.channel_id), read));
+
+        if ack && matches!(cfg.ack_mode, AckMode::Reaction | AckMode::Outcome) {
+            let permissions = permissions(&http, channel_id, me.id).await;
+            let react = permissions.and_then(|p| match p.add_reactions() {
+                true => Ok(()),
+                false => Err("missing the Add Reactions permission".to_string()),
+            });
+            checks.push((format!("react in channel {}", target.channel_id), react));
+        } else if ack {
+            let (check, channel_id) = match (cfg.ack_mode, cfg.ack_thread_id) {
+                (AckMode::Thread, Some(thread_id)) => (
+                    format!("post in thread {}", thread_id),
+                    ChannelId::new(thread_id),
+                ),
+                _ => (
+                    format!("reply in channel {}", target.channel_id),
+                    channel_id,
+                ),
+            };
+            let permissions = permissions(&http, channel_id, me.id).await;
+            let send = permissions.and_then(|p| match p.send_messages() {
+                true => Ok(()),
+                false => Err("missing the Send Messages permission".to_string()),
+            });
+            checks.push((check, send));
         }
     }
 
-    for message_id in acks {
-        acknowledge(client.http.clone(), channel_id, message_id).await;
+    checks
+}
+
+/// Whether the bot can see the channel and read its history, with what to change if it cannot.
+///
+/// Costs three requests, so `handle` only calls it to explain why reading a channel failed.
+async fn preflight(
+    http: &serenity::http::Http,
+    channel_id: ChannelId,
+    user: UserId,
+) -> Result<(), DiscordError> {
+    let permissions = permissions(http, channel_id, user).await.map_err(|e| {
+        DiscordError::Preflight(format!(
+            "cannot look up channel {}: {}. Check channel_id and that the bot was invited to its server",
+            channel_id, e
+        ))
+    })?;
+
+    let missing = missing_read_permissions(permissions);
+
+    match missing.is_empty() {
+        true => Ok(()),
+        false => Err(DiscordError::Preflight(format!(
+            "missing {} in channel {}, grant it to the bot's role or in the channel's permission overrides",
+            missing.join(" and "),
+            channel_id
+        ))),
     }
+}
 
-    Ok(codes)
+/// The permissions reading a channel needs that are not in `permissions`, by their name in Discord.
+fn missing_read_permissions(permissions: Permissions) -> Vec<&'static str> {
+    [
+        ("View Channel", permissions.view_channel()),
+        ("Read Message History", permissions.read_message_history()),
+    ]
+    .into_iter()
+    .filter(|(_, granted)| !granted)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// The permissions of `user` in a guild channel, with role and channel overwrites applied.
+async fn permissions(
+    http: &serenity::http::Http,
+    channel_id: ChannelId,
+    user: UserId,
+) -> Result<Permissions, String> {
+    let channel = http
+        .get_channel(channel_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .guild()
+        .ok_or_else(|| "not a guild channel".to_string())?;
+    let guild = http
+        .get_guild(channel.guild_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let member = http
+        .get_member(guild.id, user)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(guild.user_permissions_in(&channel, &member))
 }
 
 async fn acknowledge(
@@ -96,19 +589,427 @@ async fn acknowledge(
         .ok();
 }
 
-async fn client(cfg: &DiscordConfig) -> serenity::Client {
-    let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+const DEFAULT_ACK_TEMPLATE: &str = "Stored `{code}` (ID {id}), expires {expires}";
+
+/// Acknowledges messages once their codes are stored, with a reply or a note in `ack_thread_id`, see `ack_mode`.
+///
+/// `origins` tells which message each code came from, `stored` has the codes that were stored this run.
+pub async fn reply(
+    config: &Config,
+    cfg: &DiscordConfig,
+    messages: &[(u64, u64, u64)],
+    origins: &HashMap<String, (u64, u64)>,
+    stored: &HashMap<String, Response>,
+) {
+    let template = cfg.ack_template.as_deref().unwrap_or(DEFAULT_ACK_TEMPLATE);
+    let notes: Vec<(u64, u64, String)> = messages
+        .iter()
+        .filter_map(|&(guild_id, channel_id, message_id)| {
+            let mut codes: Vec<(&String, &Response)> = stored
+                .iter()
+                .filter(|(code, _)| origins.get(*code) == Some(&(channel_id, message_id)))
+                .collect();
+            if codes.is_empty() {
+                return None;
+            }
+            codes.sort_by(|a, b| a.0.cmp(b.0));
+
+            let link = permalink(guild_id, channel_id, message_id);
+            let text: Vec<String> = codes
+                .into_iter()
+                .map(|(code, response)| {
+                    ack_text(template, code, response.id, response.expires_at, &link)
+                })
+                .collect();
+
+            Some((channel_id, message_id, text.join("\n")))
+        })
+        .collect();
+
+    if notes.is_empty() {
+        return;
+    }
+
+    // Replies are as visible as reactions, so dry run must not send them either.
+    if !config.dry_run.is_live() {
+        info!(
+            "Dry run, would acknowledge {} messages with a {:?}",
+            notes.len(),
+            cfg.ack_mode
+        );
+        return;
+    }
+
+    let target = match (cfg.ack_mode, cfg.ack_thread_id) {
+        (AckMode::Thread, Some(thread_id)) => Some(ChannelId::new(thread_id)),
+        (AckMode::Thread, None) => {
+            error!("Not acknowledging messages, `ack_mode = \"thread\"` needs an `ack_thread_id`");
+            return;
+        }
+        _ => None,
+    };
+
+    let http = http(config, cfg);
 
-    serenity::Client::builder(&cfg.bot_token, intents)
+    for (channel_id, message_id, text) in notes {
+        pause(cfg).await;
+
+        let mut note = CreateMessage::new()
+            .content(text)
+            .allowed_mentions(CreateAllowedMentions::new());
+        if target.is_none() {
+            note = note.reference_message((ChannelId::new(channel_id), MessageId::new(message_id)));
+        }
+
+        target
+            .unwrap_or(ChannelId::new(channel_id))
+            .send_message(&http, note)
+            .await
+            .inspect_err(|e| error!("Error acknowledging message {}: {}", message_id, e))
+            .inspect(|_| debug!("Acknowledged message {}", message_id))
+            .ok();
+    }
+}
+
+/// What became of the codes found in a message, for `ack_mode = "outcome"`; a message with codes that fared differently
+/// shows the last one that applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    /// Stored in an earlier run
+    Duplicate,
+    Submitted,
+    /// Parsed, but not taken by an output
+    Rejected,
+}
+
+impl AckEmoji {
+    fn of(&self, outcome: Outcome) -> &str {
+        match outcome {
+            Outcome::Duplicate => &self.duplicate,
+            Outcome::Submitted => &self.submitted,
+            Outcome::Rejected => &self.rejected,
+        }
+    }
+}
+
+/// The outcome shown on a message, if any of the codes with an outcome were found in it.
+fn outcome_of(
+    channel_id: u64,
+    message_id: u64,
+    origins: &HashMap<String, (u64, u64)>,
+    outcomes: &HashMap<String, Outcome>,
+) -> Option<Outcome> {
+    outcomes
+        .iter()
+        .filter(|(code, _)| origins.get(*code) == Some(&(channel_id, message_id)))
+        .map(|(_, outcome)| *outcome)
+        .max()
+}
+
+/// Acknowledges messages once their codes are submitted, with the reaction in `ack_emoji` for how that went.
+pub async fn react(
+    config: &Config,
+    cfg: &DiscordConfig,
+    messages: &[(u64, u64, u64)],
+    origins: &HashMap<String, (u64, u64)>,
+    outcomes: &HashMap<String, Outcome>,
+) {
+    let reactions: Vec<(u64, u64, Outcome)> = messages
+        .iter()
+        .filter_map(|&(_, channel_id, message_id)| {
+            outcome_of(channel_id, message_id, origins, outcomes)
+                .map(|outcome| (channel_id, message_id, outcome))
+        })
+        .collect();
+
+    if reactions.is_empty() {
+        return;
+    }
+
+    if !config.dry_run.is_live() {
+        info!(
+            "Dry run, would react to {} messages with their outcome",
+            reactions.len()
+        );
+        return;
+    }
+
+    let http = http(config, cfg);
+
+    for (channel_id, message_id, outcome) in reactions {
+        let emoji = cfg.ack_emoji.of(outcome);
+        let reaction = match ReactionType::try_from(emoji) {
+            Ok(reaction) => reaction,
+            Err(e) => {
+                error!(
+                    "Not acknowledging message {}, '{}' is not an emoji: {}",
+                    message_id, emoji, e
+                );
+                continue;
+            }
+        };
+
+        pause(cfg).await;
+        http.create_reaction(
+            ChannelId::new(channel_id),
+            MessageId::new(message_id),
+            &reaction,
+        )
         .await
-        .expect("Error creating client")
+        .inspect_err(|e| error!("Error acknowledging message {}: {}", message_id, e))
+        .inspect(|_| debug!("Acknowledged message {} as {:?}", message_id, outcome))
+        .ok();
+    }
+}
+
+/// One line of a reply or note, see `ack_template`; the expiry is rendered by Discord in the reader's timezone.
+fn ack_text(template: &str, code: &str, id: Option<i32>, expires_at: u64, message: &str) -> String {
+    template
+        .replace("{code}", code)
+        .replace(
+            "{id}",
+            &id.map(|id| id.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+        )
+        .replace("{expires}", &format!("<t:{}:f>", expires_at))
+        .replace("{message}", message)
+}
+
+/// The HTTP client of the bot, shared with every other source using the same token, see `http::discord`.
+///
+/// Sources only poll over HTTP and never connect to the gateway, so they need no intents. Whether message content is
+/// readable is decided by the privileged intent in the Developer Portal, see `content_intent_granted`.
+fn http(config: &Config, cfg: &DiscordConfig) -> Arc<serenity::http::Http> {
+    crate::http::discord(
+        &config.http,
+        &cfg.bot_token,
+        cfg.api_base.as_deref(),
+        cfg.proxy.as_deref(),
+    )
 }
 
-fn parse(
+/// Waits `request_delay_ms` plus up to `request_jitter_ms` before a Discord API call.
+async fn pause(cfg: &DiscordConfig) {
+    let delay = cfg.request_delay_ms + jitter(cfg.request_jitter_ms);
+
+    if delay > 0 {
+        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+    }
+}
+
+/// A pseudo-random number of milliseconds up to `max`, unpredictable enough to break up a rhythm.
+fn jitter(max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    fnv1a(&nanos.to_le_bytes()) % (max + 1)
+}
+
+/// The guild, channel and message a crosspost from an announcement channel was originally posted as.
+fn crosspost_origin(
+    flags: Option<MessageFlags>,
+    reference: Option<&MessageReference>,
+) -> Option<(u64, u64, u64)> {
+    if !flags.is_some_and(|flags| flags.contains(MessageFlags::IS_CROSSPOST)) {
+        return None;
+    }
+
+    let reference = reference?;
+
+    Some((
+        reference.guild_id?.get(),
+        reference.channel_id.get(),
+        reference.message_id?.get(),
+    ))
+}
+
+fn permalink(guild_id: u64, channel_id: u64, message_id: u64) -> String {
+    format!("https://discord.com/channels/{guild_id}/{channel_id}/{message_id}")
+}
+
+const DEFAULT_SUBMITTER_URL: &str = "https://discord.com/channels/{guild}/{channel}/{message}";
+
+/// The submitter url of a message, see `submitter_url`.
+fn submitter_url(template: &str, guild_id: u64, channel_id: u64, message_id: u64) -> String {
+    template
+        .replace("{guild}", &guild_id.to_string())
+        .replace("{channel}", &channel_id.to_string())
+        .replace("{message}", &message_id.to_string())
+}
+
+/// Codes are sometimes relayed as a reply to an announcement, with the expiry only in the parent.
+/// If the reply lacks an expiry line, borrow it from the referenced message.
+fn merge_referenced(content: &str, referenced: &str) -> String {
+    let mut lines: Vec<&str> = content.split('\n').collect();
+
+    if lines.len() >= 5 || referenced.trim().is_empty() {
+        return content.to_string();
+    }
+
+    let expiry = referenced
+        .split('\n')
+        .rfind(|line| line.to_lowercase().contains("expire"))
+        .or_else(|| referenced.split('\n').next_back())
+        .unwrap_or_default();
+
+    lines.resize(4, "");
+    lines.push(expiry);
+
+    lines.join("\n")
+}
+
+/// Parses the content of one message into `handled`, attributed to `submitter`.
+///
+/// Public for `liccrawler test-parse`, which runs a message through it without reading Discord.
+pub async fn parse_message(
+    content: &str,
+    message_ts: u64,
+    format: MessageFormat,
+    timeparser: &TimeParser,
+    rewards: &Rewards,
+    submitter: &SourceLookup,
+    handled: &mut Handled,
+) -> Result<(), &'static str> {
+    let parsed = match format {
+        MessageFormat::Relay => parse_attributed(content.to_string(), message_ts, timeparser)
+            .await
+            .map(|parsed| vec![parsed]),
+        MessageFormat::Official => {
+            parse_official(content, message_ts, timeparser)
+                .await
+                .map(|parsed| {
+                    parsed
+                        .into_iter()
+                        .map(|(code, expires_at, name, url)| {
+                            (
+                                code,
+                                expires_at,
+                                CreatorAttribution::new(name, url, Derivation::Official),
+                            )
+                        })
+                        .collect()
+                })
+        }
+    }?;
+
+    for (code, expires_at, creator) in parsed {
+        if expires_at == week_after(message_ts) {
+            handled.guessed.insert(code.clone());
+        }
+        if format == MessageFormat::Relay {
+            if let Some(reward) = reward_line(content).and_then(|line| rewards.describe(line)) {
+                handled.metadata.entry(code.clone()).or_default().reward = Some(reward);
+            }
+        }
+        if let Some(platform) = find_platform(content) {
+            handled.metadata.entry(code.clone()).or_default().platform = Some(platform);
+        }
+
+        handled.codes.push(InsertCodeRequest {
+            code: code.clone(),
+            expires_at,
+            creator: SourceLookup {
+                name: creator.name.clone(),
+                url: creator.url.clone(),
+            },
+            submitter: Some(SourceLookup {
+                name: submitter.name.clone(),
+                url: submitter.url.clone(),
+            }),
+        });
+        let metadata = handled.metadata.entry(code).or_default();
+        metadata.attribution = Some(creator);
+        metadata.message = Some(content.to_string());
+        metadata.posted_at = Some(message_ts);
+    }
+
+    Ok(())
+}
+
+pub fn normalize_first_line(content: &str) -> String {
+    match content.split_once('\n') {
+        Some((code, rest)) => format!("{}\n{}", normalize_code(code), rest),
+        None => normalize_code(content),
+    }
+}
+
+/// Parses an announcement in the official Idle Champions server, a paragraph with the code(s) in bold:
+/// "... use code **ABCD-EFGH-IJKL**, redeemable until January 26 at noon Pacific, on Steam, Epic and consoles."
+///
+/// Mentioned platforms are ignored, the codes are attributed to the game.
+pub async fn parse_official(
+    message: &str,
+    message_ts: u64,
+    timeparser: &TimeParser,
+) -> Result<Vec<(String, u64, String, String)>, &'static str> {
+    let text = message.replace("**", " ");
+    let codes = find_codes(&text);
+
+    if codes.is_empty() {
+        return Err("No code in announcement");
+    }
+
+    let mut rest = text.clone();
+    for code in &codes {
+        rest = rest.replace(code.as_str(), "");
+    }
+    // the sentence with the expiry, so other dates (e.g. of the event) are not mistaken for it
+    let expiry = rest
+        .split(['.', '\n'])
+        .find(|sentence| {
+            let sentence = sentence.to_lowercase();
+            sentence.contains("until") || sentence.contains("expire")
+        })
+        .unwrap_or(&rest);
+
+    let expires_at = timeparser
+        .parse_at(expiry.to_string(), true, message_ts)
+        .await
+        .unwrap_or_else(|| week_after(message_ts));
+
+    Ok(codes
+        .into_iter()
+        .map(|code| {
+            (
+                code,
+                expires_at,
+                OFFICIAL_CREATOR_NAME.to_string(),
+                OFFICIAL_CREATOR_URL.to_string(),
+            )
+        })
+        .collect())
+}
+
+/// The reward line of a code message, e.g. `1x :electrumchest:`, see [`parse`].
+pub fn reward_line(message: &str) -> Option<&str> {
+    message.split('\n').nth(3)
+}
+
+/// Parses a code message: code, creator name, creator url, reward and expiry, one per line.
+///
+/// Public for the fixture tests in `tests/parser_fixtures.rs`.
+pub async fn parse(
     message: String,
     message_ts: u64,
     timeparser: &TimeParser,
 ) -> Result<(String, u64, String, String), &'static str> {
+    parse_attributed(message, message_ts, timeparser)
+        .await
+        .map(|(code, expires_at, creator)| (code, expires_at, creator.name, creator.url))
+}
+
+/// [`parse`], with how the creator was worked out.
+async fn parse_attributed(
+    message: String,
+    message_ts: u64,
+    timeparser: &TimeParser,
+) -> Result<(String, u64, CreatorAttribution), &'static str> {
     let mut parts = message.split('\n');
 
     if parts.clone().count() < 3 {
@@ -128,32 +1029,19 @@ fn parse(
         None => return Err("Missing creator URL"),
     };
 
-    // https://twitch.tv/foo -> foo
-    let mut creator_name = creator_url
-        .split('/')
-        .last()
-        .unwrap_or(creator_name_fallback.unwrap_or("Unknown"))
-        .to_lowercase();
-    // might be a youtube link
-    if creator_name.contains('?') {
-        debug!(
-            "Creator name looks fishy, using fallback: {}",
-            creator_name_fallback.unwrap_or("Unknown")
-        );
-
-        creator_name = creator_name_fallback.unwrap_or("Unknown").to_string();
-    }
+    let creator = CreatorAttribution::from_message(creator_url, creator_name_fallback);
 
     parts.next();
 
     let expires_at = match parts.next() {
-        None => next_week(),
+        None => week_after(message_ts),
         Some(txt) => timeparser
-            .parse(txt.to_string(), true)
-            .unwrap_or(message_ts + (60 * 24 * 7)),
+            .parse_at(txt.to_string(), true, message_ts)
+            .await
+            .unwrap_or_else(|| week_after(message_ts)),
     };
 
-    Ok((code, expires_at, creator_name, creator_url.to_string()))
+    Ok((code, expires_at, creator))
 }
 
 #[cfg(test)]
@@ -172,13 +1060,15 @@ mod test {
     }
     const DEFAULT_MESSAGE_TS: u64 = 1726221600;
 
-    #[test]
-    fn test_parse_many() {
+    #[tokio::test]
+    async fn test_parse_many() {
         let tp = TimeParser::new();
 
         for input in test_inputs!() {
             let (code, expires_at, creator_name, creator_url) =
-                parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp).unwrap();
+                parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp)
+                    .await
+                    .unwrap();
             assert!(!code.is_empty(), "Input: {}", input);
             assert!(expires_at > 0, "Input: {}", input);
             assert!(!creator_name.is_empty(), "Input: {}", input);
@@ -186,29 +1076,31 @@ mod test {
         }
     }
 
-    #[test]
-    fn test_parse() {
+    #[tokio::test]
+    async fn test_parse() {
         let tp = TimeParser::new();
 
         let input =
             "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires WeDontKnow";
         let (code, expires_at, creator_name, creator_url) =
-            parse(input.to_string(), 0, &tp).unwrap();
+            parse(input.to_string(), 0, &tp).await.unwrap();
 
         assert_eq!(code, "CODE-AAAA-BBBB");
-        assert_eq!(expires_at, 10080); // next week (60 * 24 * 7) added to the message timestamp (0 seconds)
+        assert_eq!(expires_at, 604800); // a week after the message timestamp (0 seconds)
         assert_eq!(creator_name, "foo");
         assert_eq!(creator_url, "https://www.twitch.tv/foo");
     }
 
-    #[test]
-    fn test_parse_youtube() {
+    #[tokio::test]
+    async fn test_parse_youtube() {
         let tp = TimeParser::new();
 
         let input =
             "EARD-EEZH-ERKS-AAAA\nGina Darling - Idle Insights\nhttps://youtu.be/sNFoGtn-Qfw?si=j8PF5-tgMw6liltq\n1x :electrumchest:\nExpires Jan 26th";
         let (code, expires_at, creator_name, creator_url) =
-            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp).unwrap();
+            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp)
+                .await
+                .unwrap();
 
         assert_eq!(code, "EARD-EEZH-ERKS-AAAA");
         assert_eq!(expires_at, 1706227200);
@@ -219,26 +1111,311 @@ mod test {
         );
     }
 
-    #[test]
-    fn test_parse_relative_time() {
+    #[tokio::test]
+    async fn test_parse_relative_time() {
         let tp = TimeParser::new();
 
         let input =
             "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week";
         let (_code, expires_at, _creator_name, _creator_url) =
-            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp).unwrap();
+            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp)
+                .await
+                .unwrap();
+
+        assert_eq!(expires_at, week_after(DEFAULT_MESSAGE_TS));
+    }
+
+    #[tokio::test]
+    async fn test_merge_referenced() {
+        let reply = "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo";
+        let parent = "New code from foo!\nExpires Jan 26th\nEnjoy";
+
+        assert_eq!(
+            merge_referenced(reply, parent),
+            "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo\n\nExpires Jan 26th"
+        );
+
+        let complete =
+            "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week";
+        assert_eq!(merge_referenced(complete, parent), complete);
 
-        assert_eq!(expires_at, next_week());
+        let tp = TimeParser::new();
+        let (_code, expires_at, _creator_name, _creator_url) =
+            parse(merge_referenced(reply, parent), DEFAULT_MESSAGE_TS, &tp)
+                .await
+                .unwrap();
+        assert_eq!(expires_at, 1706227200);
     }
 
     #[test]
-    fn test_parse_absolute_time() {
+    fn test_normalize_first_line() {
+        assert_eq!(
+            normalize_first_line(
+                "**code\u{2013}aaaa-bbbb**\nTest Input\nhttps://www.twitch.tv/foo"
+            ),
+            "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_topic() {
+        let tp = TimeParser::new();
+        let channel = "https://discord.com/channels/1/2";
+
+        let (codes, creator) = parse_topic(
+            "Current code: ABCD-EFGH-IJKL from https://www.twitch.tv/foo - expires Jan 26th",
+            channel,
+            &tp,
+            DEFAULT_MESSAGE_TS,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].code, "ABCD-EFGH-IJKL");
+        assert_eq!(codes[0].expires_at, 1706227200);
+        assert_eq!(codes[0].creator.name, "foo");
+        assert_eq!(creator.derivation, Derivation::UrlSlug);
+
+        // a link without a name in it, and no expiry: a week after the topic was first seen
+        let (codes, creator) = parse_topic(
+            "ABCD-EFGH-IJKL https://youtu.be/abc?si=def",
+            channel,
+            &tp,
+            DEFAULT_MESSAGE_TS,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(creator.derivation, Derivation::Unresolved);
+        assert_eq!(codes[0].creator.name, "Unknown");
+        assert_eq!(codes[0].expires_at, week_after(DEFAULT_MESSAGE_TS));
+
+        let (codes, _) = parse_topic(
+            "ABCD-EFGH-IJKL from https://www.twitch.tv/foo gives 10 chests",
+            channel,
+            &tp,
+            DEFAULT_MESSAGE_TS,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(codes[0].expires_at, week_after(DEFAULT_MESSAGE_TS));
+
+        assert!(parse_topic("Welcome!", channel, &tp, DEFAULT_MESSAGE_TS)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(
+            parse_topic("Code: ABCD-EFGH-IJKL", channel, &tp, DEFAULT_MESSAGE_TS)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_official() {
+        let tp = TimeParser::new();
+        // 2024-01-15
+        let message_ts = 1705276800;
+
+        let parsed = parse_official(
+            "Happy Wednesday, adventurers! The Midwinter event starts January 17. Use code **ABCD-EFGH-IJKL** \
+             for an Electrum Chest, redeemable until January 26 at noon Pacific. Works on Steam, Epic, Xbox and PlayStation.",
+            message_ts,
+            &tp,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![(
+                "ABCD-EFGH-IJKL".to_string(),
+                // noon Pacific
+                1706227200 + 20 * 60 * 60,
+                "Idle Champions".to_string(),
+                "https://www.idlechampions.com".to_string()
+            )]
+        );
+
+        // no sentence with the expiry, and "for 10" in the text is no date
+        let parsed = parse_official(
+            "Use code **ABCD-EFGH-IJKL** for 10 Gold Chests!",
+            message_ts,
+            &tp,
+        )
+        .await
+        .unwrap();
+        assert_eq!(parsed[0].1, week_after(message_ts));
+
+        assert!(parse_official("No codes today!", message_ts, &tp)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_crowd_verdict() {
+        let reactions = |confirmed: u64, dead: u64| {
+            vec![
+                ("✅".to_string(), confirmed),
+                ("❌".to_string(), dead),
+                ("electrumchest".to_string(), 10),
+            ]
+        };
+
+        let crowd = CrowdConfig::default();
+        assert_eq!(crowd_verdict(&crowd, &reactions(0, 10)), Verdict::Accept);
+
+        let crowd = CrowdConfig {
+            dead_at: 3,
+            min_confirmations: 2,
+            ..Default::default()
+        };
+        assert_eq!(crowd_verdict(&crowd, &reactions(5, 3)), Verdict::Dead(3));
+        assert_eq!(
+            crowd_verdict(&crowd, &reactions(1, 2)),
+            Verdict::Unconfirmed(1)
+        );
+        assert_eq!(crowd_verdict(&crowd, &reactions(2, 2)), Verdict::Accept);
+    }
+
+    #[test]
+    fn test_submitter_url() {
+        assert_eq!(
+            submitter_url(DEFAULT_SUBMITTER_URL, 1, 2, 3),
+            "https://discord.com/channels/1/2/3"
+        );
+        assert_eq!(
+            submitter_url("https://discord.com/channels/{guild}/{channel}", 1, 2, 3),
+            "https://discord.com/channels/1/2"
+        );
+    }
+
+    #[test]
+    fn test_outcome_of() {
+        let origins: HashMap<String, (u64, u64)> = [
+            ("ABCD-EFGH-IJKL".to_string(), (2, 10)),
+            ("MNOP-QRST-UVWX".to_string(), (2, 10)),
+            ("CODE-AAAA-BBBB".to_string(), (2, 11)),
+        ]
+        .into();
+        let outcomes: HashMap<String, Outcome> = [
+            ("ABCD-EFGH-IJKL".to_string(), Outcome::Submitted),
+            ("MNOP-QRST-UVWX".to_string(), Outcome::Rejected),
+            ("CODE-AAAA-BBBB".to_string(), Outcome::Duplicate),
+        ]
+        .into();
+
+        assert_eq!(
+            outcome_of(2, 10, &origins, &outcomes),
+            Some(Outcome::Rejected)
+        );
+        assert_eq!(
+            outcome_of(2, 11, &origins, &outcomes),
+            Some(Outcome::Duplicate)
+        );
+        assert_eq!(outcome_of(2, 12, &origins, &outcomes), None);
+        assert_eq!(AckEmoji::default().of(Outcome::Duplicate), "♻️");
+    }
+
+    #[test]
+    fn test_ack_text() {
+        assert_eq!(
+            ack_text(
+                DEFAULT_ACK_TEMPLATE,
+                "ABCD-EFGH-IJKL",
+                Some(42),
+                1706227200,
+                "https://discord.com/channels/1/2/3"
+            ),
+            "Stored `ABCD-EFGH-IJKL` (ID 42), expires <t:1706227200:f>"
+        );
+        assert_eq!(
+            ack_text(
+                "{code} from {message}: {id}",
+                "ABCD-EFGH-IJKL",
+                None,
+                0,
+                "link"
+            ),
+            "ABCD-EFGH-IJKL from link: unknown"
+        );
+    }
+
+    #[test]
+    fn test_age_cutoff() {
+        let now = 1706227200;
+
+        assert_eq!(age_cutoff(now, 0), now);
+        assert_eq!(age_cutoff(now, 24), now - 86400);
+        assert_eq!(age_cutoff(now, u64::MAX), now - i64::MAX);
+        assert_eq!(age_cutoff(i64::MIN, 1), i64::MIN);
+    }
+
+    #[test]
+    fn test_jitter() {
+        assert_eq!(jitter(0), 0);
+        assert!((0..100).all(|_| jitter(250) <= 250));
+    }
+
+    #[test]
+    fn test_missing_read_permissions() {
+        assert_eq!(
+            missing_read_permissions(Permissions::empty()),
+            vec!["View Channel", "Read Message History"]
+        );
+        assert_eq!(
+            missing_read_permissions(Permissions::VIEW_CHANNEL),
+            vec!["Read Message History"]
+        );
+        assert!(missing_read_permissions(
+            Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_crosspost_origin() {
+        let reference: MessageReference = serde_json::from_value(serde_json::json!({
+            "guild_id": "1",
+            "channel_id": "2",
+            "message_id": "3",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            crosspost_origin(Some(MessageFlags::IS_CROSSPOST), Some(&reference)),
+            Some((1, 2, 3))
+        );
+        assert_eq!(
+            crosspost_origin(Some(MessageFlags::empty()), Some(&reference)),
+            None
+        );
+        assert_eq!(crosspost_origin(None, Some(&reference)), None);
+        assert_eq!(
+            crosspost_origin(Some(MessageFlags::IS_CROSSPOST), None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_all_empty() {
+        assert!(all_empty(["", ""].into_iter()));
+        assert!(!all_empty(["", "CODE-AAAA-BBBB"].into_iter()));
+        assert!(!all_empty(std::iter::empty()));
+    }
+
+    #[tokio::test]
+    async fn test_parse_absolute_time() {
         let tp = TimeParser::new();
 
         let input =
             "CODE-AAAA-BBBB\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Jan 26th";
         let (_code, expires_at, _creator_name, _creator_url) =
-            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp).unwrap();
+            parse(input.to_string(), DEFAULT_MESSAGE_TS, &tp)
+                .await
+                .unwrap();
 
         assert_eq!(expires_at, 1706227200);
     }
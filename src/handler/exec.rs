@@ -0,0 +1,227 @@
+use crate::config::{Config, ExecConfig};
+use crate::creator::{CreatorAttribution, Derivation};
+use crate::handler::{Failure, Handled};
+use crate::import::MAX_EXPIRY_AHEAD;
+use crate::parse::{normalize_code, validate_code, week_after, TimeParser};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Keys of the expiry of a code, a unix timestamp (seconds or milliseconds) or a date.
+const EXPIRY_KEYS: [&str; 3] = ["expires_at", "expiry", "expires"];
+
+#[derive(Debug)]
+pub enum ExecError {
+    MissingConfig,
+    Io(std::io::Error),
+    /// The command exited unsuccessfully, with the end of its stderr
+    Exited(std::process::ExitStatus, String),
+}
+
+impl std::fmt::Display for ExecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecError::MissingConfig => write!(f, "command is required"),
+            ExecError::Io(e) => write!(f, "{}", e),
+            ExecError::Exited(status, stderr) if stderr.is_empty() => {
+                write!(f, "command exited with {}", status)
+            }
+            ExecError::Exited(status, stderr) => {
+                write!(f, "command exited with {}: {}", status, stderr)
+            }
+        }
+    }
+}
+
+/// Runs the command and returns every code it printed, diffing against earlier runs is left to the cache.
+pub async fn handle(config: &Config, cfg: &ExecConfig) -> Result<Handled, ExecError> {
+    let stdout = run(cfg).await?;
+
+    let timeparser = TimeParser::from_config(&config.parser);
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    Ok(parse(cfg, &stdout, &timeparser, now).await)
+}
+
+/// Whether the command runs successfully, for `liccrawler doctor`.
+pub async fn check(cfg: &ExecConfig) -> Result<(), String> {
+    run(cfg).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// The stdout of the command, which is killed if the run gives up on it.
+async fn run(cfg: &ExecConfig) -> Result<String, ExecError> {
+    let Some((program, args)) = cfg.command.split_first() else {
+        return Err(ExecError::MissingConfig);
+    };
+
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(ExecError::Io)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.trim().lines().last().unwrap_or_default().to_string();
+        return Err(ExecError::Exited(output.status, last));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads one JSON object per line, with a `code` and optionally an expiry and `creator_name` and `creator_url`, which
+/// default to the ones configured.
+pub(crate) async fn parse(
+    cfg: &ExecConfig,
+    stdout: &str,
+    timeparser: &TimeParser,
+    now: u64,
+) -> Handled {
+    let mut handled = Handled::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    let location = |i: usize| format!("{} line {}", cfg.command.join(" "), i + 1);
+
+    for (i, line) in stdout.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        handled.fetched += 1;
+
+        let item: Value = match serde_json::from_str(line) {
+            Ok(item) => item,
+            Err(e) => {
+                handled.failures.push(Failure {
+                    location: location(i),
+                    reason: format!("not JSON: {}", e),
+                    content: line.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let Some(code) = item.get("code").and_then(Value::as_str).map(normalize_code) else {
+            handled.failures.push(Failure {
+                location: location(i),
+                reason: "no code".to_string(),
+                content: line.to_string(),
+            });
+            continue;
+        };
+
+        if !validate_code(&code) {
+            handled.failures.push(Failure {
+                location: location(i),
+                reason: format!("'{}' is not a valid code", code),
+                content: line.to_string(),
+            });
+            continue;
+        }
+
+        if !seen.insert(code.clone()) {
+            continue;
+        }
+
+        let creator = match (
+            item.get("creator_name").and_then(Value::as_str),
+            item.get("creator_url").and_then(Value::as_str),
+        ) {
+            (Some(name), Some(url)) => {
+                CreatorAttribution::new(name.to_string(), url.to_string(), Derivation::FallbackLine)
+            }
+            _ => CreatorAttribution::new(
+                cfg.creator_name.clone(),
+                cfg.creator_url.clone(),
+                Derivation::ConfigAlias,
+            ),
+        };
+
+        let expires_at = match EXPIRY_KEYS.iter().find_map(|key| item.get(*key)) {
+            Some(expiry) => expiry_of(expiry, timeparser, now).await,
+            None => None,
+        };
+        if expires_at.is_none() {
+            handled.guessed.insert(code.clone());
+        }
+
+        handled
+            .metadata
+            .entry(code.clone())
+            .or_default()
+            .attribution = Some(creator.clone());
+        handled.codes.push(InsertCodeRequest {
+            code,
+            expires_at: expires_at.unwrap_or_else(|| week_after(now)),
+            creator: SourceLookup {
+                name: creator.name,
+                url: creator.url,
+            },
+            submitter: None,
+        });
+    }
+
+    handled
+}
+
+/// A unix timestamp in seconds or milliseconds, or a date the time parser understands.
+async fn expiry_of(expiry: &Value, timeparser: &TimeParser, now: u64) -> Option<u64> {
+    match expiry {
+        Value::Number(n) => n.as_u64().and_then(|ts| timestamp_of(ts, now)),
+        Value::String(s) => match s.trim().parse::<u64>() {
+            Ok(ts) => timestamp_of(ts, now),
+            Err(_) => timeparser.parse_at(s.clone(), false, now).await,
+        },
+        _ => None,
+    }
+}
+
+/// `ts` in seconds, unless it is more than `MAX_EXPIRY_AHEAD` out, which is ignored as it is in imports.
+fn timestamp_of(ts: u64, now: u64) -> Option<u64> {
+    let ts = if ts > 100_000_000_000 { ts / 1000 } else { ts };
+
+    (ts <= now.saturating_add(MAX_EXPIRY_AHEAD)).then_some(ts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2024-01-15
+    const NOW: u64 = 1705276800;
+
+    fn cfg() -> ExecConfig {
+        ExecConfig {
+            enabled: true,
+            command: vec!["./scrape.py".to_string()],
+            creator_name: "foo".to_string(),
+            creator_url: "https://www.twitch.tv/foo".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse() {
+        let handled = parse(
+            &cfg(),
+            r#"{"code": "ABCD-EFGH-IJKL", "expires_at": 1706227200}
+{"code": "abcd-efgh-ijkl"}
+
+{"code": "MNOP-QRST-UVWX", "creator_name": "bar", "creator_url": "https://www.youtube.com/@bar"}
+{"code": "SHORT"}
+not json"#,
+            &TimeParser::new(),
+            NOW,
+        )
+        .await;
+
+        assert_eq!(handled.fetched, 5);
+        assert_eq!(handled.codes.len(), 2);
+        assert_eq!(handled.codes[0].expires_at, 1706227200);
+        assert_eq!(handled.codes[0].creator.name, "foo");
+        assert_eq!(handled.codes[1].creator.name, "bar");
+        assert_eq!(handled.codes[1].expires_at, week_after(NOW));
+        assert!(handled.guessed.contains("MNOP-QRST-UVWX"));
+        assert_eq!(handled.failures.len(), 2);
+        assert_eq!(handled.failures[1].location, "./scrape.py line 6");
+    }
+}
@@ -0,0 +1,283 @@
+use crate::config::{Config, GameApiConfig};
+use crate::creator::{CreatorAttribution, Derivation};
+use crate::handler::{Failure, Handled, OFFICIAL_CREATOR_NAME, OFFICIAL_CREATOR_URL};
+use crate::http::{conditional, Validators};
+use crate::parse::{find_codes, normalize_code, validate_code, week_after, TimeParser};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use serde_json::Value;
+
+/// Keys of the list of codes or news posts when the response is an object rather than a list.
+const LIST_KEYS: [&str; 5] = ["codes", "news", "items", "posts", "data"];
+
+/// Keys of the text of a news post, looked through in this order for codes.
+const TEXT_KEYS: [&str; 5] = ["body", "content", "text", "description", "title"];
+
+/// Keys of the expiry of a code or news post, a unix timestamp (seconds or milliseconds) or a date.
+const EXPIRY_KEYS: [&str; 4] = ["expires_at", "expiry", "expires", "end_date"];
+
+#[derive(Debug)]
+pub enum GameApiError {
+    MissingConfig,
+    Http(reqwest::Error),
+}
+
+impl std::fmt::Display for GameApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameApiError::MissingConfig => write!(f, "url is required"),
+            GameApiError::Http(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Fetches the feed and returns every code in it, diffing against earlier runs is left to the caller.
+///
+/// With `known`, the validators of the last fetch, nothing is returned if the feed did not change since.
+pub async fn handle(
+    config: &Config,
+    cfg: &GameApiConfig,
+    known: Option<&Validators>,
+) -> Result<Handled, GameApiError> {
+    if !cfg.enabled || cfg.url.is_empty() {
+        return Err(GameApiError::MissingConfig);
+    }
+
+    let Some((contents, validators)) = fetch(config, cfg, known).await? else {
+        debug!("{} unchanged since the last poll", cfg.url);
+        return Ok(Handled {
+            validators: known.cloned(),
+            ..Default::default()
+        });
+    };
+
+    let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    Ok(Handled {
+        validators: Some(validators),
+        ..parse(cfg, &contents, &timeparser, now).await
+    })
+}
+
+/// Whether the feed can be fetched and is JSON, for `liccrawler doctor`.
+pub async fn check(config: &Config, cfg: &GameApiConfig) -> Result<(), String> {
+    if cfg.url.is_empty() {
+        return Err(GameApiError::MissingConfig.to_string());
+    }
+
+    let (contents, _) = fetch(config, cfg, None)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+
+    serde_json::from_str::<Value>(&contents)
+        .map(|_| ())
+        .map_err(|e| format!("not JSON: {}", e))
+}
+
+/// The feed and its validators, `None` if it did not change since `known`.
+async fn fetch(
+    config: &Config,
+    cfg: &GameApiConfig,
+    known: Option<&Validators>,
+) -> Result<Option<(String, Validators)>, GameApiError> {
+    let request = crate::http::source_client(&config.http, cfg.proxy.as_deref())
+        .get(&cfg.url)
+        .header(reqwest::header::ACCEPT, "application/json");
+
+    conditional(request, known)
+        .await
+        .map_err(GameApiError::Http)
+}
+
+/// Reads a list of codes (`{"code": ..., "expires_at": ...}`) or news posts with codes in their text, either as the
+/// whole response or under one of [`LIST_KEYS`].
+pub(crate) async fn parse(
+    cfg: &GameApiConfig,
+    contents: &str,
+    timeparser: &TimeParser,
+    now: u64,
+) -> Handled {
+    let mut handled = Handled::default();
+
+    let json: Value = match serde_json::from_str(contents) {
+        Ok(json) => json,
+        Err(e) => {
+            handled.failures.push(Failure {
+                location: cfg.url.clone(),
+                reason: format!("not JSON: {}", e),
+                content: String::new(),
+            });
+            return handled;
+        }
+    };
+
+    let items = match &json {
+        Value::Array(items) => items.as_slice(),
+        Value::Object(object) => LIST_KEYS
+            .iter()
+            .find_map(|key| object.get(*key).and_then(Value::as_array))
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+        _ => &[],
+    };
+
+    let creator = match (&cfg.creator_name, &cfg.creator_url) {
+        (Some(name), Some(url)) => {
+            CreatorAttribution::new(name.clone(), url.clone(), Derivation::ConfigAlias)
+        }
+        _ => CreatorAttribution::new(
+            OFFICIAL_CREATOR_NAME.to_string(),
+            OFFICIAL_CREATOR_URL.to_string(),
+            Derivation::Official,
+        ),
+    };
+
+    for (i, item) in items.iter().enumerate() {
+        handled.fetched += 1;
+
+        let text = TEXT_KEYS
+            .iter()
+            .filter_map(|key| item.get(*key).and_then(Value::as_str))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let codes = match item.get("code").and_then(Value::as_str) {
+            Some(code) => vec![normalize_code(code)],
+            None => find_codes(&text),
+        };
+
+        if codes.is_empty() {
+            continue;
+        }
+
+        let posted = match EXPIRY_KEYS.iter().find_map(|key| item.get(*key)) {
+            Some(expiry) => expiry_of(expiry, timeparser, now).await,
+            None => None,
+        };
+        let expires_at = match posted {
+            Some(expires_at) => Some(expires_at),
+            None => timeparser.parse_at(text.clone(), true, now).await,
+        };
+
+        for code in codes {
+            if !validate_code(&code) {
+                handled.failures.push(Failure {
+                    location: format!("{} item {}", cfg.url, i + 1),
+                    reason: format!("'{}' is not a valid code", code),
+                    content: item.to_string(),
+                });
+                continue;
+            }
+
+            if expires_at.is_none() {
+                handled.guessed.insert(code.clone());
+            }
+
+            handled
+                .metadata
+                .entry(code.clone())
+                .or_default()
+                .attribution = Some(creator.clone());
+            handled.codes.push(InsertCodeRequest {
+                code,
+                expires_at: expires_at.unwrap_or_else(|| week_after(now)),
+                creator: SourceLookup {
+                    name: creator.name.clone(),
+                    url: creator.url.clone(),
+                },
+                submitter: None,
+            });
+        }
+    }
+
+    handled
+}
+
+/// A unix timestamp in seconds or milliseconds, or a date the time parser understands.
+async fn expiry_of(expiry: &Value, timeparser: &TimeParser, now: u64) -> Option<u64> {
+    match expiry {
+        Value::Number(n) => n.as_u64().map(seconds),
+        Value::String(s) => match s.trim().parse::<u64>() {
+            Ok(ts) => Some(seconds(ts)),
+            Err(_) => timeparser.parse_at(s.clone(), false, now).await,
+        },
+        _ => None,
+    }
+}
+
+/// A unix timestamp in seconds or milliseconds, in seconds.
+fn seconds(ts: u64) -> u64 {
+    if ts > 100_000_000_000 {
+        ts / 1000
+    } else {
+        ts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2024-01-15
+    const NOW: u64 = 1705276800;
+
+    fn cfg() -> GameApiConfig {
+        GameApiConfig {
+            enabled: true,
+            url: "https://example.com/news.json".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_codes() {
+        let handled = parse(
+            &cfg(),
+            r#"{"codes": [
+                {"code": "ABCD-EFGH-IJKL", "expires_at": 1706227200},
+                {"code": "ABCD-EFGH-IJKL-MNOP", "expires_at": 1706227200000},
+                {"code": "ABCDEFGHIJKLMNOP"},
+                {"code": "SHORT"}
+            ]}"#,
+            &TimeParser::new(),
+            NOW,
+        )
+        .await;
+
+        assert_eq!(handled.fetched, 4);
+        assert_eq!(handled.codes.len(), 3);
+        assert_eq!(handled.codes[0].expires_at, 1706227200);
+        assert_eq!(handled.codes[1].expires_at, 1706227200);
+        assert_eq!(handled.codes[2].expires_at, week_after(NOW));
+        assert_eq!(handled.codes[0].creator.name, OFFICIAL_CREATOR_NAME);
+        assert!(handled.guessed.contains("ABCDEFGHIJKLMNOP"));
+        assert_eq!(handled.failures.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_parse_news() {
+        let handled = parse(
+            &cfg(),
+            r#"[
+                {"title": "Patch notes", "body": "Balance changes, no codes"},
+                {"title": "Free chest!", "body": "Use code SPOO-KYSP-OOKY this week", "expires_at": "1706227200"}
+            ]"#,
+            &TimeParser::new(),
+            NOW,
+        ).await;
+
+        assert_eq!(handled.fetched, 2);
+        assert_eq!(handled.codes.len(), 1);
+        assert_eq!(handled.codes[0].code, "SPOO-KYSP-OOKY");
+        assert_eq!(handled.codes[0].expires_at, 1706227200);
+        assert!(handled.guessed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parse_invalid() {
+        let handled = parse(&cfg(), "<html>", &TimeParser::new(), NOW).await;
+
+        assert!(handled.codes.is_empty());
+        assert_eq!(handled.failures.len(), 1);
+    }
+}
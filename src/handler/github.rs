@@ -0,0 +1,328 @@
+use crate::config::{Config, GithubConfig};
+use crate::creator::CreatorAttribution;
+use crate::handler::{Failure, Handled};
+use crate::http::{conditional, Validators};
+use crate::import::{self, ImportRow};
+use crate::output::Metadata;
+use crate::parse::{find_codes, find_platform, week_after, TimeParser};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use std::collections::HashMap;
+
+const API_BASE: &str = "https://api.github.com";
+
+#[derive(Debug)]
+pub enum GithubError {
+    MissingConfig,
+    Http(reqwest::Error),
+}
+
+impl std::fmt::Display for GithubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GithubError::MissingConfig => write!(f, "repo and path are required"),
+            GithubError::Http(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Fetches the watched file and returns every code in it, diffing against earlier runs is left to the caller.
+///
+/// With `known`, the validators of the last fetch, nothing is returned if the file did not change since.
+pub async fn handle(
+    config: &Config,
+    cfg: &GithubConfig,
+    known: Option<&Validators>,
+) -> Result<Handled, GithubError> {
+    if !cfg.enabled || cfg.repo.is_empty() || cfg.path.is_empty() {
+        return Err(GithubError::MissingConfig);
+    }
+
+    let fetched = fetch(
+        &crate::http::source_client(&config.http, cfg.proxy.as_deref()),
+        cfg,
+        known,
+    )
+    .await?;
+    let Some((contents, validators)) = fetched else {
+        debug!("{}/{} unchanged since the last poll", cfg.repo, cfg.path);
+        return Ok(Handled {
+            validators: known.cloned(),
+            ..Default::default()
+        });
+    };
+
+    let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    Ok(Handled {
+        validators: Some(validators),
+        ..parse(cfg, &contents, &timeparser, now).await
+    })
+}
+
+/// Whether the watched file can be fetched with the configured token, for `liccrawler doctor`.
+pub async fn check(config: &Config, cfg: &GithubConfig) -> Result<(), String> {
+    fetch(
+        &crate::http::source_client(&config.http, cfg.proxy.as_deref()),
+        cfg,
+        None,
+    )
+    .await
+    .map(|_| ())
+    .map_err(|e| match e {
+        GithubError::MissingConfig => "repo and path are required".to_string(),
+        GithubError::Http(e) => e.to_string(),
+    })
+}
+
+/// The contents of the file and its validators, `None` if it did not change since `known`.
+async fn fetch(
+    http: &reqwest::Client,
+    cfg: &GithubConfig,
+    known: Option<&Validators>,
+) -> Result<Option<(String, Validators)>, GithubError> {
+    let url = format!(
+        "{}/repos/{}/contents/{}",
+        cfg.api_base
+            .as_deref()
+            .unwrap_or(API_BASE)
+            .trim_end_matches('/'),
+        cfg.repo,
+        cfg.path.trim_start_matches('/')
+    );
+
+    let mut request = http
+        .get(url)
+        // the file itself rather than its metadata with base64 encoded contents
+        .header(reqwest::header::ACCEPT, "application/vnd.github.raw+json");
+
+    if let Some(branch) = &cfg.branch {
+        request = request.query(&[("ref", branch)]);
+    }
+
+    if !cfg.token.is_empty() {
+        request = request.bearer_auth(&cfg.token);
+    }
+
+    // 304 responses do not count against GitHub's rate limit
+    conditional(request, known).await.map_err(GithubError::Http)
+}
+
+/// JSON files are read in the `liccrawler import` format, anything else (e.g. Markdown) is scanned line by line.
+pub(crate) async fn parse(
+    cfg: &GithubConfig,
+    contents: &str,
+    timeparser: &TimeParser,
+    now: u64,
+) -> Handled {
+    let is_json = cfg.path.ends_with(".json") || cfg.path.ends_with(".jsonl");
+
+    let submitter = SourceLookup {
+        name: cfg.repo.clone(),
+        url: format!(
+            "https://github.com/{}/blob/{}/{}",
+            cfg.repo,
+            cfg.branch.as_deref().unwrap_or("HEAD"),
+            cfg.path.trim_start_matches('/')
+        ),
+    };
+
+    let (mut codes, failures, metadata) = match is_json {
+        true => {
+            let (codes, failures) = parse_json(contents, timeparser, &submitter.url).await;
+            (codes, failures, HashMap::new())
+        }
+        false => {
+            let (codes, metadata) = parse_text(cfg, contents, timeparser, now).await;
+            (codes, vec![], metadata)
+        }
+    };
+    for code in codes.iter_mut() {
+        code.submitter = Some(SourceLookup {
+            name: submitter.name.clone(),
+            url: submitter.url.clone(),
+        });
+    }
+
+    let guessed = codes
+        .iter()
+        .filter(|code| code.expires_at == week_after(now))
+        .map(|code| code.code.clone())
+        .collect();
+
+    Handled {
+        codes,
+        fetched: 1,
+        failures,
+        guessed,
+        metadata,
+        ..Default::default()
+    }
+}
+
+async fn parse_json(
+    contents: &str,
+    timeparser: &TimeParser,
+    url: &str,
+) -> (Vec<InsertCodeRequest>, Vec<Failure>) {
+    let rows: import::Rows = match serde_json::from_str::<Vec<ImportRow>>(contents) {
+        Ok(rows) => rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| (i + 1, Ok(row)))
+            .collect(),
+        Err(_) => import::parse_json_lines(contents),
+    };
+
+    let mut codes = vec![];
+    let mut failures = vec![];
+
+    for (line, row) in rows {
+        let request = match row {
+            Ok(row) => import::to_request(row, timeparser).await,
+            Err(e) => Err(e),
+        };
+
+        match request {
+            Ok(request) => codes.push(request),
+            Err(e) => {
+                debug!("Skipping row {} of {}: {}", line, url, e);
+                failures.push(Failure {
+                    location: format!("{} row {}", url, line),
+                    reason: e,
+                    content: String::new(),
+                });
+            }
+        }
+    }
+
+    (codes, failures)
+}
+
+async fn parse_text(
+    cfg: &GithubConfig,
+    contents: &str,
+    timeparser: &TimeParser,
+    now: u64,
+) -> (Vec<InsertCodeRequest>, HashMap<String, Metadata>) {
+    let mut codes = vec![];
+    let mut metadata = HashMap::new();
+
+    for line in contents.lines() {
+        let found = find_codes(line);
+        if found.is_empty() {
+            continue;
+        }
+
+        let creator = CreatorAttribution::from_text(line, &cfg.creator_name, &cfg.creator_url);
+
+        let mut rest = line.to_string();
+        for code in &found {
+            rest = rest.replace(code.as_str(), "");
+        }
+        let expires_at = timeparser
+            .parse_at(rest, true, now)
+            .await
+            .unwrap_or_else(|| week_after(now));
+
+        let platform = find_platform(line);
+        for code in &found {
+            metadata.insert(
+                code.clone(),
+                Metadata {
+                    platform: platform.clone(),
+                    attribution: Some(creator.clone()),
+                    message: Some(line.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+
+        codes.extend(found.into_iter().map(|code| InsertCodeRequest {
+            code,
+            expires_at,
+            creator: SourceLookup {
+                name: creator.name.clone(),
+                url: creator.url.clone(),
+            },
+            submitter: None,
+        }));
+    }
+
+    (codes, metadata)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2024-01-15
+    const NOW: u64 = 1705276800;
+
+    fn cfg(path: &str) -> GithubConfig {
+        GithubConfig {
+            enabled: true,
+            repo: "someone/codes".to_string(),
+            path: path.to_string(),
+            creator_name: "community".to_string(),
+            creator_url: "https://example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_markdown() {
+        let handled = parse(
+            &cfg("README.md"),
+            "# Codes\n\n- `ABCD-EFGH-IJKL` by [Foo](https://www.twitch.tv/Foo)\n- ABCD-EFGH-IJKL-MNOP\n",
+            &TimeParser::new(),
+            NOW,
+        ).await;
+
+        assert_eq!(handled.codes.len(), 2);
+        assert_eq!(handled.codes[0].code, "ABCD-EFGH-IJKL");
+        assert_eq!(handled.codes[0].creator.name, "foo");
+        assert_eq!(handled.codes[1].creator.name, "community");
+        assert_eq!(handled.codes[1].expires_at, week_after(NOW));
+        assert_eq!(
+            handled.codes[1].submitter.as_ref().map(|s| s.url.as_str()),
+            Some("https://github.com/someone/codes/blob/HEAD/README.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_text_without_expiry() {
+        let handled = parse(
+            &cfg("codes.txt"),
+            "ABCD-EFGH-IJKL gives 10 chests
+",
+            &TimeParser::new(),
+            NOW,
+        )
+        .await;
+
+        assert_eq!(handled.codes.len(), 1);
+        assert_eq!(handled.codes[0].expires_at, week_after(NOW));
+    }
+
+    #[tokio::test]
+    async fn test_parse_json() {
+        let handled = parse(
+            &cfg("codes.json"),
+            r#"[
+                {"code": "ABCD-EFGH-IJKL", "expires": 1706227200, "creator_name": "foo", "creator_url": "https://www.twitch.tv/foo"},
+                {"code": "ABCD", "expires": 1706227200, "creator_name": "foo", "creator_url": "https://www.twitch.tv/foo"}
+            ]"#,
+            &TimeParser::new(),
+            NOW,
+        ).await;
+
+        assert_eq!(handled.codes.len(), 1);
+        assert_eq!(handled.codes[0].expires_at, 1706227200);
+        assert_eq!(handled.failures.len(), 1);
+        assert_eq!(
+            handled.failures[0].location,
+            "https://github.com/someone/codes/blob/HEAD/codes.json row 2"
+        );
+    }
+}
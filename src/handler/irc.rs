@@ -0,0 +1,419 @@
+use crate::config::IrcConfig;
+use crate::daemon::Task;
+use crate::parse::{find_codes, find_creator, week_after, TimeParser};
+use crate::validate::{self, CodeValidator};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedSender;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(15);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+/// A connection that lasted this long was healthy, so the next reconnect starts from `MIN_BACKOFF` again.
+const STABLE_AFTER: Duration = Duration::from_secs(5 * 60);
+/// Pause between JOINs, servers disconnect clients that send too much at once.
+const JOIN_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum IrcError {
+    Io(std::io::Error),
+    /// The server sent ERROR, usually right before closing the connection
+    Server(String),
+}
+
+/// Idles in `cfg.channels` and hands every code posted there to the daemon loop through `tasks`, reconnecting
+/// with an increasing delay until the process stops.
+pub async fn run(
+    name: String,
+    cfg: IrcConfig,
+    timeparser: TimeParser,
+    tasks: UnboundedSender<Task>,
+) {
+    let source = format!("irc.{}", name);
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        let connected = Instant::now();
+
+        match session(&source, &cfg, &timeparser, &tasks).await {
+            Ok(()) => warn!("IRC '{}' closed the connection", name),
+            Err(e) => error!("Error in IRC '{}': {:?}", name, e),
+        }
+
+        if connected.elapsed() >= STABLE_AFTER {
+            backoff = MIN_BACKOFF;
+        }
+
+        info!("Reconnecting to IRC '{}' in {}s", name, backoff.as_secs());
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+async fn session(
+    source: &str,
+    cfg: &IrcConfig,
+    timeparser: &TimeParser,
+    tasks: &UnboundedSender<Task>,
+) -> Result<(), IrcError> {
+    let stream = connect(cfg).await.map_err(IrcError::Io)?;
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut nickname = cfg.nickname.clone();
+
+    send(&mut writer, &format!("NICK {}", nickname)).await?;
+    send(
+        &mut writer,
+        &format!("USER {} 0 * :liccrawler", cfg.nickname),
+    )
+    .await?;
+
+    while let Some(line) = lines.next_line().await.map_err(IrcError::Io)? {
+        let message = match Message::parse(&line) {
+            Some(message) => message,
+            None => continue,
+        };
+
+        match message.command {
+            "PING" => {
+                let token = message.params.last().copied().unwrap_or_default();
+                send(&mut writer, &format!("PONG :{}", token)).await?;
+            }
+            // RPL_WELCOME, registration is complete
+            "001" => {
+                info!("Connected to {} as {}", cfg.server, nickname);
+
+                for channel in &cfg.channels {
+                    send(&mut writer, &format!("JOIN {}", channel)).await?;
+                    tokio::time::sleep(JOIN_DELAY).await;
+                }
+            }
+            // ERR_NICKNAMEINUSE
+            "433" => {
+                nickname.push('_');
+                send(&mut writer, &format!("NICK {}", nickname)).await?;
+            }
+            "PRIVMSG" => {
+                if let ([target, text], Some(nick)) = (message.params.as_slice(), message.nick()) {
+                    if cfg.channels.iter().any(|c| c.eq_ignore_ascii_case(target)) {
+                        let found = requests(cfg, timeparser, nick, target, text, now()).await;
+                        submit(source, tasks, found);
+                    }
+                }
+            }
+            "ERROR" => {
+                return Err(IrcError::Server(message.params.join(" ")));
+            }
+            _ => trace!("IRC: {}", line),
+        }
+    }
+
+    Ok(())
+}
+
+/// Connects to `cfg.server`, through `cfg.proxy` when set.
+pub(crate) async fn connect(cfg: &IrcConfig) -> std::io::Result<TcpStream> {
+    let proxy = match cfg.proxy.as_deref().filter(|p| !p.is_empty()) {
+        Some(proxy) => proxy,
+        None => return TcpStream::connect(&cfg.server).await,
+    };
+
+    let invalid =
+        |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg.to_string());
+    let proxy = proxy
+        .strip_prefix("socks5://")
+        .or_else(|| proxy.strip_prefix("socks5h://"))
+        .ok_or_else(|| invalid("only socks5:// proxies are supported for IRC"))?;
+    let (host, port) = cfg
+        .server
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .ok_or_else(|| invalid("server must be host:port"))?;
+
+    let mut stream = TcpStream::connect(proxy).await?;
+
+    // version 5, offering one method: no authentication
+    stream.write_all(&[5, 1, 0]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [5, 0] {
+        return Err(std::io::Error::other("proxy requires authentication"));
+    }
+
+    stream.write_all(&socks5_connect(host, port)?).await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(std::io::Error::other(format!(
+            "proxy could not connect, reply {}",
+            reply[1]
+        )));
+    }
+
+    // skip the address the proxy bound, and its port
+    let len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        _ => return Err(std::io::Error::other("proxy sent an unknown address type")),
+    };
+    stream.read_exact(&mut vec![0u8; len + 2]).await?;
+
+    Ok(stream)
+}
+
+/// A SOCKS5 CONNECT request, leaving name resolution to the proxy.
+fn socks5_connect(host: &str, port: u16) -> std::io::Result<Vec<u8>> {
+    let len = u8::try_from(host.len()).map_err(|_| std::io::Error::other("host name too long"))?;
+
+    let mut request = vec![5, 1, 0, 3, len];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+
+    Ok(request)
+}
+
+async fn send(writer: &mut OwnedWriteHalf, line: &str) -> Result<(), IrcError> {
+    writer
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .await
+        .map_err(IrcError::Io)
+}
+
+/// Hands the valid codes among `requests` to the daemon loop, which submits them between cycles.
+fn submit(source: &str, tasks: &UnboundedSender<Task>, mut requests: Vec<InsertCodeRequest>) {
+    let validators = validate::for_source(source);
+    requests.retain(|request| match validators.check(&request.code) {
+        Ok(()) => true,
+        Err(reason) => {
+            debug!("Ignoring code from {}: {}", source, reason);
+            false
+        }
+    });
+
+    if requests.is_empty() {
+        return;
+    }
+
+    let task = Task::Submit {
+        source: source.to_string(),
+        requests,
+        reply: None,
+    };
+    if tasks.send(task).is_err() {
+        error!("Dropping codes from {}, the daemon loop stopped", source);
+    }
+}
+
+/// One line received from the server, e.g. `:nick!user@host PRIVMSG #channel :hello world`
+#[derive(Debug, PartialEq)]
+struct Message<'a> {
+    prefix: Option<&'a str>,
+    command: &'a str,
+    params: Vec<&'a str>,
+}
+
+impl<'a> Message<'a> {
+    fn parse(line: &'a str) -> Option<Message<'a>> {
+        let mut line = line.trim_end_matches(['\r', '\n']);
+
+        // IRCv3 message tags, which we never request but some servers send anyway
+        if line.starts_with('@') {
+            line = line.split_once(' ')?.1;
+        }
+
+        let (prefix, rest) = match line.strip_prefix(':') {
+            Some(line) => {
+                let (prefix, rest) = line.split_once(' ')?;
+                (Some(prefix), rest)
+            }
+            None => (None, line),
+        };
+
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+
+        let mut words = middle.split(' ').filter(|w| !w.is_empty());
+        let command = words.next()?;
+        let mut params: Vec<&str> = words.collect();
+        params.extend(trailing);
+
+        Some(Message {
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    fn nick(&self) -> Option<&'a str> {
+        self.prefix.and_then(|p| p.split('!').next())
+    }
+}
+
+async fn requests(
+    cfg: &IrcConfig,
+    timeparser: &TimeParser,
+    nick: &str,
+    channel: &str,
+    text: &str,
+    now: u64,
+) -> Vec<InsertCodeRequest> {
+    let codes = find_codes(text);
+    if codes.is_empty() {
+        return vec![];
+    }
+
+    let creator = match find_creator(text) {
+        Some((name, url)) => SourceLookup { name, url },
+        None => SourceLookup {
+            name: cfg.creator_name.clone(),
+            url: cfg.creator_url.clone(),
+        },
+    };
+
+    let mut rest = text.to_string();
+    for code in &codes {
+        rest = rest.replace(code.as_str(), "");
+    }
+    let expires_at = timeparser
+        .parse_at(rest, true, now)
+        .await
+        .unwrap_or_else(|| week_after(now));
+
+    codes
+        .into_iter()
+        .map(|code| InsertCodeRequest {
+            code,
+            expires_at,
+            creator: SourceLookup {
+                name: creator.name.clone(),
+                url: creator.url.clone(),
+            },
+            submitter: Some(SourceLookup {
+                name: nick.to_string(),
+                url: format!("irc://{}/{}", cfg.server, channel.trim_start_matches('#')),
+            }),
+        })
+        .collect()
+}
+
+fn now() -> u64 {
+    time::OffsetDateTime::now_utc().unix_timestamp() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(
+            Message::parse(":nick!user@host PRIVMSG #codes :ABCD-EFGH-IJKL is live\r\n"),
+            Some(Message {
+                prefix: Some("nick!user@host"),
+                command: "PRIVMSG",
+                params: vec!["#codes", "ABCD-EFGH-IJKL is live"],
+            })
+        );
+        assert_eq!(
+            Message::parse("PING :irc.libera.chat").map(|m| m.params),
+            Some(vec!["irc.libera.chat"])
+        );
+        assert_eq!(
+            Message::parse("@time=2024-01-15T00:00:00Z :server 001 liccrawler :Welcome")
+                .map(|m| m.command),
+            Some("001")
+        );
+        assert_eq!(
+            Message::parse(":nick!user@host PRIVMSG #codes :hi").and_then(|m| m.nick()),
+            Some("nick")
+        );
+        assert_eq!(Message::parse(""), None);
+    }
+
+    #[test]
+    fn test_socks5_connect() {
+        assert_eq!(
+            socks5_connect("irc.example", 6667).unwrap(),
+            [&[5, 1, 0, 3, 11][..], b"irc.example", &[0x1a, 0x0b]].concat()
+        );
+        assert!(socks5_connect(&"a".repeat(256), 6667).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests() {
+        let cfg = IrcConfig {
+            server: "irc.libera.chat:6667".to_string(),
+            creator_name: "relay".to_string(),
+            creator_url: "https://example.com".to_string(),
+            ..Default::default()
+        };
+        let tp = TimeParser::new();
+        // 2024-01-15
+        let now = 1705276800;
+
+        let found = requests(
+            &cfg,
+            &tp,
+            "nick",
+            "#codes",
+            "ABCD-EFGH-IJKL from the stream",
+            now,
+        )
+        .await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code, "ABCD-EFGH-IJKL");
+        assert_eq!(found[0].expires_at, week_after(now));
+        assert_eq!(found[0].creator.name, "relay");
+        assert_eq!(
+            found[0].submitter.as_ref().map(|s| s.url.as_str()),
+            Some("irc://irc.libera.chat:6667/codes")
+        );
+
+        let found = requests(
+            &cfg,
+            &tp,
+            "nick",
+            "#codes",
+            "ABCD-EFGH-IJKL https://www.twitch.tv/Foo",
+            now,
+        )
+        .await;
+        assert_eq!(found[0].creator.name, "foo");
+        assert_eq!(found[0].creator.url, "https://www.twitch.tv/Foo");
+
+        // "gives 10" is no date, the expiry follows it
+        let found = requests(
+            &cfg,
+            &tp,
+            "nick",
+            "#codes",
+            "ABCD-EFGH-IJKL gives 10 chests",
+            now,
+        )
+        .await;
+        assert_eq!(found[0].expires_at, week_after(now));
+        let found = requests(
+            &cfg,
+            &tp,
+            "nick",
+            "#codes",
+            "ABCD-EFGH-IJKL gives 10 chests, expires Jan 26th",
+            now,
+        )
+        .await;
+        assert_eq!(found[0].expires_at, 1706227200);
+
+        assert!(requests(&cfg, &tp, "nick", "#codes", "no codes today", now)
+            .await
+            .is_empty());
+    }
+}
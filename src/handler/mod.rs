@@ -1,2 +1,72 @@
 #[cfg(feature = "discord")]
 pub mod discord;
+pub mod exec;
+pub mod game_api;
+pub mod github;
+pub mod irc;
+
+use crate::output::Metadata;
+use licc::write::InsertCodeRequest;
+use std::collections::{HashMap, HashSet};
+
+/// Who codes posted by the game itself are attributed to.
+pub(crate) const OFFICIAL_CREATOR_NAME: &str = "Idle Champions";
+pub(crate) const OFFICIAL_CREATOR_URL: &str = "https://www.idlechampions.com";
+
+/// The codes found in a source during one run, and the side effects that were held back in dry run.
+#[derive(Debug, Default)]
+pub struct Handled {
+    pub codes: Vec<InsertCodeRequest>,
+    /// Messages (or other items) fetched from the source
+    pub fetched: usize,
+    /// Items that could not be parsed
+    pub failures: Vec<Failure>,
+    /// Messages that would have been acknowledged if not in dry run
+    pub would_acknowledge: usize,
+    /// Reward, platform and such per code, for sources that post them
+    pub metadata: HashMap<String, Metadata>,
+    /// Codes without a posted expiry, submitted with the fallback a week after they were found
+    pub guessed: HashSet<String>,
+    /// Channel and message ID each code was found in, to retract it when the message is deleted
+    pub origins: HashMap<String, (u64, u64)>,
+    /// The messages fetched per channel ID
+    pub listed: HashMap<u64, Listed>,
+    /// Messages to acknowledge once their codes are stored, by guild, channel and message ID, see `ack_mode`
+    pub replies: Vec<(u64, u64, u64)>,
+    /// Validators of the resource fetched, for sources that poll a single resource over HTTP
+    pub validators: Option<crate::http::Validators>,
+}
+
+/// The messages fetched from a channel, to tell which of the messages codes were found in were deleted since.
+#[derive(Debug, Default)]
+pub struct Listed {
+    /// The oldest message ID fetched, older messages were not looked at
+    pub oldest: u64,
+    pub ids: HashSet<u64>,
+}
+
+impl Listed {
+    /// Whether `message_id` should have been fetched, but was not.
+    pub fn deleted(&self, message_id: u64) -> bool {
+        message_id >= self.oldest && !self.ids.contains(&message_id)
+    }
+}
+
+/// An item a source could not parse, and where to find it.
+#[derive(Debug)]
+pub struct Failure {
+    /// e.g. a message permalink
+    pub location: String,
+    pub reason: String,
+    /// The item itself if it is text, e.g. the message content
+    pub content: String,
+}
+
+impl Failure {
+    /// Identifies this failure across runs, an edited item is a new failure.
+    pub fn key(&self) -> String {
+        let item = format!("{}\n{}\n{}", self.location, self.reason, self.content);
+
+        format!("{:016x}", crate::audit::fnv1a(item.as_bytes()))
+    }
+}
@@ -0,0 +1,232 @@
+use crate::config::HttpConfig;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+#[cfg(feature = "discord")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Clients shared by every source and output for as long as the process runs, so requests with the same settings go
+/// through one connection pool instead of handshaking anew for every handler and run.
+///
+/// Clients are kept by the settings they were built with, a reloaded config with other settings gets new ones.
+#[derive(Default)]
+struct HttpContext {
+    clients: HashMap<(HttpConfig, Option<String>), reqwest::Client>,
+    /// By bot token, API base, settings and proxy
+    #[cfg(feature = "discord")]
+    discord: HashMap<DiscordKey, Arc<serenity::http::Http>>,
+}
+
+#[cfg(feature = "discord")]
+type DiscordKey = (String, Option<String>, HttpConfig, Option<String>);
+
+static CONTEXT: Lazy<Mutex<HttpContext>> = Lazy::new(Default::default);
+
+/// The client that outbound HTTP requests go through, so `[http]` applies to every source and output.
+///
+/// Invalid settings are logged and skipped rather than aborting the run.
+pub fn client(cfg: &HttpConfig) -> reqwest::Client {
+    source_client(cfg, None)
+}
+
+/// [`client`] for a source with its own `proxy` setting, which replaces `http.proxy` when set;
+/// an empty string connects directly.
+pub fn source_client(cfg: &HttpConfig, proxy: Option<&str>) -> reqwest::Client {
+    CONTEXT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clients
+        .entry((cfg.clone(), proxy.map(str::to_string)))
+        .or_insert_with(|| build(builder(cfg, proxy)))
+        .clone()
+}
+
+/// The Discord HTTP client of a bot, shared by every source, check and reply using the same token so they also
+/// share its rate limits. `api_base` replaces the Discord API host, e.g. for tests.
+#[cfg(feature = "discord")]
+pub fn discord(
+    cfg: &HttpConfig,
+    token: &str,
+    api_base: Option<&str>,
+    proxy: Option<&str>,
+) -> Arc<serenity::http::Http> {
+    let key = (
+        token.to_string(),
+        api_base.map(str::to_string),
+        cfg.clone(),
+        proxy.map(str::to_string),
+    );
+    if let Some(http) = CONTEXT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .discord
+        .get(&key)
+    {
+        return http.clone();
+    }
+
+    let mut builder = serenity::all::HttpBuilder::new(token).client(source_client(cfg, proxy));
+    if let Some(api_base) = api_base {
+        builder = builder.proxy(api_base).ratelimiter_disabled(true);
+    }
+    let http = Arc::new(builder.build());
+
+    CONTEXT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .discord
+        .insert(key, http.clone());
+
+    http
+}
+
+/// [`client`] that sends `headers` with every request, for API clients that do not take headers per request.
+pub fn client_with_headers(
+    cfg: &HttpConfig,
+    headers: reqwest::header::HeaderMap,
+) -> reqwest::Client {
+    build(builder(cfg, None).default_headers(headers))
+}
+
+fn builder(cfg: &HttpConfig, proxy: Option<&str>) -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(&cfg.user_agent)
+        .connect_timeout(Duration::from_secs(cfg.connect_timeout_secs))
+        .timeout(Duration::from_secs(cfg.timeout_secs));
+
+    match proxy.or(cfg.proxy.as_deref()) {
+        // also ignore HTTP(S)_PROXY from the environment
+        Some("") => builder = builder.no_proxy(),
+        Some(proxy) => match reqwest::Proxy::all(proxy) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => error!("Invalid proxy '{}', not using it: {}", proxy, e),
+        },
+        None => {}
+    }
+
+    if let Some(path) = &cfg.ca_bundle {
+        match certificates(path) {
+            Ok(certificates) => {
+                for certificate in certificates {
+                    builder = builder.add_root_certificate(certificate);
+                }
+            }
+            Err(e) => error!("Error reading http.ca_bundle {}: {}", path.display(), e),
+        }
+    }
+
+    builder
+}
+
+fn build(builder: reqwest::ClientBuilder) -> reqwest::Client {
+    builder.build().unwrap_or_else(|e| {
+        error!("Error building HTTP client, using defaults: {}", e);
+        reqwest::Client::new()
+    })
+}
+
+/// `ETag` and `Last-Modified` of a fetched resource, sent back so an unchanged resource is answered with
+/// 304 Not Modified instead of its contents.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn of(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+}
+
+/// Sends `request` conditionally on `known`, returning the body and its validators, or `None` if it did not change.
+pub async fn conditional(
+    mut request: reqwest::RequestBuilder,
+    known: Option<&Validators>,
+) -> Result<Option<(String, Validators)>, reqwest::Error> {
+    if let Some(known) = known {
+        if let Some(etag) = &known.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &known.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+
+    let response = response.error_for_status()?;
+    let validators = Validators::of(response.headers());
+
+    Ok(Some((response.text().await?, validators)))
+}
+
+/// Every certificate in a PEM bundle, `Certificate::from_pem` only reads the first.
+fn certificates(path: &std::path::Path) -> Result<Vec<reqwest::Certificate>, String> {
+    let pem = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    split_pem(&pem)
+        .iter()
+        .map(|block| reqwest::Certificate::from_pem(block.as_bytes()).map_err(|e| e.to_string()))
+        .collect()
+}
+
+fn split_pem(pem: &str) -> Vec<String> {
+    const END: &str = "-----END CERTIFICATE-----";
+
+    pem.split_inclusive(END)
+        .filter(|block| block.contains(END))
+        .map(|block| block.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validators() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::ETAG, "W/\"abc\"".parse().unwrap());
+
+        assert_eq!(
+            Validators::of(&headers),
+            Validators {
+                etag: Some("W/\"abc\"".to_string()),
+                last_modified: None,
+            }
+        );
+        assert_eq!(
+            Validators::of(&reqwest::header::HeaderMap::new()),
+            Validators::default()
+        );
+    }
+
+    #[test]
+    fn test_split_pem() {
+        let pem = "# first\n-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----\n\n-----BEGIN CERTIFICATE-----\nBBB\n-----END CERTIFICATE-----\n";
+
+        assert_eq!(
+            split_pem(pem),
+            vec![
+                "# first\n-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----",
+                "-----BEGIN CERTIFICATE-----\nBBB\n-----END CERTIFICATE-----",
+            ]
+        );
+        assert!(split_pem("").is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::cache;
+use crate::config::Config;
+use crate::crawl;
+use crate::parse::{validate_code, TimeParser};
+use licc::write::{InsertCodeRequest, SourceLookup};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Expiries further out than this are taken for a mistake, e.g. milliseconds instead of seconds.
+pub(crate) const MAX_EXPIRY_AHEAD: u64 = 60 * 60 * 24 * 366;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ImportRow {
+    pub code: String,
+    pub expires: Expires,
+    pub creator_name: String,
+    pub creator_url: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Expires {
+    Timestamp(u64),
+    Text(String),
+}
+
+/// Imports codes from a CSV file (with a `code,expires,creator_name,creator_url` header) or JSON lines,
+/// submitting every valid row through the same path as crawled codes.
+pub async fn run(config: &Config, file: &Path) {
+    let rows = match read(file) {
+        Ok(rows) => rows,
+        Err(e) => {
+            error!("Error reading {}: {}", file.display(), e);
+            return;
+        }
+    };
+
+    let timeparser = TimeParser::from_config(&config.parser);
+    let mut requests: Vec<InsertCodeRequest> = vec![];
+
+    for (line, row) in rows {
+        let request = match row {
+            Ok(row) => to_request(row, &timeparser).await,
+            Err(e) => Err(e),
+        };
+
+        match request {
+            Ok(request) => requests.push(request),
+            Err(e) => error!("Skipping row {} of {}: {}", line, file.display(), e),
+        }
+    }
+
+    info!("Importing {} codes from {}", requests.len(), file.display());
+
+    cache::setup();
+    let mut cache = cache::read();
+
+    let responses = crawl::submit(config, &mut cache, BTreeMap::from([("import", requests)])).await;
+    crawl::report(config, responses);
+
+    cache.bust();
+    cache::write(cache).await;
+}
+
+pub(crate) type Rows = Vec<(usize, Result<ImportRow, String>)>;
+
+fn read(file: &Path) -> Result<Rows, String> {
+    let is_csv = file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+    if is_csv {
+        let mut reader = csv::Reader::from_path(file).map_err(|e| e.to_string())?;
+
+        // line 1 is the header
+        Ok(reader
+            .deserialize()
+            .enumerate()
+            .map(|(i, row)| (i + 2, row.map_err(|e| e.to_string())))
+            .collect())
+    } else {
+        let contents = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+
+        Ok(parse_json_lines(&contents))
+    }
+}
+
+pub(crate) fn parse_json_lines(contents: &str) -> Rows {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+        .collect()
+}
+
+pub(crate) async fn to_request(
+    row: ImportRow,
+    timeparser: &TimeParser,
+) -> Result<InsertCodeRequest, String> {
+    let code = row.code.replace(' ', "");
+
+    if !validate_code(&code) {
+        return Err(format!("Invalid code '{}'", code));
+    }
+
+    let expires_at = match row.expires {
+        Expires::Timestamp(ts) => ts,
+        Expires::Text(txt) => match txt.trim().parse::<u64>() {
+            Ok(ts) => ts,
+            Err(_) => timeparser
+                .parse(txt.clone(), false)
+                .await
+                .ok_or_else(|| format!("Unrecognised expiry '{}'", txt))?,
+        },
+    };
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+    if expires_at > now.saturating_add(MAX_EXPIRY_AHEAD) {
+        return Err(format!("Expiry {} is more than a year out", expires_at));
+    }
+
+    Ok(InsertCodeRequest {
+        code,
+        expires_at,
+        creator: SourceLookup {
+            name: row.creator_name,
+            url: row.creator_url,
+        },
+        submitter: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_lines() {
+        let rows = parse_json_lines(
+            r#"{"code": "CODE-AAAA-BBBB", "expires": 1706227200, "creator_name": "foo", "creator_url": "https://www.twitch.tv/foo"}
+
+{"code": "CODE-AAAA-BBBB-CCCC", "expires": "Jan 26th", "creator_name": "foo", "creator_url": "https://www.twitch.tv/foo"}
+{"code": "CODE-AAAA"}"#,
+        );
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].0, 3);
+        assert!(rows[0].1.is_ok());
+        assert!(rows[1].1.is_ok());
+        assert!(rows[2].1.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_to_request() {
+        let tp = TimeParser::new();
+        let row = |code: &str, expires: Expires| ImportRow {
+            code: code.to_string(),
+            expires,
+            creator_name: "foo".to_string(),
+            creator_url: "https://www.twitch.tv/foo".to_string(),
+        };
+
+        let request = to_request(
+            row("CODE-AAAA-BBBB", Expires::Text("1706227200".to_string())),
+            &tp,
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.expires_at, 1706227200);
+        assert_eq!(request.creator.name, "foo");
+        assert!(request.submitter.is_none());
+
+        assert!(
+            to_request(row("CODE-AAAA-BBBB", Expires::Text("idk".to_string())), &tp)
+                .await
+                .is_err()
+        );
+        assert!(to_request(row("CODE", Expires::Timestamp(1706227200)), &tp)
+            .await
+            .is_err());
+        assert!(
+            to_request(row("CODE-AAAA-BBBB", Expires::Timestamp(u64::MAX)), &tp)
+                .await
+                .is_err()
+        );
+        assert!(to_request(
+            row(
+                "CODE-AAAA-BBBB",
+                Expires::Text("18446744073709551615".to_string())
+            ),
+            &tp
+        )
+        .await
+        .is_err());
+    }
+}
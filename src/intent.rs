@@ -0,0 +1,142 @@
+use crate::config::dir;
+use crate::pending::Deferred;
+use crate::storage;
+use licc::write::InsertCodeRequest;
+
+/// A code being sent to an output, written to `submitting.jsonl` before the request goes out and finished once the
+/// outcome is in the cache journal.
+///
+/// One left unfinished means the process died in between, and the code may or may not have arrived, see
+/// [`in_doubt`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Intent {
+    /// The output, e.g. `licc`
+    pub sink: String,
+    /// The request, `deferred_at` being when it was sent
+    pub request: Deferred,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Line {
+    Submitting(Intent),
+    Finished { code: String },
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("submitting.jsonl")
+}
+
+fn append(line: &Line) {
+    let written = serde_json::to_string(line)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            storage::append(&file(), &format!("{}\n", line)).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = written {
+        error!("Error writing {}: {}", file().display(), e);
+    }
+}
+
+/// Records that `request`, found by `from`, is about to be sent to `sink`.
+pub fn begin(from: &str, sink: &str, request: &InsertCodeRequest) {
+    append(&Line::Submitting(Intent {
+        sink: sink.to_string(),
+        request: Deferred::new(from, request),
+    }));
+}
+
+/// Records that the outcome of sending `code` to every output is in the cache.
+pub fn finish(code: &str) {
+    append(&Line::Finished {
+        code: code.to_string(),
+    });
+}
+
+/// Submissions that were begun but never finished, by an earlier run that died halfway.
+pub fn in_doubt() -> Vec<Intent> {
+    match storage::read_to_string(&file()) {
+        Ok(contents) => unfinished(&contents),
+        Err(_) => vec![],
+    }
+}
+
+fn unfinished(contents: &str) -> Vec<Intent> {
+    let mut intents: Vec<Intent> = vec![];
+
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Line>(line) {
+            Ok(Line::Submitting(intent)) => intents.push(intent),
+            Ok(Line::Finished { code }) => intents.retain(|intent| intent.request.code != code),
+            Err(e) => warn!("Skipping unreadable line in submitting.jsonl: {}", e),
+        }
+    }
+
+    intents
+}
+
+/// Replaces the log with the submissions still in doubt, once the others are settled.
+pub fn write(intents: &[Intent]) {
+    if intents.is_empty() {
+        if let Err(e) = storage::remove_file(&file()) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Error removing submitting.jsonl: {}", e);
+            }
+        }
+        return;
+    }
+
+    let contents: String = intents
+        .iter()
+        .filter_map(|intent| serde_json::to_string(&Line::Submitting(intent.clone())).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    if let Err(e) = storage::write(&file(), contents) {
+        error!("Error writing submitting.jsonl: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_unfinished() {
+        let request = |code: &str| InsertCodeRequest {
+            code: code.to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+        let line = |line: Line| serde_json::to_string(&line).unwrap() + "\n";
+        let submitting = |code: &str, sink: &str| {
+            Line::Submitting(Intent {
+                sink: sink.to_string(),
+                request: Deferred::new("discord.default", &request(code)),
+            })
+        };
+
+        let contents = [
+            line(submitting("ABCD-EFGH-IJKL", "licc")),
+            line(submitting("ABCD-EFGH-IJKL", "webhook")),
+            line(submitting("MNOP-QRST-UVWX", "licc")),
+            line(Line::Finished {
+                code: "ABCD-EFGH-IJKL".to_string(),
+            }),
+            // cut off by a crash
+            "{\"submitting\":{\"sink\":\"li".to_string(),
+        ]
+        .concat();
+
+        let intents = unfinished(&contents);
+        assert_eq!(intents.len(), 1);
+        assert_eq!(intents[0].sink, "licc");
+        assert_eq!(intents[0].request.code, "MNOP-QRST-UVWX");
+    }
+}
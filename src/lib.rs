@@ -0,0 +1,45 @@
+pub mod anomaly;
+pub mod archive;
+pub mod audit;
+pub mod cache;
+pub mod calendar;
+pub mod cli;
+pub mod client;
+pub mod config;
+#[cfg(feature = "discord")]
+pub mod control;
+pub mod crawl;
+pub mod creator;
+pub mod daemon;
+pub mod digest;
+pub mod dlq;
+pub mod doctor;
+pub mod export;
+pub mod handler;
+pub mod http;
+pub mod import;
+pub mod intent;
+pub mod logging;
+pub mod metrics;
+pub mod migrate;
+pub mod notify;
+pub mod output;
+pub mod parse;
+pub mod pending;
+pub mod progress;
+pub mod resend;
+pub mod retry;
+pub mod reward;
+pub mod script;
+pub mod serve;
+pub mod simulate;
+pub mod source;
+pub mod state;
+pub mod storage;
+pub mod telemetry;
+pub mod translate;
+pub mod validate;
+pub mod version;
+
+#[macro_use]
+extern crate log;
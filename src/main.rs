@@ -1,107 +1,76 @@
-#[cfg(feature = "discord")]
-use crate::handler::discord;
-use licc::write::InsertCodeRequest;
-use std::collections::HashMap;
-
-mod cache;
-mod client;
-mod config;
-mod handler;
-mod parse;
-
-#[macro_use]
-extern crate log;
+use clap::Parser;
+use liccrawler::cli::{Cli, Command};
+use liccrawler::{
+    archive, cache, config, crawl, daemon, dlq, doctor, export, import, logging, progress, resend,
+    script, simulate, source, storage, validate, version,
+};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    zarthus_env_logger::init_named("liccrawler");
-
-    let config = config::read();
-    cache::setup();
-    let mut cache = cache::read();
-
-    #[allow(unused_mut)]
-    let mut requests: HashMap<&str, Vec<InsertCodeRequest>> = HashMap::new();
-    let mut responses: HashMap<String, Option<i32>> = HashMap::new();
+    logging::init();
 
-    #[cfg(feature = "discord")]
-    for (name, discord) in &config.discord {
-        if discord.enabled {
-            let outcome = discord::handle(discord).await;
-
-            match outcome {
-                Ok(out) => {
-                    requests.insert("discord", out);
-
-                    info!(
-                        "Handled discord '{}' (Application ID: {})",
-                        name, discord.application_id
-                    );
-                }
-                Err(err) => {
-                    error!("Error handling discord '{}': {:?}", name, err);
-                }
-            };
-        } else {
-            info!(
-                "Skipping discord '{}', not enabled (Application ID: {})",
-                name, discord.application_id
-            );
-        }
+    let cli = Cli::parse();
+    if let Some(path) = cli.config {
+        config::set_path(path);
+    }
+    match (cli.data_dir, &cli.cache_path) {
+        (Some(path), _) => config::set_dir(path),
+        // The other data files are kept next to the cache, the config directory may well be read-only.
+        (None, Some(cache)) => config::set_dir(match cache.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => ".".into(),
+        }),
+        (None, None) => {}
+    }
+    if let Some(path) = cli.cache_path {
+        cache::set_path(path);
+    }
+    if cli.memory_cache {
+        cache::keep_in_memory();
+        storage::keep_in_memory();
     }
 
-    if config.dry_run {
-        info!("Dry run enabled, not sending requests.");
-
-        for (_, value) in requests {
-            for request in value {
-                if cache.has(&request.code) {
-                    debug!("Skipping '{}', already stored.", &request.code);
-                    continue;
-                }
+    let config = config::read();
+    logging::configure(&config);
+    cache::set_limit(config.cache.limit);
+    validate::configure(&config.validation);
+    script::configure(&config.scripts);
 
-                responses.insert(request.code.clone(), None);
+    if cli.print_config {
+        match config::effective(&config) {
+            Ok(effective) => print!("{}", effective),
+            Err(e) => {
+                log::error!("Error printing the configuration: {}", e);
+                std::process::exit(1);
             }
         }
-    } else {
-        let mut client = config.client.client();
-
-        for (from, value) in requests {
-            for request in value {
-                if cache.has(&request.code) {
-                    info!("Skipping '{}' from {}, already stored.", request.code, from);
-                    continue;
-                }
-
-                match client.insert_code(request.clone()).await {
-                    Ok(response) => {
-                        responses.insert(request.code.clone(), response);
-                        cache.insert(request.code.clone());
-                    }
-                    Err(e) => {
-                        responses.insert(request.code.clone(), None);
-                        error!("Error ({}: {}): {:?}", from, request.code.clone(), e);
-                    }
-                }
-            }
+        return;
+    }
+    if log::log_enabled!(log::Level::Debug) {
+        if let Ok(effective) = config::effective(&config) {
+            log::debug!("Effective configuration:\n{}", effective);
         }
     }
 
-    for (code, response) in responses {
-        match response {
-            Some(num) => {
-                info!("Stored '{}': {}", code, num);
-            }
-            None => {
-                if config.dry_run {
-                    info!("Stored '{}': No", code);
-                } else {
-                    warn!("Stored '{}': No", code);
-                }
-            }
+    match cli.command {
+        Some(Command::Import { file }) => import::run(&config, &file).await,
+        Some(Command::Export { format }) => export::run(format),
+        Some(Command::History { filter, format }) => archive::run(filter, format),
+        Some(Command::Version { check }) => version::run(&config, check).await,
+        Some(Command::Doctor) => doctor::run(&config).await,
+        Some(Command::Source { action }) => source::run(&config, action),
+        Some(Command::Cache { action }) => cache::run(action).await,
+        Some(Command::Dlq { action }) => dlq::run(&config, action).await,
+        Some(Command::TestParse {
+            source,
+            message_file,
+        }) => simulate::run(&config, &source, &message_file).await,
+        Some(Command::Resend { code }) => resend::run(&config, &code).await,
+        None if cli.daemon => daemon::run(config).await,
+        None => {
+            source::select(&config, cli.sources, cli.only_new);
+            progress::enable();
+            crawl::once(&config).await
         }
     }
-
-    cache.bust();
-    cache::write(cache);
 }
@@ -0,0 +1,154 @@
+use crate::config::dir;
+use crate::storage;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Latencies kept per endpoint, older ones are dropped so percentiles follow recent behaviour in daemon mode.
+const SAMPLES: usize = 1000;
+
+#[derive(Debug, Default)]
+struct Endpoint {
+    latencies_ms: VecDeque<u64>,
+    calls: u64,
+    errors: u64,
+}
+
+static ENDPOINTS: Lazy<Mutex<BTreeMap<String, Endpoint>>> = Lazy::new(Default::default);
+
+/// Latency and outcome of the calls made to one endpoint since the process started.
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct Summary {
+    pub calls: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "calls={} errors={} error_rate={:.3} p50_ms={} p95_ms={}",
+            self.calls, self.errors, self.error_rate, self.p50_ms, self.p95_ms
+        )
+    }
+}
+
+pub fn record(endpoint: &str, elapsed: Duration, ok: bool) {
+    let mut endpoints = ENDPOINTS.lock().unwrap_or_else(|e| e.into_inner());
+    let endpoint = endpoints.entry(endpoint.to_string()).or_default();
+
+    endpoint.calls += 1;
+    if !ok {
+        endpoint.errors += 1;
+    }
+    if endpoint.latencies_ms.len() == SAMPLES {
+        endpoint.latencies_ms.pop_front();
+    }
+    endpoint.latencies_ms.push_back(elapsed.as_millis() as u64);
+}
+
+/// Awaits `call`, recording how long it took and whether it failed under `endpoint`.
+pub async fn timed<T, E>(endpoint: &str, call: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let started = Instant::now();
+    let result = call.await;
+
+    record(endpoint, started.elapsed(), result.is_ok());
+
+    result
+}
+
+pub fn summary() -> BTreeMap<String, Summary> {
+    let endpoints = ENDPOINTS.lock().unwrap_or_else(|e| e.into_inner());
+
+    endpoints
+        .iter()
+        .map(|(name, endpoint)| (name.clone(), summarize(endpoint)))
+        .collect()
+}
+
+fn summarize(endpoint: &Endpoint) -> Summary {
+    let mut sorted: Vec<u64> = endpoint.latencies_ms.iter().copied().collect();
+    sorted.sort_unstable();
+
+    Summary {
+        calls: endpoint.calls,
+        errors: endpoint.errors,
+        error_rate: match endpoint.calls {
+            0 => 0.0,
+            calls => endpoint.errors as f64 / calls as f64,
+        },
+        p50_ms: percentile(&sorted, 50),
+        p95_ms: percentile(&sorted, 95),
+    }
+}
+
+/// Nearest-rank percentile of already sorted samples, 0 without samples.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+pub fn render() -> String {
+    serde_json::to_string_pretty(&summary()).unwrap_or_default()
+}
+
+/// Logs a line per endpoint and writes the summary to `metrics.json` in the data directory.
+pub fn write() {
+    let summary = summary();
+
+    for (endpoint, summary) in &summary {
+        info!("endpoint={} {}", endpoint, summary);
+    }
+
+    if summary.is_empty() {
+        return;
+    }
+
+    if let Err(e) = storage::write(&dir().join("metrics.json"), render()) {
+        error!("Error writing metrics.json: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<u64> = (1..=100).collect();
+
+        assert_eq!(percentile(&sorted, 50), 50);
+        assert_eq!(percentile(&sorted, 95), 95);
+        assert_eq!(percentile(&[7], 95), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let endpoint = Endpoint {
+            latencies_ms: VecDeque::from([30, 10, 20, 400]),
+            calls: 4,
+            errors: 1,
+        };
+
+        assert_eq!(
+            summarize(&endpoint),
+            Summary {
+                calls: 4,
+                errors: 1,
+                error_rate: 0.25,
+                p50_ms: 20,
+                p95_ms: 400,
+            }
+        );
+    }
+}
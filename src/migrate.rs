@@ -0,0 +1,209 @@
+//! Upgrades `config.toml` and `cache.toml` written by older versions before they are deserialized.
+//!
+//! Every file carries a `version` key, files without one are version 1. Each migration upgrades a file by one version,
+//! add new ones to the end of the lists below.
+
+use toml::{Table, Value};
+
+type Migration = fn(&mut Table);
+
+const CACHE: [Migration; 2] = [cache_v2, cache_v3];
+const CONFIG: [Migration; 1] = [config_v2];
+
+pub const CACHE_VERSION: u32 = CACHE.len() as u32 + 1;
+pub const CONFIG_VERSION: u32 = CONFIG.len() as u32 + 1;
+
+/// Returns whether the cache was migrated.
+pub fn cache(table: &mut Table) -> bool {
+    run("cache.toml", table, &CACHE)
+}
+
+/// Returns whether the config was migrated, which is not written back so comments are kept.
+pub fn config(table: &mut Table) -> bool {
+    run("config.toml", table, &CONFIG)
+}
+
+fn run(name: &str, table: &mut Table, migrations: &[Migration]) -> bool {
+    let latest = migrations.len() + 1;
+    let version = table
+        .get("version")
+        .and_then(Value::as_integer)
+        .unwrap_or(1)
+        .max(1) as usize;
+
+    if version > latest {
+        warn!(
+            "{} is version {} but this liccrawler only knows up to {}, reading it anyway",
+            name, version, latest
+        );
+        return false;
+    }
+
+    for (from, migration) in migrations.iter().enumerate().skip(version - 1) {
+        info!(
+            "Migrating {} from version {} to {}",
+            name,
+            from + 1,
+            from + 2
+        );
+        migration(table);
+    }
+
+    table.insert("version".to_string(), Value::Integer(latest as i64));
+
+    version < latest
+}
+
+/// Cache entries used to be a bare TTL.
+fn cache_v2(table: &mut Table) {
+    let items = match table.get_mut("items").and_then(Value::as_table_mut) {
+        Some(items) => items,
+        None => return,
+    };
+
+    for (_, entry) in items.iter_mut() {
+        if let Value::Integer(ttl) = entry {
+            let mut upgraded = Table::new();
+            upgraded.insert("ttl".to_string(), Value::Integer(*ttl));
+            *entry = Value::Table(upgraded);
+        }
+    }
+}
+
+/// Entries are tracked per output. Codes were only cached once every output stored them, and only the licc API
+/// assigns IDs, so an ID is kept as licc's.
+fn cache_v3(table: &mut Table) {
+    let items = match table.get_mut("items").and_then(Value::as_table_mut) {
+        Some(items) => items,
+        None => return,
+    };
+
+    for entry in items
+        .iter_mut()
+        .filter_map(|(_, entry)| entry.as_table_mut())
+    {
+        let api_id = match entry.get("api_id") {
+            Some(Value::Integer(api_id)) => *api_id,
+            _ => continue,
+        };
+
+        let mut licc = Table::new();
+        licc.insert("status".to_string(), Value::String("stored".to_string()));
+        licc.insert(
+            "at".to_string(),
+            entry
+                .get("submitted_at")
+                .cloned()
+                .unwrap_or(Value::Integer(0)),
+        );
+        licc.insert("api_id".to_string(), Value::Integer(api_id));
+
+        let mut remotes = Table::new();
+        remotes.insert("licc".to_string(), Value::Table(licc));
+        entry.insert("remotes".to_string(), Value::Table(remotes));
+    }
+}
+
+/// `discord.*.public_key` was never used.
+fn config_v2(table: &mut Table) {
+    let discords = match table.get_mut("discord").and_then(Value::as_table_mut) {
+        Some(discords) => discords,
+        None => return,
+    };
+
+    for (name, discord) in discords.iter_mut() {
+        if let Some(discord) = discord.as_table_mut() {
+            if discord.remove("public_key").is_some() {
+                info!("Ignoring discord.{}.public_key, it is no longer used", name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn table(toml: &str) -> Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_cache() {
+        let mut cache = table(
+            r#"
+            [items]
+            CODE-AAAA-BBBB = 1706227200
+
+            [items.CODE-AAAA-CCCC]
+            ttl = 1706227200
+            "#,
+        );
+
+        assert!(super::cache(&mut cache));
+        assert_eq!(cache["version"].as_integer(), Some(CACHE_VERSION as i64));
+        assert_eq!(
+            cache["items"]["CODE-AAAA-BBBB"]["ttl"].as_integer(),
+            Some(1706227200)
+        );
+        assert_eq!(
+            cache["items"]["CODE-AAAA-CCCC"]["ttl"].as_integer(),
+            Some(1706227200)
+        );
+
+        // already up to date
+        assert!(!super::cache(&mut cache));
+    }
+
+    #[test]
+    fn test_migrate_cache_remotes() {
+        let mut cache = table(
+            r#"
+            version = 2
+
+            [items.CODE-AAAA-BBBB]
+            ttl = 1706227200
+            submitted_at = 1705622400
+            api_id = 5
+
+            [items.CODE-AAAA-CCCC]
+            ttl = 1706227200
+            "#,
+        );
+
+        assert!(super::cache(&mut cache));
+        let licc = &cache["items"]["CODE-AAAA-BBBB"]["remotes"]["licc"];
+        assert_eq!(licc["status"].as_str(), Some("stored"));
+        assert_eq!(licc["at"].as_integer(), Some(1705622400));
+        assert_eq!(licc["api_id"].as_integer(), Some(5));
+        assert!(cache["items"]["CODE-AAAA-CCCC"].get("remotes").is_none());
+    }
+
+    #[test]
+    fn test_migrate_config() {
+        let mut config = table(
+            r#"
+            dry_run = true
+
+            [discord.default]
+            enabled = true
+            public_key = "unused"
+            "#,
+        );
+
+        assert!(super::config(&mut config));
+        assert!(config["discord"]["default"].get("public_key").is_none());
+        assert_eq!(
+            config["discord"]["default"]["enabled"].as_bool(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_newer_version_is_left_alone() {
+        let mut config = table("version = 99\ndry_run = true");
+
+        assert!(!super::config(&mut config));
+        assert_eq!(config["version"].as_integer(), Some(99));
+    }
+}
@@ -0,0 +1,25 @@
+use crate::config::Config;
+use crate::http;
+use crate::logging;
+
+/// Tells the operator about something that needs a human, e.g. a source that went quiet.
+///
+/// Always logged as a warning, and posted to `notify.webhook` as a Discord message if configured.
+pub async fn send(config: &Config, message: &str) {
+    warn!("{}", message);
+
+    let Some(url) = &config.notify.webhook else {
+        return;
+    };
+
+    let result = http::client(&config.http)
+        .post(url)
+        .json(&serde_json::json!({ "content": logging::redact(message) }))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+
+    if let Err(e) = result {
+        error!("Error sending notification: {}", e);
+    }
+}
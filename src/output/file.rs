@@ -0,0 +1,150 @@
+use crate::config::FileFormat;
+use crate::output::{as_update, with_metadata, Metadata, OutputError, OutputSink};
+use licc::write::InsertCodeRequest;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Appends every code to a local JSON lines or CSV file.
+pub struct FileSink {
+    path: PathBuf,
+    format: FileFormat,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf, format: FileFormat) -> Self {
+        Self { path, format }
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputSink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        self.submit_with_metadata(request, &Metadata::default())
+            .await
+    }
+
+    /// The metadata is only kept in JSON, the CSV columns are fixed.
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        let is_new = !self.path.exists();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(OutputError::Io)?;
+
+        let line = match self.format {
+            FileFormat::Json => with_metadata(request, metadata)?.to_string(),
+            FileFormat::Csv => csv_row(request, is_new)?,
+        };
+
+        writeln!(file, "{}", line.trim_end()).map_err(OutputError::Io)?;
+
+        Ok(None)
+    }
+
+    /// Updates are appended as another line in JSON, CSV rows have nowhere to mark them.
+    async fn update_expiry(
+        &self,
+        request: &InsertCodeRequest,
+        _api_id: Option<i32>,
+    ) -> Result<bool, OutputError> {
+        if matches!(self.format, FileFormat::Csv) {
+            return Ok(false);
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(OutputError::Io)?;
+
+        writeln!(file, "{}", as_update(request)?).map_err(OutputError::Io)?;
+
+        Ok(true)
+    }
+
+    /// Whether a line of the file holds the code, in either format.
+    async fn lookup(&self, request: &InsertCodeRequest) -> Result<Option<bool>, OutputError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Ok(Some(
+                contents.lines().any(|line| line.contains(&request.code)),
+            )),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Some(false)),
+            Err(e) => Err(OutputError::Io(e)),
+        }
+    }
+}
+
+fn csv_row(request: &InsertCodeRequest, header: bool) -> Result<String, OutputError> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let submitter = request.submitter.as_ref();
+
+    let mut rows: Vec<Vec<&str>> = vec![];
+    if header {
+        rows.push(vec![
+            "code",
+            "expires_at",
+            "creator_name",
+            "creator_url",
+            "submitter_name",
+            "submitter_url",
+        ]);
+    }
+
+    let expires_at = request.expires_at.to_string();
+    rows.push(vec![
+        request.code.as_str(),
+        expires_at.as_str(),
+        request.creator.name.as_str(),
+        request.creator.url.as_str(),
+        submitter.map(|s| s.name.as_str()).unwrap_or_default(),
+        submitter.map(|s| s.url.as_str()).unwrap_or_default(),
+    ]);
+
+    for row in rows {
+        writer
+            .write_record(row)
+            .map_err(|e| OutputError::Serialize(e.to_string()))?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| OutputError::Serialize(e.to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| OutputError::Serialize(e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_csv_row() {
+        let request = InsertCodeRequest {
+            code: "CODE-AAAA-BBBB".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo, bar".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+
+        assert_eq!(
+            csv_row(&request, false).unwrap(),
+            "CODE-AAAA-BBBB,1706227200,\"foo, bar\",https://www.twitch.tv/foo,,\n"
+        );
+        assert!(csv_row(&request, true)
+            .unwrap()
+            .starts_with("code,expires_at,creator_name"));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::output::{OutputError, OutputSink};
+use licc::write::InsertCodeRequest;
+use std::path::PathBuf;
+use tokio::sync::OnceCell;
+use yup_oauth2::authenticator::DefaultAuthenticator;
+
+const SCOPES: &[&str] = &["https://www.googleapis.com/auth/spreadsheets"];
+
+/// Appends a row per code to a Google Sheet, authenticating as a service account
+/// that the sheet has been shared with.
+pub struct GSheetsSink {
+    spreadsheet_id: String,
+    range: String,
+    service_account_key: PathBuf,
+    auth: OnceCell<DefaultAuthenticator>,
+    client: reqwest::Client,
+}
+
+impl GSheetsSink {
+    pub fn new(
+        spreadsheet_id: String,
+        range: String,
+        service_account_key: PathBuf,
+        client: reqwest::Client,
+    ) -> Self {
+        Self {
+            spreadsheet_id,
+            range,
+            service_account_key,
+            auth: OnceCell::new(),
+            client,
+        }
+    }
+
+    async fn token(&self) -> Result<String, OutputError> {
+        let auth = self
+            .auth
+            .get_or_try_init(|| async {
+                let key = yup_oauth2::read_service_account_key(&self.service_account_key)
+                    .await
+                    .map_err(OutputError::Io)?;
+
+                yup_oauth2::ServiceAccountAuthenticator::builder(key)
+                    .build()
+                    .await
+                    .map_err(OutputError::Io)
+            })
+            .await?;
+
+        let token = auth
+            .token(SCOPES)
+            .await
+            .map_err(|e| OutputError::Auth(e.to_string()))?;
+
+        token
+            .token()
+            .map(str::to_string)
+            .ok_or_else(|| OutputError::Auth("no access token returned".to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputSink for GSheetsSink {
+    fn name(&self) -> &str {
+        "gsheets"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        let token = self.token().await?;
+        let url = format!(
+            "https://sheets.googleapis.com/v4/spreadsheets/{}/values/{}:append",
+            self.spreadsheet_id, self.range
+        );
+
+        self.client
+            .post(url)
+            .bearer_auth(token)
+            .query(&[
+                ("valueInputOption", "USER_ENTERED"),
+                ("insertDataOption", "INSERT_ROWS"),
+            ])
+            .json(&serde_json::json!({ "values": [row(request)] }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(OutputError::Http)?;
+
+        Ok(None)
+    }
+}
+
+fn row(request: &InsertCodeRequest) -> Vec<String> {
+    let expires = time::OffsetDateTime::from_unix_timestamp(request.expires_at as i64)
+        .map(|dt| dt.date().to_string())
+        .unwrap_or_else(|_| request.expires_at.to_string());
+    let submitter = request.submitter.as_ref();
+
+    vec![
+        request.code.clone(),
+        expires,
+        request.creator.name.clone(),
+        request.creator.url.clone(),
+        submitter.map(|s| s.name.clone()).unwrap_or_default(),
+        submitter.map(|s| s.url.clone()).unwrap_or_default(),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_row() {
+        let request = InsertCodeRequest {
+            code: "CODE-AAAA-BBBB".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+
+        assert_eq!(
+            row(&request),
+            vec![
+                "CODE-AAAA-BBBB",
+                "2024-01-26",
+                "foo",
+                "https://www.twitch.tv/foo",
+                "",
+                ""
+            ]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::config::{ClientConfig, Config, HttpConfig};
+use crate::output::{Metadata, OutputError, OutputSink};
+use crate::{metrics, telemetry};
+use licc::client::CodesClient;
+use licc::write::InsertCodeRequest;
+use std::sync::{Arc, Mutex};
+
+pub struct LiccSink {
+    client: CodesClient,
+    cfg: ClientConfig,
+    http: HttpConfig,
+    /// The client carrying the trace context of the current run, and that context
+    traced: Mutex<Option<(String, Arc<CodesClient>)>>,
+}
+
+impl LiccSink {
+    pub fn new(config: &Config, http: reqwest::Client) -> Self {
+        Self {
+            client: config.client.client(http),
+            cfg: config.client.clone(),
+            http: config.http.clone(),
+            traced: Mutex::new(None),
+        }
+    }
+
+    /// A client that carries `traceparent`, the trace context of the run, so the licc server's spans join its trace.
+    ///
+    /// licc takes no headers per request, so the client is built once per run and its connections are reused for
+    /// every code, rather than paying a TLS handshake per code for the context of each.
+    fn traced(&self, traceparent: String) -> Arc<CodesClient> {
+        let mut traced = self.traced.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((known, client)) = traced.as_ref() {
+            if *known == traceparent {
+                return client.clone();
+            }
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        if let Ok(value) = reqwest::header::HeaderValue::from_str(&traceparent) {
+            headers.insert("traceparent", value);
+        }
+        let client = Arc::new(
+            self.cfg
+                .client(crate::http::client_with_headers(&self.http, headers)),
+        );
+        *traced = Some((traceparent, client.clone()));
+
+        client
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputSink for LiccSink {
+    fn name(&self) -> &str {
+        "licc"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        metrics::timed("licc.insert_code", self.client.insert_code(request.clone()))
+            .await
+            .map_err(|e| OutputError::Licc(format!("{:?}", e)))
+    }
+
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        let traceparent = metadata
+            .trace
+            .as_ref()
+            .and_then(|_| telemetry::run_traceparent());
+        let Some(traceparent) = traceparent else {
+            return self.submit(request).await;
+        };
+
+        let client = self.traced(traceparent);
+        metrics::timed("licc.insert_code", client.insert_code(request.clone()))
+            .await
+            .map_err(|e| OutputError::Licc(format!("{:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_traced_client_per_run() {
+        let config = Config::default();
+        let sink = LiccSink::new(&config, crate::http::client(&config.http));
+        let run = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+        let first = sink.traced(run.to_string());
+        assert!(Arc::ptr_eq(&first, &sink.traced(run.to_string())));
+
+        let next = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert!(!Arc::ptr_eq(&first, &sink.traced(next.to_string())));
+    }
+}
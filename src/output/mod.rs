@@ -0,0 +1,348 @@
+use crate::config::{Config, OutputConfig};
+use crate::creator::CreatorAttribution;
+use licc::write::{InsertCodeRequest, SourceLookup};
+
+pub mod file;
+#[cfg(feature = "gsheets")]
+pub mod gsheets;
+pub mod licc_api;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod stdout;
+pub mod webhook;
+
+#[derive(Debug)]
+pub enum OutputError {
+    Licc(String),
+    Auth(String),
+    Io(std::io::Error),
+    Http(reqwest::Error),
+    Mqtt(String),
+    Serialize(String),
+}
+
+impl std::fmt::Display for OutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputError::Licc(e) => write!(f, "licc: {}", e),
+            OutputError::Auth(e) => write!(f, "auth: {}", e),
+            OutputError::Io(e) => write!(f, "io: {}", e),
+            OutputError::Http(e) => write!(f, "http: {}", e),
+            OutputError::Mqtt(e) => write!(f, "mqtt: {}", e),
+            OutputError::Serialize(e) => write!(f, "serialize: {}", e),
+        }
+    }
+}
+
+impl OutputError {
+    /// Whether retrying cannot help, e.g. the code was rejected as invalid, as opposed to an outage or rate limit.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            OutputError::Http(e) => e.status().is_some_and(|s| {
+                s.is_client_error()
+                    && s != reqwest::StatusCode::REQUEST_TIMEOUT
+                    && s != reqwest::StatusCode::TOO_MANY_REQUESTS
+            }),
+            // licc only gives us its error formatted, the status is in there
+            OutputError::Licc(e) => PERMANENT_LICC_ERRORS.iter().any(|p| e.contains(p)),
+            OutputError::Serialize(_) => true,
+            OutputError::Auth(_) | OutputError::Io(_) | OutputError::Mqtt(_) => false,
+        }
+    }
+
+    /// The HTTP status the target responded with, if it got that far.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            OutputError::Http(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+/// How the licc API rejects codes it will never accept, e.g. an invalid code or unknown creator.
+const PERMANENT_LICC_ERRORS: [&str; 6] = [
+    "400",
+    "Bad Request",
+    "404",
+    "Not Found",
+    "422",
+    "Unprocessable",
+];
+
+/// Somewhere newly found codes are sent to.
+#[async_trait::async_trait]
+pub trait OutputSink: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns the ID the target assigned to the code, if it has such a concept.
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError>;
+
+    /// [`submit`](OutputSink::submit) with what else is known about the code, which only sinks
+    /// that store more than the licc API accepts keep.
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        _metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        self.submit(request).await
+    }
+
+    /// Tells the target a code submitted earlier with a guessed expiry expires at `request.expires_at`,
+    /// `api_id` being the ID the target assigned to it, if any.
+    ///
+    /// Returns `false` if the target has no way to update codes.
+    async fn update_expiry(
+        &self,
+        _request: &InsertCodeRequest,
+        _api_id: Option<i32>,
+    ) -> Result<bool, OutputError> {
+        Ok(false)
+    }
+
+    /// Whether the target has the code, to settle a submission a crash left in doubt.
+    ///
+    /// Returns `None` if the target has no way to tell, the code is then submitted again.
+    async fn lookup(&self, _request: &InsertCodeRequest) -> Result<Option<bool>, OutputError> {
+        Ok(None)
+    }
+}
+
+/// What a source told about a code beyond what the licc API accepts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// Readable description of the reward, e.g. `1x Electrum Chest`
+    pub reward: Option<String>,
+    /// The only platform the code can be redeemed on, e.g. `Xbox`
+    pub platform: Option<String>,
+    /// How the creator was worked out, for sources that had to guess; not sent to outputs
+    pub attribution: Option<CreatorAttribution>,
+    /// The message the code was found in, for `[scripts]`; not sent to outputs
+    pub message: Option<String>,
+    /// When that message was posted, to report codes in the order they were; not sent to outputs
+    pub posted_at: Option<u64>,
+    /// W3C `traceparent` of the submission, set per sink when `[telemetry]` is configured; not sent to outputs
+    pub trace: Option<String>,
+}
+
+/// A request in the shape the licc API receives it, as [`InsertCodeRequest`] itself cannot be serialized.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Payload {
+    pub code: String,
+    pub expires_at: u64,
+    pub creator_name: String,
+    pub creator_url: String,
+    pub submitter_name: Option<String>,
+    pub submitter_url: Option<String>,
+}
+
+impl From<&InsertCodeRequest> for Payload {
+    fn from(request: &InsertCodeRequest) -> Self {
+        Self {
+            code: request.code.clone(),
+            expires_at: request.expires_at,
+            creator_name: request.creator.name.clone(),
+            creator_url: request.creator.url.clone(),
+            submitter_name: request.submitter.as_ref().map(|s| s.name.clone()),
+            submitter_url: request.submitter.as_ref().map(|s| s.url.clone()),
+        }
+    }
+}
+
+impl From<Payload> for InsertCodeRequest {
+    fn from(payload: Payload) -> Self {
+        InsertCodeRequest {
+            code: payload.code,
+            expires_at: payload.expires_at,
+            creator: SourceLookup {
+                name: payload.creator_name,
+                url: payload.creator_url,
+            },
+            submitter: match (payload.submitter_name, payload.submitter_url) {
+                (Some(name), Some(url)) => Some(SourceLookup { name, url }),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The request as JSON, with `reward` and `platform` fields added if known.
+fn with_metadata(
+    request: &InsertCodeRequest,
+    metadata: &Metadata,
+) -> Result<serde_json::Value, OutputError> {
+    let mut value = serde_json::to_value(Payload::from(request))
+        .map_err(|e| OutputError::Serialize(e.to_string()))?;
+
+    if let Some(object) = value.as_object_mut() {
+        if let Some(reward) = &metadata.reward {
+            object.insert("reward".to_string(), reward.as_str().into());
+        }
+        if let Some(platform) = &metadata.platform {
+            object.insert("platform".to_string(), platform.as_str().into());
+        }
+    }
+
+    Ok(value)
+}
+
+/// The request as JSON, marked as an update of a code sent before.
+fn as_update(request: &InsertCodeRequest) -> Result<serde_json::Value, OutputError> {
+    let mut value = with_metadata(request, &Metadata::default())?;
+
+    if let Some(object) = value.as_object_mut() {
+        object.insert("update".to_string(), true.into());
+    }
+
+    Ok(value)
+}
+
+/// Identifies a submission across retries: the code and where it was found.
+///
+/// Requests do not carry the message they came from, the submitter url (usually a link to the message) or creator url
+/// stands in for it.
+pub fn idempotency_key(request: &InsertCodeRequest) -> String {
+    let from = match &request.submitter {
+        Some(submitter) => &submitter.url,
+        None => &request.creator.url,
+    };
+
+    format!(
+        "{:016x}",
+        crate::audit::fnv1a(format!("{}\n{}", request.code, from).as_bytes())
+    )
+}
+
+/// Builds the configured sinks, falling back to only the licc API when `outputs` is empty.
+pub fn sinks(config: &Config) -> Vec<Box<dyn OutputSink>> {
+    let http = crate::http::client(&config.http);
+
+    if config.outputs.is_empty() {
+        return vec![Box::new(licc_api::LiccSink::new(config, http))];
+    }
+
+    config
+        .outputs
+        .iter()
+        .filter_map(|output| -> Option<Box<dyn OutputSink>> {
+            match output {
+                OutputConfig::Licc => Some(Box::new(licc_api::LiccSink::new(config, http.clone()))),
+                OutputConfig::File { path, format } => {
+                    Some(Box::new(file::FileSink::new(path.clone(), *format)))
+                }
+                OutputConfig::Webhook { url, template } => Some(Box::new(
+                    webhook::WebhookSink::new(url.clone(), template.clone(), http.clone()),
+                )),
+                OutputConfig::Stdout => Some(Box::new(stdout::StdoutSink)),
+                #[cfg(feature = "mqtt")]
+                OutputConfig::Mqtt {
+                    host,
+                    port,
+                    topic,
+                    username,
+                    password,
+                } => Some(Box::new(mqtt::MqttSink::new(
+                    host.as_str(),
+                    *port,
+                    topic.clone(),
+                    username.clone().zip(password.clone()),
+                ))),
+                #[cfg(not(feature = "mqtt"))]
+                OutputConfig::Mqtt { .. } => {
+                    error!("Output 'mqtt' requires the 'mqtt' feature, skipping it");
+                    None
+                }
+                #[cfg(feature = "gsheets")]
+                OutputConfig::GSheets {
+                    spreadsheet_id,
+                    range,
+                    service_account_key,
+                } => Some(Box::new(gsheets::GSheetsSink::new(
+                    spreadsheet_id.clone(),
+                    range.clone(),
+                    service_account_key.clone(),
+                    http.clone(),
+                ))),
+                #[cfg(not(feature = "gsheets"))]
+                OutputConfig::GSheets { .. } => {
+                    error!("Output 'gsheets' requires the 'gsheets' feature, skipping it");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key() {
+        let request = |code: &str, submitter: Option<&str>| InsertCodeRequest {
+            code: code.to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: submitter.map(|url| SourceLookup {
+                name: "bar".to_string(),
+                url: url.to_string(),
+            }),
+        };
+
+        let key = idempotency_key(&request(
+            "ABCD-EFGH-IJKL",
+            Some("https://discord.com/channels/1/2"),
+        ));
+        assert_eq!(key.len(), 16);
+        assert_eq!(
+            key,
+            idempotency_key(&request(
+                "ABCD-EFGH-IJKL",
+                Some("https://discord.com/channels/1/2")
+            ))
+        );
+        assert_ne!(
+            key,
+            idempotency_key(&request(
+                "ABCD-EFGH-IJKM",
+                Some("https://discord.com/channels/1/2")
+            ))
+        );
+        assert_ne!(key, idempotency_key(&request("ABCD-EFGH-IJKL", None)));
+    }
+
+    #[test]
+    fn test_with_metadata() {
+        let request = InsertCodeRequest {
+            code: "ABCD-EFGH-IJKL".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+        let metadata = Metadata {
+            reward: Some("1x Electrum Chest".to_string()),
+            platform: Some("Xbox".to_string()),
+            trace: Some("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            with_metadata(&request, &metadata).unwrap(),
+            serde_json::json!({
+                "code": "ABCD-EFGH-IJKL",
+                "expires_at": 1706227200,
+                "creator_name": "foo",
+                "creator_url": "https://www.twitch.tv/foo",
+                "submitter_name": null,
+                "submitter_url": null,
+                "reward": "1x Electrum Chest",
+                "platform": "Xbox",
+            })
+        );
+    }
+}
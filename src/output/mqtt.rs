@@ -0,0 +1,108 @@
+use crate::output::{as_update, with_metadata, Metadata, OutputError, OutputSink};
+use licc::write::InsertCodeRequest;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// How long to wait for the broker to acknowledge a message.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Publishes every code as a retained JSON message, so subscribers that connect later still see the active codes.
+pub struct MqttSink {
+    options: MqttOptions,
+    topic: String,
+    /// Held for the whole of a publish, the event loop can only wait for one acknowledgement at a time
+    connection: Mutex<Option<(AsyncClient, EventLoop)>>,
+}
+
+impl MqttSink {
+    pub fn new(
+        host: &str,
+        port: u16,
+        topic: String,
+        credentials: Option<(String, String)>,
+    ) -> Self {
+        let mut options =
+            MqttOptions::new(format!("liccrawler-{}", std::process::id()), host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some((username, password)) = credentials {
+            options.set_credentials(username, password);
+        }
+
+        Self {
+            options,
+            topic,
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Publishes `payload` for `code` and waits for the broker to acknowledge it, connecting first if need be.
+    async fn publish(&self, code: &str, payload: serde_json::Value) -> Result<(), OutputError> {
+        let topic = self.topic.replace("{code}", code);
+        let mut connection = self.connection.lock().await;
+        let (client, eventloop) =
+            connection.get_or_insert_with(|| AsyncClient::new(self.options.clone(), 10));
+
+        client
+            .publish(topic, QoS::AtLeastOnce, true, payload.to_string())
+            .await
+            .map_err(|e| OutputError::Mqtt(e.to_string()))?;
+
+        let acknowledged = tokio::time::timeout(PUBLISH_TIMEOUT, async {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::PubAck(_))) => return Ok(()),
+                    Ok(_) => continue,
+                    Err(e) => return Err(OutputError::Mqtt(e.to_string())),
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(OutputError::Mqtt(
+                "timed out waiting for the broker".to_string(),
+            ))
+        });
+
+        // Reconnect on the next code rather than reuse a connection in an unknown state.
+        if acknowledged.is_err() {
+            *connection = None;
+        }
+
+        acknowledged
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputSink for MqttSink {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        self.submit_with_metadata(request, &Metadata::default())
+            .await
+    }
+
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        self.publish(&request.code, with_metadata(request, metadata)?)
+            .await?;
+
+        Ok(None)
+    }
+
+    /// The retained message of the code is replaced.
+    async fn update_expiry(
+        &self,
+        request: &InsertCodeRequest,
+        _api_id: Option<i32>,
+    ) -> Result<bool, OutputError> {
+        self.publish(&request.code, as_update(request)?).await?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,39 @@
+use crate::output::{as_update, with_metadata, Metadata, OutputError, OutputSink};
+use licc::write::InsertCodeRequest;
+
+/// Prints every code as a JSON line, for piping into other tools.
+pub struct StdoutSink;
+
+#[async_trait::async_trait]
+impl OutputSink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        self.submit_with_metadata(request, &Metadata::default())
+            .await
+    }
+
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        let line = with_metadata(request, metadata)?.to_string();
+
+        println!("{}", line);
+
+        Ok(None)
+    }
+
+    async fn update_expiry(
+        &self,
+        request: &InsertCodeRequest,
+        _api_id: Option<i32>,
+    ) -> Result<bool, OutputError> {
+        println!("{}", as_update(request)?);
+
+        Ok(true)
+    }
+}
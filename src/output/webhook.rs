@@ -0,0 +1,196 @@
+use crate::output::{as_update, idempotency_key, with_metadata, Metadata, OutputError, OutputSink};
+use licc::write::InsertCodeRequest;
+use once_cell::sync::Lazy;
+
+/// `{{code}}`, `{{ creator.name }}` and such in a webhook template.
+static PLACEHOLDER: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"\{\{\s*([\w.]+)\s*\}\}").unwrap());
+
+/// POSTs every code as JSON to an arbitrary endpoint.
+pub struct WebhookSink {
+    url: String,
+    template: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String, template: Option<String>, client: reqwest::Client) -> Self {
+        Self {
+            url,
+            template,
+            client,
+        }
+    }
+
+    /// The body for `request`: the template with its placeholders filled in, or the request itself.
+    fn body(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+        update: bool,
+    ) -> Result<serde_json::Value, OutputError> {
+        match (&self.template, update) {
+            (Some(template), _) => render(template, request, metadata, update),
+            (None, false) => with_metadata(request, metadata),
+            (None, true) => as_update(request),
+        }
+    }
+}
+
+/// Fills in the placeholders of a template, each value escaped to go inside a JSON string; `{{expires_at}}` and
+/// `{{update}}` may also stand on their own, as a number and a boolean.
+///
+/// Placeholders: `code`, `expires_at` (unix timestamp), `expires` (RFC 3339), `creator.name`, `creator.url`,
+/// `submitter.name`, `submitter.url`, `reward`, `platform` and `update`, whether the code was sent before; unknown
+/// values are empty.
+fn render(
+    template: &str,
+    request: &InsertCodeRequest,
+    metadata: &Metadata,
+    update: bool,
+) -> Result<serde_json::Value, OutputError> {
+    let expires = time::OffsetDateTime::from_unix_timestamp(request.expires_at as i64)
+        .ok()
+        .map(|dt| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                dt.year(),
+                dt.month() as u8,
+                dt.day(),
+                dt.hour(),
+                dt.minute(),
+                dt.second()
+            )
+        });
+    let submitter = request.submitter.as_ref();
+
+    let mut unknown: Option<String> = None;
+    let body = PLACEHOLDER.replace_all(template, |c: &regex::Captures| {
+        let value = match &c[1] {
+            "code" => Some(request.code.clone()),
+            "expires_at" => Some(request.expires_at.to_string()),
+            "expires" => expires.clone(),
+            "creator.name" => Some(request.creator.name.clone()),
+            "creator.url" => Some(request.creator.url.clone()),
+            "submitter.name" => submitter.map(|s| s.name.clone()),
+            "submitter.url" => submitter.map(|s| s.url.clone()),
+            "reward" => metadata.reward.clone(),
+            "platform" => metadata.platform.clone(),
+            "update" => Some(update.to_string()),
+            other => {
+                unknown.get_or_insert(other.to_string());
+                None
+            }
+        };
+
+        // as a JSON string without its quotes
+        let escaped = serde_json::Value::String(value.unwrap_or_default()).to_string();
+        escaped[1..escaped.len() - 1].to_string()
+    });
+
+    if let Some(placeholder) = unknown {
+        return Err(OutputError::Serialize(format!(
+            "unknown placeholder {{{{{}}}}} in the webhook template",
+            placeholder
+        )));
+    }
+
+    serde_json::from_str(&body)
+        .map_err(|e| OutputError::Serialize(format!("webhook template is not JSON: {}", e)))
+}
+
+#[async_trait::async_trait]
+impl OutputSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn submit(&self, request: &InsertCodeRequest) -> Result<Option<i32>, OutputError> {
+        self.submit_with_metadata(request, &Metadata::default())
+            .await
+    }
+
+    async fn submit_with_metadata(
+        &self,
+        request: &InsertCodeRequest,
+        metadata: &Metadata,
+    ) -> Result<Option<i32>, OutputError> {
+        self.client
+            .post(&self.url)
+            .header("Idempotency-Key", idempotency_key(request))
+            .json(&self.body(request, metadata, false)?)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(OutputError::Http)?;
+
+        Ok(None)
+    }
+
+    async fn update_expiry(
+        &self,
+        request: &InsertCodeRequest,
+        _api_id: Option<i32>,
+    ) -> Result<bool, OutputError> {
+        self.client
+            .post(&self.url)
+            .header(
+                "Idempotency-Key",
+                format!("{}-{}", idempotency_key(request), request.expires_at),
+            )
+            .json(&self.body(request, &Metadata::default(), true)?)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(OutputError::Http)?;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_render() {
+        let request = InsertCodeRequest {
+            code: "ABCD-EFGH-IJKL".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "Foo \"the\" Bar".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+        let metadata = Metadata {
+            reward: Some("1x Electrum Chest".to_string()),
+            ..Default::default()
+        };
+
+        let body = render(
+            r#"{"text": "{{code}} by {{ creator.name }} ({{reward}}), until {{expires}}", "at": {{expires_at}}, "by": "{{submitter.name}}", "update": {{update}}}"#,
+            &request,
+            &metadata,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "text": "ABCD-EFGH-IJKL by Foo \"the\" Bar (1x Electrum Chest), until 2024-01-26T00:00:00Z",
+                "at": 1706227200,
+                "by": "",
+                "update": false,
+            })
+        );
+
+        assert!(matches!(
+            render("{\"x\": \"{{nope}}\"}", &request, &metadata, false),
+            Err(OutputError::Serialize(e)) if e.contains("{{nope}}")
+        ));
+        assert!(render("{{code}}", &request, &metadata, false).is_err());
+    }
+}
@@ -1,74 +1,369 @@
 #![allow(dead_code)]
 
+use crate::translate::{self, Language, Translator};
+use once_cell::sync::Lazy;
 use std::num::ParseIntError;
 use std::ops::Add;
 use time::{Date, Duration, Month};
 
+// Compiled once and shared by every `TimeParser`, which are created per source and message.
+static YYYYMMDD: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"(?:(\d{4})[/-])?(\d{1,2})[/-](\d{1,2})").unwrap()); // 2024/1/1
+static MMDDYYYY_FULL: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"(?:^|\D)(\d{1,2})[/-](\d{1,2})[/-](\d{4}|\d{2})\b").unwrap()
+    // 1/1/2024, 1/1/24
+});
+static MMDDYYYY: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"(\d{1,2})[/-](\d{1,2})[/-]?(\d{1,4})?").unwrap()); // 1/1/2024
+static AMERICAN_EDGE_CASE: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"(\d{1,2})[/-](\d{1,2})[/-]?(\d{2})").unwrap()); // 1/1/24
+static ENGDATE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"(\w{3,16}) (\d{1,2})(?:\w{2})?(?:,? (\d{4}))?").unwrap()
+    // Jan 1st, 2024
+});
+static END_OF_DAY_PHRASE: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"\b(?:eod|end of (?:the )?day)\b").unwrap());
+/// Time zones a time of day may be given in, see `TimeParser::time_of_day`.
+const ZONE: &str =
+    r"(pt|pst|pdt|pacific|mt|mst|mdt|mountain|ct|cst|cdt|central|et|est|edt|eastern|utc|gmt)";
+// at noon PT, until midnight, at reset, reset PT; "reset" alone is too common a word to be taken for a time.
+static TIME_OF_DAY_PHRASE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(&format!(
+        r"\b(?:(?:at |by |until |till )?(noon|midday|midnight)\b(?:\s*{zone}\b)?|(?:at |by |until |till )(?:the )?(?:daily |game |server )?(reset)\b(?:\s*{zone}\b)?|(?:the )?(?:daily |game |server )?(reset)\s*{zone}\b)",
+        zone = ZONE
+    ))
+    .unwrap()
+});
+static DAY_MONTH_NAME: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(r"\b(\d{1,2})(?:st|nd|rd|th)?\.? (?:of )?(\w{3,16})\.?(?:,? (\d{4}))?")
+        .unwrap()
+    // 1st January 2024, 1. Januar 2024
+});
+static DOTTED_DATE: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"\b(\d{1,2})\.(\d{1,2})\.(\d{4}|\d{2})?").unwrap()); // 1.1.2024
+
+/// Between the start and end of a range of dates, e.g. the `through` in "January 10th through 17th".
+const RANGE: &str = r"\s*(?:-|–|—|to|through|thru|until|till)\s*";
+static NUMERIC_RANGE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(&format!(
+        r"\b(\d{{4}}-\d{{1,2}}-\d{{1,2}}|\d{{1,2}}/\d{{1,2}}(?:/\d{{2,4}})?){}(\d{{4}}-\d{{1,2}}-\d{{1,2}}|\d{{1,2}}/\d{{1,2}}(?:/\d{{2,4}})?)\b",
+        RANGE
+    ))
+    .unwrap()
+    // 1/10-1/17, 2024-01-10 to 2024-01-17
+});
+static MONTH_RANGE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(&format!(
+        r"\b(\w{{3,16}}) (\d{{1,2}})(?:st|nd|rd|th)?{}(?:(\w{{3,16}}) )?(\d{{1,2}})(?:st|nd|rd|th)?\b",
+        RANGE
+    ))
+    .unwrap()
+    // jan 10-17, january 30th to february 2nd
+});
+/// Follows the end of a [`MONTH_RANGE`] that is a time instead, e.g. "jan 18 - 11:59 pm" or "jan 18th - 2 pm".
+static TIME_AFTER: Lazy<regex::Regex> =
+    Lazy::new(|| regex::Regex::new(r"^(?::\d|\s*[ap]\.?m\b)").unwrap());
+static DAY_RANGE: Lazy<regex::Regex> = Lazy::new(|| {
+    regex::Regex::new(&format!(
+        r"\b(\d{{1,2}})(?:st|nd|rd|th)?{}(\d{{1,2}})(?:st|nd|rd|th)?\.? (?:of )?(\w{{3,16}})",
+        RANGE
+    ))
+    .unwrap()
+    // 10-17 january, 10th to 17th of january
+});
+
+/// How a source writes dates, see `locale` in the source config.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Locale {
+    /// 1/20/2024, January 20th
+    #[serde(rename = "en-US")]
+    EnUs,
+    /// 20/01/2024, 20th January
+    #[serde(rename = "en-GB")]
+    EnGb,
+    /// 20.01.2024, 20. Januar
+    #[serde(rename = "de-DE")]
+    DeDe,
+}
+
+impl Locale {
+    fn day_first(self) -> bool {
+        self != Locale::EnUs
+    }
+
+    /// The language month names may be written in besides English.
+    fn language(self) -> Option<&'static Language> {
+        match self {
+            Locale::DeDe => translate::language("german"),
+            Locale::EnUs | Locale::EnGb => None,
+        }
+    }
+}
+
+/// How parsed expiries are rounded, see `expiry_rounding` in `[parser]`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryRounding {
+    /// As parsed
+    #[default]
+    Exact,
+    /// Up to the end of the day (23:59) in UTC
+    EndOfDay,
+    /// Up to the next noon in Pacific time, when codes usually switch over
+    NoonPacific,
+}
+
+impl ExpiryRounding {
+    pub fn apply(self, ts: u64) -> u64 {
+        let date = date_of(ts);
+
+        match self {
+            ExpiryRounding::Exact => ts,
+            ExpiryRounding::EndOfDay => {
+                let end = date.midnight().assume_utc().unix_timestamp() as u64 + END_OF_DAY;
+                match ts <= end {
+                    true => end,
+                    false => end + 24 * 60 * 60,
+                }
+            }
+            ExpiryRounding::NoonPacific => match noon_pacific(date) {
+                noon if ts <= noon => noon,
+                _ => noon_pacific(date.add(Duration::days(1))),
+            },
+        }
+    }
+}
+
 pub struct TimeParser {
-    regex_yyyymmdd: regex::Regex,
-    regex_mmddyyyy: regex::Regex,
-    regex_american_edge_case: regex::Regex,
-    regex_engdate: regex::Regex,
+    open_ended_days: i64,
+    safety_net_days: i64,
+    translator: Option<Translator>,
+    locale: Option<Locale>,
+    rounding: ExpiryRounding,
+    reset_hour: u64,
+}
+
+/// Phrases promising no particular date, which expire `open_ended_days` from now.
+const OPEN_ENDED_IDIOMS: [&str; 4] = [
+    "while supplies last",
+    "until the next stream",
+    "until next stream",
+    "until further notice",
+];
+
+impl Default for TimeParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TimeParser {
     pub fn new() -> TimeParser {
         TimeParser {
-            regex_yyyymmdd: regex::Regex::new(r"(?:(\d{4})[/-])?(\d{1,2})[/-](\d{1,2})").unwrap(), // 2024/1/1
-            regex_mmddyyyy: regex::Regex::new(r"(\d{1,2})[/-](\d{1,2})[/-]?(\d{1,4})?").unwrap(), // 1/1/2024
-            regex_american_edge_case: regex::Regex::new(r"(\d{1,2})[/-](\d{1,2})[/-]?(\d{2})")
-                .unwrap(), // 1/1/24
-            regex_engdate: regex::Regex::new(r"(\w{3,16}) (\d{1,2})(?:\w{2})?(?:,? (\d{4}))?") // Jan 1st, 2024
-                .unwrap(),
+            open_ended_days: 7,
+            safety_net_days: 32,
+            translator: None,
+            locale: None,
+            rounding: ExpiryRounding::Exact,
+            reset_hour: 0,
+        }
+    }
+
+    /// Days until codes with open-ended expiries ("while supplies last") are assumed to expire.
+    pub fn open_ended_days(mut self, days: u32) -> TimeParser {
+        self.open_ended_days = days as i64;
+        self
+    }
+
+    /// Days past a week after the message an expiry may lie before it is assumed to be a mistake.
+    pub fn safety_net_days(mut self, days: u32) -> TimeParser {
+        self.safety_net_days = days as i64;
+        self
+    }
+
+    /// Translates expiries in other languages to English before parsing them.
+    pub fn translator(mut self, translator: Translator) -> TimeParser {
+        self.translator = Some(translator);
+        self
+    }
+
+    /// Reads dates the way `locale` writes them, instead of guessing the order of day and month from "am"/"pm".
+    pub fn locale(mut self, locale: Option<Locale>) -> TimeParser {
+        self.locale = locale;
+        self
+    }
+
+    /// Rounds what was parsed as `rounding` says, the fallback for expiries that could not be parsed is left as is.
+    pub fn rounding(mut self, rounding: ExpiryRounding) -> TimeParser {
+        self.rounding = rounding;
+        self
+    }
+
+    /// The hour in UTC the game resets at, what "expires at reset" means.
+    pub fn reset_hour(mut self, hour: u32) -> TimeParser {
+        self.reset_hour = hour as u64;
+        self
+    }
+
+    pub fn from_config(config: &crate::config::ParserConfig) -> TimeParser {
+        let parser = TimeParser::new()
+            .open_ended_days(config.open_ended_days)
+            .safety_net_days(config.safety_net_days)
+            .rounding(config.expiry_rounding)
+            .reset_hour(config.reset_hour);
+
+        match config.translate {
+            true => parser.translator(Translator::new(config.translate_command.clone())),
+            false => parser,
         }
     }
 
-    pub fn parse(&self, ts: String, safety_net: bool) -> Option<u64> {
+    pub async fn parse(&self, ts: String, safety_net: bool) -> Option<u64> {
+        self.parse_at(ts, safety_net, now()).await
+    }
+
+    /// Parses relative to `anchor` (e.g. when the message was posted) instead of now,
+    /// so "next week" or a date without a year mean what they did at the time.
+    pub async fn parse_at(&self, ts: String, safety_net: bool, anchor: u64) -> Option<u64> {
         if ts.is_empty() {
             return None;
         }
 
-        let normalized_ts = ts.to_lowercase();
+        let mut normalized_ts = ts.to_lowercase();
+        if let Some(translator) = &self.translator {
+            if let Some(translated) = translator.translate(&normalized_ts).await {
+                normalized_ts = translated;
+            }
+        }
+        if self.locale == Some(Locale::DeDe) {
+            normalized_ts = DOTTED_DATE
+                .replace_all(&normalized_ts, "$1/$2/$3")
+                .to_string();
+        }
+
+        normalized_ts = self.range_end(&normalized_ts);
+
+        // Midnight is the end of a day already, rounding it would make it the end of the next one.
+        let rounding = match normalized_ts.contains("midnight") {
+            true => ExpiryRounding::Exact,
+            false => self.rounding,
+        };
+
+        let parsed = self.parse_user_expires_string(normalized_ts, date_of(anchor));
 
         if safety_net {
-            self.parse_user_expires_string(normalized_ts)
-                .map(|unixtime| self.safety_net(unixtime, &ts))
+            parsed.map(|unixtime| rounding.apply(self.safety_net(unixtime, &ts, anchor)))
         } else {
-            self.parse_user_expires_string(normalized_ts)
+            parsed.map(|unixtime| rounding.apply(unixtime))
         }
     }
 
-    fn parse_user_expires_string(&self, normalized_ts: String) -> Option<u64> {
+    fn parse_user_expires_string(&self, normalized_ts: String, anchor: Date) -> Option<u64> {
         if normalized_ts.contains("next week") {
-            return Some(next_week());
+            return self.date_to_unix(anchor.add(Duration::days(7)));
+        }
+
+        if OPEN_ENDED_IDIOMS
+            .iter()
+            .any(|idiom| normalized_ts.contains(idiom))
+        {
+            return self.date_to_unix(anchor.add(Duration::days(self.open_ended_days)));
+        }
+
+        if normalized_ts.contains("end of the month") || normalized_ts.contains("end of month") {
+            let last = time::util::days_in_month(anchor.month(), anchor.year());
+
+            return Date::from_calendar_date(anchor.year(), anchor.month(), last)
+                .ok()
+                .and_then(|d| self.date_to_unix(d))
+                .map(|ts| ts + END_OF_DAY);
+        }
+
+        if END_OF_DAY_PHRASE.is_match(&normalized_ts) {
+            let remainder = END_OF_DAY_PHRASE.replace(&normalized_ts, "").to_string();
+
+            return self
+                .parse_user_expires_string(remainder, anchor)
+                .or_else(|| self.date_to_unix(anchor))
+                .map(|ts| ts + END_OF_DAY);
+        }
+
+        if let Some(mtch) = TIME_OF_DAY_PHRASE.captures(&normalized_ts) {
+            // Each way of writing it has its own groups, the phrase and then the zone.
+            let first = |groups: [usize; 3]| groups.into_iter().find_map(|g| mtch.get(g));
+            let (phrase, zone) = (
+                first([1, 3, 5]).map_or(String::new(), |p| p.as_str().to_string()),
+                first([2, 4, 6]).map(|z| z.as_str().to_string()),
+            );
+            let remainder = TIME_OF_DAY_PHRASE.replace(&normalized_ts, "").to_string();
+            let date = self
+                .parse_user_expires_string(remainder, anchor)
+                .map(date_of)
+                .unwrap_or(anchor);
+
+            return self
+                .date_to_unix(date)
+                .map(|midnight| midnight + self.time_of_day(&phrase, zone.as_deref(), date));
         }
 
         // stupid assumption: Swap numbers if time contains AM or PM
         let is_american = normalized_ts.contains("am") || normalized_ts.contains("pm");
+        // Sources with a locale say which order they use, and years first are always year, month, day.
+        let (day_first, swap_iso) = match self.locale {
+            Some(locale) => (locale.day_first(), false),
+            None => (is_american, is_american),
+        };
 
         if is_american {
-            if let Some(mtch) = self.regex_american_edge_case.captures(&normalized_ts) {
+            if let Some(mtch) = AMERICAN_EDGE_CASE.captures(&normalized_ts) {
                 return self
-                    .handle_captures(mtch, Some(3), 1, 2, false, is_american)
+                    .handle_captures(mtch, (Some(3), 1, 2), false, day_first, anchor)
                     .unwrap_or(None);
             }
         }
 
-        if let Some(mtch) = self.regex_yyyymmdd.captures(&normalized_ts) {
+        // before yyyymmdd, which would match the month and day and drop the year
+        if let Some(mtch) = MMDDYYYY_FULL.captures(&normalized_ts) {
             return self
-                .handle_captures(mtch, Some(1), 2, 3, false, is_american)
+                .handle_captures(mtch, (Some(3), 1, 2), false, day_first, anchor)
                 .unwrap_or(None);
         }
 
-        if let Some(mtch) = self.regex_mmddyyyy.captures(&normalized_ts) {
+        if let Some(mtch) = YYYYMMDD.captures(&normalized_ts) {
+            let swap = match mtch.get(1) {
+                Some(_) => swap_iso,
+                None => day_first,
+            };
+
+            return self
+                .handle_captures(mtch, (Some(1), 2, 3), false, swap, anchor)
+                .unwrap_or(None);
+        }
+
+        if let Some(mtch) = MMDDYYYY.captures(&normalized_ts) {
             return self
-                .handle_captures(mtch, Some(3), 1, 2, false, is_american)
+                .handle_captures(mtch, (Some(3), 1, 2), false, day_first, anchor)
                 .unwrap_or(None);
         }
 
-        if let Some(mtch) = self.regex_engdate.captures(&normalized_ts) {
+        if self.locale.is_some_and(Locale::day_first) {
+            if let Some(mtch) = DAY_MONTH_NAME
+                .captures_iter(&normalized_ts)
+                .find(|mtch| self.month_number(&mtch[2]).is_some())
+            {
+                return self
+                    .handle_captures(mtch, (Some(3), 2, 1), true, false, anchor)
+                    .unwrap_or(None);
+            }
+        }
+
+        // Any "word N" matches, e.g. "gives 10 chests", only one naming a month is a date.
+        if let Some(mtch) = ENGDATE
+            .captures_iter(&normalized_ts)
+            .find(|mtch| self.month_number(&mtch[1]).is_some())
+        {
             return self
-                .handle_captures(mtch, Some(3), 1, 2, true, is_american)
+                .handle_captures(mtch, (Some(3), 1, 2), true, is_american, anchor)
                 .unwrap_or(None);
         }
 
@@ -80,27 +375,85 @@ impl TimeParser {
         None
     }
 
+    /// Replaces a range of dates with its end, the day the code stops working, as the date patterns would take the
+    /// first date they find: "valid 1/10-1/17" reads as "valid 1/17".
+    ///
+    /// Ranges of times such as "2-4 pm", and dates followed by a time such as "jan 18 - 11:59 pm", are left alone.
+    fn range_end(&self, text: &str) -> String {
+        let numeric = NUMERIC_RANGE.replace_all(text, "$2");
+
+        let text = MONTH_RANGE.replace_all(&numeric, |c: &regex::Captures| {
+            let end_month = c.get(3).map(|m| m.as_str());
+            let end = c.get(0).map_or(numeric.len(), |m| m.end());
+            let is_range = self.month_number(&c[1]).is_some()
+                && end_month.is_none_or(|m| self.month_number(m).is_some())
+                && !TIME_AFTER.is_match(&numeric[end..]);
+
+            match is_range {
+                true => format!("{} {}", end_month.unwrap_or(&c[1]), &c[4]),
+                false => c[0].to_string(),
+            }
+        });
+
+        DAY_RANGE
+            .replace_all(&text, |c: &regex::Captures| {
+                match self.month_number(&c[3]).is_some() {
+                    true => format!("{} {}", &c[3], &c[2]),
+                    false => c[0].to_string(),
+                }
+            })
+            .to_string()
+    }
+
+    /// Seconds after midnight UTC on `date` that "noon", "midnight" (the end of the day) or "reset" in `zone` are,
+    /// UTC if no zone is given. Resets are at `reset_hour`, whatever the zone.
+    fn time_of_day(&self, phrase: &str, zone: Option<&str>, date: Date) -> u64 {
+        let hour = match phrase {
+            "noon" | "midday" => 12,
+            "midnight" => 24,
+            _ => return self.reset_hour * 60 * 60,
+        };
+
+        let us_offset = |standard: i64| match pacific_dst(date) {
+            true => standard - 1,
+            false => standard,
+        };
+        let behind_utc = match zone {
+            Some("pt" | "pst" | "pdt" | "pacific") => us_offset(8),
+            Some("mt" | "mst" | "mdt" | "mountain") => us_offset(7),
+            Some("ct" | "cst" | "cdt" | "central") => us_offset(6),
+            Some("et" | "est" | "edt" | "eastern") => us_offset(5),
+            _ => 0,
+        };
+
+        ((hour + behind_utc) * 60 * 60) as u64
+    }
+
+    /// The date in `mtch`, whose year (if any), month and day are in the capture groups `indices`.
     fn handle_captures(
         &self,
         mtch: regex::Captures,
-        year_index: Option<usize>,
-        mut month_index: usize,
-        mut day_index: usize,
+        indices: (Option<usize>, usize, usize),
         month_is_string: bool,
         is_american: bool,
+        anchor: Date,
     ) -> Result<Option<u64>, ParseIntError> {
+        let (year_index, mut month_index, mut day_index) = indices;
+
         if is_american && !month_is_string {
             debug!("Swapping month and day as american date indicated");
             (month_index, day_index) = (day_index, month_index);
         }
 
         let m = if month_is_string {
-            let m_str = match mtch.get(month_index) {
-                Some(m) => m.as_str().to_string(),
+            // A word that names no month is no date, rather than one in whatever month it is.
+            match mtch
+                .get(month_index)
+                .and_then(|m| self.month_number(m.as_str()))
+            {
+                Some(m) => m,
                 None => return Ok(None),
-            };
-
-            self.month_from_str(m_str)
+            }
         } else {
             match mtch.get(month_index) {
                 Some(m) => m.as_str().parse::<u8>(),
@@ -115,13 +468,16 @@ impl TimeParser {
 
         let mut y = match year_index {
             Some(i) => match mtch.get(i) {
-                Some(yr) => yr.as_str().parse::<i32>().unwrap_or(self.predict_year(m)),
-                None => self.predict_year(m),
+                Some(yr) => yr
+                    .as_str()
+                    .parse::<i32>()
+                    .unwrap_or(self.predict_year(m, anchor)),
+                None => self.predict_year(m, anchor),
             },
-            None => self.predict_year(m),
+            None => self.predict_year(m, anchor),
         };
 
-        y = self.normalize_year(y);
+        y = self.normalize_year(y, anchor);
 
         Ok(self.format_from_ymd(y, m, d))
     }
@@ -144,24 +500,23 @@ impl TimeParser {
         }
     }
 
-    fn predict_year(&self, month: u8) -> i32 {
-        let now = time::OffsetDateTime::now_utc();
-        let year = now.year();
+    fn predict_year(&self, month: u8, anchor: Date) -> i32 {
+        let year = anchor.year();
 
         let parsed_month = match Month::try_from(month) {
             Ok(m) => m,
             Err(_) => return year,
         };
 
-        if parsed_month.eq(&Month::January) && now.month().eq(&Month::December) {
+        if parsed_month.eq(&Month::January) && anchor.month().eq(&Month::December) {
             year + 1
         } else {
             year
         }
     }
 
-    fn normalize_year(&self, mut year: i32) -> i32 {
-        let this_year = time::OffsetDateTime::now_utc().year();
+    fn normalize_year(&self, mut year: i32, anchor: Date) -> i32 {
+        let this_year = anchor.year();
 
         if year < 1000 {
             year += 2000;
@@ -178,22 +533,31 @@ impl TimeParser {
         year
     }
 
-    fn month_from_str(&self, m: String) -> u8 {
-        match m.to_lowercase().as_str() {
-            "jan" | "january" => 1,
-            "feb" | "february" => 2,
-            "mar" | "march" => 3,
-            "apr" | "april" => 4,
-            "may" => 5,
-            "jun" | "june" => 6,
-            "jul" | "july" => 7,
-            "aug" | "august" => 8,
-            "sep" | "september" => 9,
-            "oct" | "october" => 10,
-            "nov" | "november" => 11,
-            "dec" | "december" => 12,
-            _ => time::OffsetDateTime::now_utc().month() as u8,
-        }
+    /// The month named `m`, in English or the language of the locale.
+    fn month_number(&self, m: &str) -> Option<u8> {
+        let m = m.to_lowercase();
+
+        let english = match m.as_str() {
+            "jan" | "january" => Some(1),
+            "feb" | "february" => Some(2),
+            "mar" | "march" => Some(3),
+            "apr" | "april" => Some(4),
+            "may" => Some(5),
+            "jun" | "june" => Some(6),
+            "jul" | "july" => Some(7),
+            "aug" | "august" => Some(8),
+            "sep" | "september" => Some(9),
+            "oct" | "october" => Some(10),
+            "nov" | "november" => Some(11),
+            "dec" | "december" => Some(12),
+            _ => None,
+        };
+
+        english.or_else(|| {
+            self.locale
+                .and_then(Locale::language)
+                .and_then(|language| language.month_number(&m))
+        })
     }
 
     fn date_to_unix(&self, date: Date) -> Option<u64> {
@@ -206,12 +570,11 @@ impl TimeParser {
         Some(ts as u64)
     }
 
-    /// if ts is incredibly far in the future, just return next week.
-    fn safety_net(&self, ts: u64, tsstring: &str) -> u64 {
-        let nextweek = next_week();
+    /// if ts is incredibly far in the future of `anchor`, just return the week after it.
+    fn safety_net(&self, ts: u64, tsstring: &str, anchor: u64) -> u64 {
+        let nextweek = week_after(anchor);
 
-        // 2764800 = 32 days in seconds
-        if ts > nextweek + 2764800 {
+        if ts > nextweek + self.safety_net_days as u64 * 86400 {
             warn!(
                 "Had to use safety net for date conversion of '{}', '{}'",
                 ts, tsstring
@@ -223,19 +586,198 @@ impl TimeParser {
     }
 }
 
+/// 23:59, in seconds after midnight.
+const END_OF_DAY: u64 = 23 * 60 * 60 + 59 * 60;
+
+fn today() -> Date {
+    time::OffsetDateTime::now_utc().date()
+}
+
+fn now() -> u64 {
+    time::OffsetDateTime::now_utc().unix_timestamp() as u64
+}
+
+fn date_of(ts: u64) -> Date {
+    time::OffsetDateTime::from_unix_timestamp(ts as i64)
+        .map(|dt| dt.date())
+        .unwrap_or_else(|_| today())
+}
+
+/// Noon in Pacific time on `date`: 20:00 UTC, or 19:00 UTC during daylight saving time.
+fn noon_pacific(date: Date) -> u64 {
+    let midnight = date.midnight().assume_utc().unix_timestamp() as u64;
+
+    match pacific_dst(date) {
+        true => midnight + 19 * 60 * 60,
+        false => midnight + 20 * 60 * 60,
+    }
+}
+
+/// Whether `date` is in daylight saving time in the US, from the second Sunday of March to the first Sunday of November.
+fn pacific_dst(date: Date) -> bool {
+    let nth_sunday = |month: Month, n: i64| {
+        Date::from_calendar_date(date.year(), month, 1)
+            .ok()
+            .map(|first| {
+                let until_sunday = (7 - first.weekday().number_days_from_sunday() as i64) % 7;
+                first.add(Duration::days(until_sunday + 7 * (n - 1)))
+            })
+    };
+
+    match (nth_sunday(Month::March, 2), nth_sunday(Month::November, 1)) {
+        (Some(start), Some(end)) => date >= start && date < end,
+        _ => false,
+    }
+}
+
 pub fn next_week() -> u64 {
-    time::OffsetDateTime::now_utc()
-        .date()
+    week_after(now())
+}
+
+/// Midnight a week after the day of `ts`, the fallback expiry for codes without a usable one.
+pub fn week_after(ts: u64) -> u64 {
+    date_of(ts)
         .add(Duration::days(7))
         .midnight()
         .assume_utc()
         .unix_timestamp() as u64
 }
 
+/// Whether `code` may be a code, see [`validate::any_source`](crate::validate::any_source).
 pub fn validate_code(code: &str) -> bool {
-    let clen = code.replace('-', "").len();
+    crate::validate::any_source(code)
+}
+
+/// Codes mentioned anywhere in free text such as chat messages and announcements, in order of appearance.
+///
+/// Codes may sit mid-sentence, against punctuation or in markup ("use code:**ABCD-EFGH-IJKL**!"), but not inside a
+/// longer word, number or link.
+pub fn find_codes(text: &str) -> Vec<String> {
+    static CODE: Lazy<regex::Regex> = Lazy::new(|| {
+        regex::Regex::new(r"[A-Z0-9!@#$%^&*]{4}(?:-?[A-Z0-9!@#$%^&*]{4}){2,4}").unwrap()
+    });
+
+    // Markup is not part of a code, but `*` could otherwise be taken for one of the symbols codes may contain.
+    let text = ["**", "__", "~~", "||", "`"]
+        .iter()
+        .fold(text.to_string(), |text, markup| text.replace(markup, " "));
+
+    let mut codes: Vec<String> = vec![];
+    let mut at = 0;
+
+    while let Some(found) = CODE.find_at(&text, at) {
+        let before = text[..found.start()].chars().next_back();
+        let after = text[found.end()..].chars().next();
+        let joined = before.is_some_and(|c| c.is_alphanumeric() || "-/.=".contains(c))
+            || after.is_some_and(|c| c.is_alphanumeric() || "-/".contains(c));
+
+        if joined {
+            // It may have started a character early on a symbol, try again from the next one.
+            at = found.start()
+                + text[found.start()..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            continue;
+        }
+
+        let code = found.as_str();
+        if validate_code(code) && !codes.iter().any(|c| c == code) {
+            codes.push(code.to_string());
+        }
+        at = found.end();
+    }
+
+    codes
+}
+
+/// The first link in free text and the creator name it implies, e.g. `https://twitch.tv/foo` -> `foo`.
+pub fn find_creator(text: &str) -> Option<(String, String)> {
+    let start = text.find("http://").or_else(|| text.find("https://"))?;
+    let url = text[start..]
+        .split(|c: char| c.is_whitespace() || c == ')' || c == '>')
+        .next()?;
+    let name = url.trim_end_matches('/').rsplit('/').next()?.to_lowercase();
 
-    clen == 16 || clen == 12
+    Some((name, url.to_string()))
+}
+
+/// Platforms codes are sometimes restricted to, as spelled in messages and as reported.
+const PLATFORMS: [(&str, &str); 10] = [
+    ("xbox", "Xbox"),
+    ("playstation", "PlayStation"),
+    ("ps4", "PlayStation"),
+    ("ps5", "PlayStation"),
+    ("switch", "Switch"),
+    ("console", "Console"),
+    ("steam", "Steam"),
+    ("epic", "Epic"),
+    ("pc", "PC"),
+    ("mobile", "Mobile"),
+];
+
+/// "Xbox only", "only on PS4", "PC exclusive"; merely mentioning platforms does not restrict a code.
+static PLATFORM_ONLY: Lazy<regex::Regex> = Lazy::new(|| {
+    let platforms = PLATFORMS.map(|(spelled, _)| spelled).join("|");
+
+    regex::Regex::new(&format!(
+        r"(?i)\b(?:({0})s?\s+(?:only|exclusive)|only\s+(?:on|for)\s+(?:the\s+)?({0})s?)\b",
+        platforms
+    ))
+    .unwrap()
+});
+
+/// The platform a code is restricted to, if the text says so.
+pub fn find_platform(text: &str) -> Option<String> {
+    let caps = PLATFORM_ONLY.captures(text)?;
+    let spelled = caps.get(1).or_else(|| caps.get(2))?.as_str().to_lowercase();
+
+    PLATFORMS
+        .iter()
+        .find(|(platform, _)| *platform == spelled)
+        .map(|(_, name)| name.to_string())
+}
+
+/// Undoes common transcription damage: markdown emphasis, typographic dashes and lowercase letters.
+pub fn normalize_code(code: &str) -> String {
+    code.trim_matches(|c: char| c == '*' || c == '`' || c == '_' || c.is_whitespace())
+        .replace(
+            ['\u{2010}', '\u{2011}', '\u{2012}', '\u{2013}', '\u{2014}'],
+            "-",
+        )
+        .replace(' ', "")
+        .to_uppercase()
+}
+
+const CONFUSABLES: [&str; 2] = ["O0", "I1L"];
+
+/// Positions beyond this are left untouched, to keep the number of variants small.
+const MAX_CONFUSABLE_POSITIONS: usize = 4;
+
+/// Variants of `code` with commonly confused characters (O/0, I/1/L) swapped, excluding `code` itself.
+pub fn confusion_variants(code: &str) -> Vec<String> {
+    let mut variants = vec![String::new()];
+    let mut positions = 0;
+
+    for c in code.chars() {
+        let group = CONFUSABLES.iter().find(|g| g.contains(c));
+
+        variants = match group {
+            Some(group) if positions < MAX_CONFUSABLE_POSITIONS => {
+                positions += 1;
+
+                variants
+                    .iter()
+                    .flat_map(|v| group.chars().map(move |alt| format!("{}{}", v, alt)))
+                    .collect()
+            }
+            _ => variants.into_iter().map(|v| v + &c.to_string()).collect(),
+        };
+    }
+
+    variants.retain(|v| v != code);
+
+    variants
 }
 
 #[cfg(test)]
@@ -250,25 +792,108 @@ mod test {
         assert!(!validate_code("123456781234567"));
     }
 
+    #[test]
+    fn test_normalize_code() {
+        assert_eq!(
+            normalize_code("**abcd\u{2013}efgh-ijkl**"),
+            "ABCD-EFGH-IJKL"
+        );
+        assert_eq!(normalize_code("`ABCD EFGH IJKL`"), "ABCDEFGHIJKL");
+    }
+
+    #[test]
+    fn test_find_codes() {
+        assert_eq!(
+            find_codes("new code: ABCD-EFGH-IJKL, also (ABCD1234EFGH5678) and ABCD-EFGH-IJKL"),
+            vec!["ABCD-EFGH-IJKL", "ABCD1234EFGH5678"]
+        );
+        assert_eq!(
+            find_codes("S#PR-1SE!-C0DE expires friday"),
+            vec!["S#PR-1SE!-C0DE"]
+        );
+        assert!(find_codes("an announcement about ABCD-EFGH").is_empty());
+        assert!(find_codes("abcd-efgh-ijkl").is_empty());
+    }
+
+    #[test]
+    fn test_find_codes_in_prose() {
+        assert_eq!(
+            find_codes("Use code SPOO-KYSP-OOKY-2024 before Friday!"),
+            vec!["SPOO-KYSP-OOKY-2024"]
+        );
+        assert_eq!(
+            find_codes("code:**ABCDEFGHIJKL**! and ||WXYZ-1234-EFGH||."),
+            vec!["ABCDEFGHIJKL", "WXYZ-1234-EFGH"]
+        );
+        assert_eq!(find_codes("_ABCD-EFGH-IJKL_"), vec!["ABCD-EFGH-IJKL"]);
+        assert_eq!(find_codes("!ABCDEFGHIJKL"), vec!["ABCDEFGHIJKL"]);
+        assert!(find_codes("https://www.twitch.tv/ABCDEFGHIJKL").is_empty());
+        assert!(find_codes("ABCD-EFGH-IJKL-MNOP-QRST").is_empty());
+        assert!(find_codes("<#123456789012345678>").is_empty());
+    }
+
+    #[test]
+    fn test_find_creator() {
+        assert_eq!(
+            find_creator("ABCD-EFGH-IJKL from [Foo](https://www.twitch.tv/Foo/)"),
+            Some(("foo".to_string(), "https://www.twitch.tv/Foo/".to_string()))
+        );
+        assert_eq!(find_creator("no links here"), None);
+    }
+
+    #[test]
+    fn test_find_platform() {
+        assert_eq!(
+            find_platform("ABCD-EFGH-IJKL (Xbox only)").as_deref(),
+            Some("Xbox")
+        );
+        assert_eq!(
+            find_platform("Only on PS4, sorry PC folks").as_deref(),
+            Some("PlayStation")
+        );
+        assert_eq!(
+            find_platform("consoles only this time").as_deref(),
+            Some("Console")
+        );
+        assert_eq!(find_platform("PC exclusive").as_deref(), Some("PC"));
+        assert_eq!(find_platform("Works on Steam, Epic and consoles"), None);
+        assert_eq!(find_platform("Expires: January 26th, 2024"), None);
+    }
+
+    #[test]
+    fn test_confusion_variants() {
+        assert!(confusion_variants("ABCD-EFGH-JKMN").is_empty());
+
+        let variants = confusion_variants("CODE-I");
+        assert_eq!(variants.len(), 5);
+        assert!(variants.contains(&"C0DE-I".to_string()));
+        assert!(variants.contains(&"C0DE-L".to_string()));
+        assert!(!variants.contains(&"CODE-I".to_string()));
+
+        assert_eq!(confusion_variants("OOOOOO").len(), 15);
+    }
+
     struct TimeParseUnit {
         pub input: &'static str,
         pub expected: Option<u64>,
     }
 
-    #[test]
-    fn test_parse_expires_string() {
+    #[tokio::test]
+    async fn test_parse_expires_string() {
         zarthus_env_logger::init_named("liccrawler");
 
         const SPECIAL_CASE_KEY: u64 = 1;
+        // 2024-01-15, years before last are taken as typos so the dates below only hold around then
+        const ANCHOR: u64 = 1705276800;
 
-        let time_parse_units: [TimeParseUnit; 15] = [
+        let time_parse_units: [TimeParseUnit; 14] = [
             TimeParseUnit {
                 input: "next week",
-                expected: Some(next_week()),
+                expected: Some(week_after(ANCHOR)),
             },
             TimeParseUnit {
                 input: "Next Week",
-                expected: Some(next_week()),
+                expected: Some(week_after(ANCHOR)),
             },
             TimeParseUnit {
                 input: "idk",
@@ -279,20 +904,13 @@ mod test {
                 expected: None,
             },
             TimeParseUnit {
-                input: "Expires Jan 26th",
-                expected: Some(SPECIAL_CASE_KEY),
-            },
-            TimeParseUnit {
-                // note no need to handle 6AM PST
-                // in the grand scheme of things the remote upcasts it to "next day" anyway.
-                input: "Expires 1/15/24 6AM PST.",
-                expected: Some(1705276800),
+                // no month, not the 10th of the current one
+                input: "Expires Fooday 10",
+                expected: None,
             },
             TimeParseUnit {
-                // note no need to handle 6AM PST
-                // in the grand scheme of things the remote upcasts it to "next day" anyway.
-                input: "Expires 1/15/25 6AM PST.",
-                expected: Some(1736899200),
+                input: "Expires Jan 26th",
+                expected: Some(SPECIAL_CASE_KEY),
             },
             TimeParseUnit {
                 input: "This code is active until January 18th @ 2 PM PT.",
@@ -316,15 +934,16 @@ mod test {
             },
             TimeParseUnit {
                 input: "Expires 2024-3-4",
-                expected: Some(1711238400),
+                expected: Some(1709510400),
             },
             TimeParseUnit {
+                // without a locale, am/pm still swap month and day
                 input: "Expires 2024-3-4 PM",
-                expected: Some(1711238400),
+                expected: Some(1712102400),
             },
             TimeParseUnit {
                 input: "Expires 2024-1-1",
-                expected: Some(1706054400),
+                expected: Some(1704067200),
             },
         ];
 
@@ -334,7 +953,10 @@ mod test {
             if let Some(ts) = unit.expected {
                 if ts == SPECIAL_CASE_KEY {
                     assert!(
-                        parser.parse(unit.input.to_string(), false).is_some(),
+                        parser
+                            .parse_at(unit.input.to_string(), false, ANCHOR)
+                            .await
+                            .is_some(),
                         "Failed to parse: {}",
                         unit.input,
                     );
@@ -344,7 +966,7 @@ mod test {
             }
 
             assert_eq!(
-                parser.parse(unit.input.to_string(), false),
+                parser.parse_at(unit.input.to_string(), false, ANCHOR).await,
                 unit.expected,
                 "Failed to parse: {}",
                 unit.input,
@@ -352,6 +974,193 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn test_date_ranges() {
+        // 2024-01-05
+        let anchor = 1704412800;
+        let parse = |text: &str| TimeParser::new().parse_at(text.to_string(), false, anchor);
+        // 2024-01-17
+        let end = Some(1705449600);
+
+        assert_eq!(parse("Active January 10th through 17th").await, end);
+        assert_eq!(parse("Valid Jan 10–17").await, end);
+        assert_eq!(parse("valid 1/10-1/17").await, end);
+        assert_eq!(parse("Valid from 1/10/2024 to 1/17/2024").await, end);
+        assert_eq!(parse("Valid 2024-01-10 to 2024-01-17").await, end);
+        assert_eq!(parse("Valid 10th to 17th of January").await, end);
+        // 2024-02-02
+        assert_eq!(
+            parse("Runs January 30th to February 2nd").await,
+            Some(1706832000)
+        );
+        // not ranges of dates
+        assert_eq!(
+            TimeParser::new().range_end("expires 2-4 pm"),
+            "expires 2-4 pm"
+        );
+        // 2024-01-18
+        assert_eq!(
+            parse("Expires Jan 18 - 11:59 PM PT").await,
+            Some(1705536000)
+        );
+        assert_eq!(parse("Jan 18th - 2 PM").await, Some(1705536000));
+        assert_eq!(parse("Expires Jan 10").await, Some(1704844800));
+        assert_eq!(
+            parse("Gives 10 chests, expires Jan 10").await,
+            Some(1704844800)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_idioms() {
+        let parser = TimeParser::new().open_ended_days(3);
+        // 2024-01-15
+        let anchor = 1705276800;
+        let parse = |text: &str| parser.parse_at(text.to_string(), false, anchor);
+        let day = 24 * 60 * 60;
+
+        assert_eq!(
+            parse("Expires 2024-3-4 EOD").await,
+            parse("Expires 2024-3-4").await.map(|ts| ts + END_OF_DAY)
+        );
+        assert_eq!(
+            parse("Valid until the end of the day").await,
+            Some(anchor + END_OF_DAY)
+        );
+        assert_eq!(parse("While supplies last!").await, Some(anchor + 3 * day));
+        // 2024-01-31
+        assert_eq!(
+            parse("Expires at the end of the month").await,
+            Some(1706659200 + END_OF_DAY)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_time_of_day_phrases() {
+        let parser = TimeParser::new().reset_hour(16);
+        // 2024-01-15
+        let anchor = 1705276800;
+        let parse = |text: &str| parser.parse_at(text.to_string(), false, anchor);
+        let jan_18 = 1705536000;
+
+        assert_eq!(
+            parse("Valid until January 18th at noon PT").await,
+            Some(jan_18 + 20 * 60 * 60)
+        );
+        assert_eq!(
+            parse("Expires 1/18 at noon Eastern").await,
+            Some(jan_18 + 17 * 60 * 60)
+        );
+        assert_eq!(
+            parse("Valid until midnight").await,
+            Some(anchor + 24 * 60 * 60)
+        );
+        assert_eq!(
+            parse("until 2024-01-18 midnight UTC").await,
+            Some(jan_18 + 24 * 60 * 60)
+        );
+        assert_eq!(parse("Expires at reset").await, Some(anchor + 16 * 60 * 60));
+        assert_eq!(
+            parse("expires jan 18 at the daily reset").await,
+            Some(jan_18 + 16 * 60 * 60)
+        );
+        assert_eq!(parse("Expires reset PT").await, Some(anchor + 16 * 60 * 60));
+        // Not a time of day, the date alone is parsed.
+        assert_eq!(
+            parse("Expires jan 18, after the reset of the event").await,
+            parse("Expires jan 18").await
+        );
+
+        // 2024-07-18, daylight saving time
+        assert_eq!(
+            parse("Valid until July 18th at noon PT").await,
+            Some(1721260800 + 19 * 60 * 60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_locale() {
+        // 2024-01-15
+        let anchor = 1705276800;
+        let parse = |locale, text: &str| {
+            let text = text.to_string();
+            async move {
+                TimeParser::new()
+                    .locale(Some(locale))
+                    .parse_at(text, false, anchor)
+                    .await
+            }
+        };
+
+        assert_eq!(
+            parse(Locale::EnUs, "Expires 02/03/2024").await,
+            Some(1706918400)
+        );
+        assert_eq!(
+            parse(Locale::EnGb, "Expires 02/03/2024").await,
+            Some(1709337600)
+        );
+        assert_eq!(
+            parse(Locale::EnGb, "Expires 2024/02/03").await,
+            Some(1706918400)
+        );
+        assert_eq!(
+            parse(Locale::DeDe, "Gültig bis 02.03.2024").await,
+            Some(1709337600)
+        );
+        assert_eq!(
+            parse(Locale::EnUs, "Expires 1/20 11:59 PM").await,
+            Some(1705708800)
+        );
+        assert_eq!(
+            parse(Locale::EnGb, "Expires 20/1 at 11pm").await,
+            Some(1705708800)
+        );
+        assert_eq!(
+            parse(Locale::EnGb, "until the 20th of January").await,
+            Some(1705708800)
+        );
+        assert_eq!(
+            parse(Locale::DeDe, "bis zum 20. Januar 2024").await,
+            Some(1705708800)
+        );
+        assert_eq!(parse(Locale::DeDe, "bis 2. März").await, Some(1709337600));
+    }
+
+    #[tokio::test]
+    async fn test_parse_at() {
+        let parser = TimeParser::new();
+        // 2024-01-15
+        let anchor = 1705276800;
+
+        assert_eq!(
+            parser
+                .parse_at("Expires next week".to_string(), false, anchor)
+                .await,
+            Some(week_after(anchor))
+        );
+        assert_eq!(
+            parser
+                .parse_at("Expires Jan 26th".to_string(), false, anchor)
+                .await,
+            Some(1706227200)
+        );
+        // note no need to handle 6AM PST
+        // in the grand scheme of things the remote upcasts it to "next day" anyway.
+        assert_eq!(
+            parser
+                .parse_at("Expires 1/15/24 6AM PST.".to_string(), false, anchor)
+                .await,
+            Some(1705276800)
+        );
+        assert_eq!(
+            parser
+                .parse_at("Expires 1/15/25 6AM PST.".to_string(), false, anchor)
+                .await,
+            Some(1736899200)
+        );
+    }
+
     #[test]
     fn test_safety_net() {
         let future = std::time::SystemTime::now()
@@ -363,6 +1172,97 @@ mod test {
             * 60;
 
         let parser = TimeParser::new();
-        assert!(parser.safety_net(future, "test") < future);
+        assert!(parser.safety_net(future, "test", now()) < future);
+    }
+
+    #[tokio::test]
+    async fn test_translated_expiry() {
+        // 2024-01-15
+        let anchor = 1705276800;
+        let parser = TimeParser::new().translator(Translator::default());
+
+        assert_eq!(
+            parser
+                .parse_at("Gültig bis 26. Januar".to_string(), false, anchor)
+                .await,
+            Some(1706227200)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_safety_net_anchored_to_message() {
+        // 2024-01-01, long before now: a backfilled message
+        let anchor = 1704067200;
+        let parser = TimeParser::new();
+
+        assert_eq!(
+            parser
+                .parse_at("Expires 2024-01-21".to_string(), true, anchor)
+                .await,
+            Some(1705795200)
+        );
+        assert_eq!(
+            parser
+                .parse_at("Expires 2024-03-01".to_string(), true, anchor)
+                .await,
+            Some(week_after(anchor))
+        );
+
+        let parser = TimeParser::new().safety_net_days(90);
+        assert_eq!(
+            parser
+                .parse_at("Expires 2024-03-01".to_string(), true, anchor)
+                .await,
+            Some(1709251200)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expiry_rounding() {
+        // 2024-01-15, standard time
+        let winter = 1705276800;
+        // 2024-07-01, daylight saving time
+        let summer = 1719792000;
+
+        assert_eq!(ExpiryRounding::Exact.apply(winter), winter);
+        assert_eq!(ExpiryRounding::EndOfDay.apply(winter), winter + END_OF_DAY);
+        assert_eq!(
+            ExpiryRounding::NoonPacific.apply(winter),
+            winter + 20 * 60 * 60
+        );
+        assert_eq!(
+            ExpiryRounding::NoonPacific.apply(summer),
+            summer + 19 * 60 * 60
+        );
+        // past noon, so the next day's
+        assert_eq!(
+            ExpiryRounding::NoonPacific.apply(winter + 21 * 60 * 60),
+            winter + (24 + 20) * 60 * 60
+        );
+
+        let parser = TimeParser::new().rounding(ExpiryRounding::EndOfDay);
+        assert_eq!(
+            parser
+                .parse_at("Expires 2024-01-21".to_string(), false, winter)
+                .await,
+            Some(1705795200 + END_OF_DAY)
+        );
+        // the end of the day it was posted, not of the next
+        assert_eq!(
+            parser
+                .parse_at("Valid until midnight".to_string(), false, winter)
+                .await,
+            Some(winter + 24 * 60 * 60)
+        );
+    }
+
+    #[test]
+    fn test_pacific_dst() {
+        let date = |month, day| Date::from_calendar_date(2024, month, day).unwrap();
+
+        assert!(!pacific_dst(date(Month::March, 9)));
+        assert!(pacific_dst(date(Month::March, 10)));
+        assert!(pacific_dst(date(Month::November, 2)));
+        assert!(!pacific_dst(date(Month::November, 3)));
     }
 }
@@ -0,0 +1,122 @@
+use crate::config::dir;
+use crate::storage;
+use licc::write::{InsertCodeRequest, SourceLookup};
+
+/// A code found in a run that ran out of `max_run_seconds` before submitting it, kept in `pending.jsonl` and
+/// submitted first thing next run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Deferred {
+    pub code: String,
+    pub source: String,
+    /// Unix timestamp of the run that ran out of time
+    pub deferred_at: u64,
+    pub expires_at: u64,
+    pub creator_name: String,
+    pub creator_url: String,
+    pub submitter_name: Option<String>,
+    pub submitter_url: Option<String>,
+}
+
+impl Deferred {
+    pub fn new(source: &str, request: &InsertCodeRequest) -> Self {
+        Self {
+            code: request.code.clone(),
+            source: source.to_string(),
+            deferred_at: time::OffsetDateTime::now_utc().unix_timestamp() as u64,
+            expires_at: request.expires_at,
+            creator_name: request.creator.name.clone(),
+            creator_url: request.creator.url.clone(),
+            submitter_name: request.submitter.as_ref().map(|s| s.name.clone()),
+            submitter_url: request.submitter.as_ref().map(|s| s.url.clone()),
+        }
+    }
+
+    pub fn to_request(&self) -> InsertCodeRequest {
+        InsertCodeRequest {
+            code: self.code.clone(),
+            expires_at: self.expires_at,
+            creator: SourceLookup {
+                name: self.creator_name.clone(),
+                url: self.creator_url.clone(),
+            },
+            submitter: match (&self.submitter_name, &self.submitter_url) {
+                (Some(name), Some(url)) => Some(SourceLookup {
+                    name: name.clone(),
+                    url: url.clone(),
+                }),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("pending.jsonl")
+}
+
+pub fn read() -> Vec<Deferred> {
+    let contents = match storage::read_to_string(&file()) {
+        Ok(contents) => contents,
+        Err(_) => return vec![],
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| error!("Skipping unreadable line in pending.jsonl: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+/// Replaces the queue, once a run has taken in everything that was in it.
+pub fn write(deferred: &[Deferred]) {
+    if deferred.is_empty() {
+        if let Err(e) = storage::remove_file(&file()) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Error removing pending.jsonl: {}", e);
+            }
+        }
+        return;
+    }
+
+    let contents: String = deferred
+        .iter()
+        .filter_map(|deferred| serde_json::to_string(deferred).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    if let Err(e) = storage::write(&file(), contents) {
+        error!("Error writing pending.jsonl: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::output::Payload;
+
+    #[test]
+    fn test_deferred_round_trip() {
+        let request = InsertCodeRequest {
+            code: "ABCD-EFGH-IJKL".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+
+        let deferred = Deferred::new("discord.default", &request);
+        let line = serde_json::to_string(&deferred).unwrap();
+
+        assert_eq!(serde_json::from_str::<Deferred>(&line).unwrap(), deferred);
+        assert_eq!(
+            Payload::from(&deferred.to_request()),
+            Payload::from(&request)
+        );
+    }
+}
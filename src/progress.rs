@@ -0,0 +1,180 @@
+use crate::crawl::SourceStats;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Counts {
+    fetched: usize,
+    parsed: usize,
+    submitted: usize,
+}
+
+struct Bars {
+    multi: MultiProgress,
+    sources: BTreeMap<String, (ProgressBar, Counts)>,
+}
+
+/// Only set by [`enable`], every other function here does nothing without it.
+static BARS: Lazy<Mutex<Option<Bars>>> = Lazy::new(Default::default);
+
+/// Shows a progress bar per source from now on, when stdout is a terminal; logs stay plain otherwise.
+pub fn enable() {
+    if !std::io::stdout().is_terminal() {
+        return;
+    }
+
+    *BARS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Bars {
+        multi: MultiProgress::with_draw_target(ProgressDrawTarget::stdout()),
+        sources: BTreeMap::new(),
+    });
+}
+
+/// Adds a bar for `source`, which is about to be polled.
+pub fn start(source: &str) {
+    let mut bars = BARS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(bars) = bars.as_mut() else {
+        return;
+    };
+
+    let bar = bars.multi.add(ProgressBar::new_spinner());
+    bar.set_style(
+        ProgressStyle::with_template("{spinner} {prefix:<24} {msg} {elapsed}")
+            .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+    );
+    bar.set_prefix(source.to_string());
+    bar.enable_steady_tick(Duration::from_millis(120));
+
+    let counts = Counts::default();
+    bar.set_message(message(&counts));
+    bars.sources.insert(source.to_string(), (bar, counts));
+}
+
+pub fn fetched(source: &str, count: usize) {
+    update(source, |counts| counts.fetched += count);
+}
+
+pub fn parsed(source: &str, count: usize) {
+    update(source, |counts| counts.parsed += count);
+}
+
+pub fn submitted(source: &str, count: usize) {
+    update(source, |counts| counts.submitted += count);
+}
+
+fn update(source: &str, change: impl FnOnce(&mut Counts)) {
+    let mut bars = BARS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some((bar, counts)) = bars.as_mut().and_then(|bars| bars.sources.get_mut(source)) else {
+        return;
+    };
+
+    change(counts);
+    bar.set_message(message(counts));
+}
+
+fn message(counts: &Counts) -> String {
+    format!(
+        "fetched={} parsed={} submitted={}",
+        counts.fetched, counts.parsed, counts.submitted
+    )
+}
+
+/// Runs `f`, e.g. writing a log line, with the bars hidden so they are not drawn over.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    let multi = BARS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|bars| bars.multi.clone());
+
+    match multi {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
+
+/// Removes the bars and prints a table of the run in their place.
+pub fn finish(sources: &[(String, SourceStats)]) {
+    let mut bars = BARS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(bars) = bars.as_mut() else {
+        return;
+    };
+
+    for (bar, _) in std::mem::take(&mut bars.sources).into_values() {
+        bar.finish_and_clear();
+    }
+    let _ = bars.multi.clear();
+
+    let mut out = std::io::stdout().lock();
+    let _ = write!(out, "{}", table(sources));
+    let _ = out.flush();
+}
+
+/// One row per source, with the counters of its [`SourceStats`].
+fn table(sources: &[(String, SourceStats)]) -> String {
+    let width = sources
+        .iter()
+        .map(|(source, _)| source.len())
+        .chain(["source".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!(
+        "{:<width$}  {:>7}  {:>6}  {:>4}  {:>6}  {:>6}  {:>7}\n",
+        "source", "fetched", "parsed", "new", "cached", "errors", "seconds"
+    );
+
+    for (source, stats) in sources {
+        table.push_str(&format!(
+            "{:<width$}  {:>7}  {:>6}  {:>4}  {:>6}  {:>6}  {:>7.1}\n",
+            source,
+            stats.fetched,
+            stats.parsed,
+            stats.new,
+            stats.cached,
+            stats.errors,
+            stats.duration_ms as f64 / 1000.0
+        ));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let sources = vec![
+            (
+                "discord.default".to_string(),
+                SourceStats {
+                    fetched: 25,
+                    parsed: 2,
+                    new: 1,
+                    cached: 1,
+                    duration_ms: 1500,
+                    ..Default::default()
+                },
+            ),
+            (
+                "github.default".to_string(),
+                SourceStats {
+                    errors: 1,
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        assert_eq!(
+            table(&sources),
+            "source           fetched  parsed   new  cached  errors  seconds\n\
+             discord.default       25       2     1       1       0      1.5\n\
+             github.default         0       0     0       0       1      0.0\n"
+        );
+    }
+}
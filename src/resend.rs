@@ -0,0 +1,62 @@
+use crate::config::Config;
+use crate::{archive, audit, cache, crawl, dlq};
+use std::collections::BTreeMap;
+
+/// Submits a code found before again, even though the cache says it was stored, for when the remote lost it.
+///
+/// The code is looked up in the archive, or the audit log for codes found before it existed, and submitted with
+/// the expiry in the cache if that was corrected since.
+pub async fn run(config: &Config, code: &str) {
+    let code = code.trim().to_uppercase();
+
+    let found = archive::read()
+        .into_iter()
+        .rev()
+        .find(|record| record.code == code)
+        .map(|record| (record.source.clone(), record.to_request()))
+        .or_else(|| audit::find(&code));
+
+    let Some((source, mut request)) = found else {
+        error!(
+            "'{}' is not in archive.jsonl or audit.jsonl, there is nothing to resend",
+            code
+        );
+        std::process::exit(1);
+    };
+
+    if dlq::codes().contains(&code) {
+        error!(
+            "'{}' is in the dead-letter queue, use `liccrawler dlq retry {}` instead",
+            code, code
+        );
+        std::process::exit(1);
+    }
+
+    cache::setup();
+    let mut cache = cache::read();
+
+    if let Some(entry) = cache.items.remove(&code) {
+        if entry.expires_at != 0 && entry.expires_at != request.expires_at {
+            info!(
+                "Using the corrected expiry {} of '{}' instead of {}",
+                entry.expires_at, code, request.expires_at
+            );
+            request.expires_at = entry.expires_at;
+        }
+    }
+
+    info!("Resending '{}' from {}", code, source);
+
+    let responses = crawl::submit(
+        config,
+        &mut cache,
+        BTreeMap::from([(source.as_str(), vec![request])]),
+    )
+    .await;
+    crawl::report(config, responses);
+
+    // A dry run stores nothing, keep the entry that was taken out.
+    if config.dry_run.submits() {
+        cache::write(cache).await;
+    }
+}
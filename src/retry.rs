@@ -0,0 +1,167 @@
+use crate::config::{dir, RetryConfig};
+use crate::pending::Deferred;
+use crate::storage;
+use licc::write::InsertCodeRequest;
+
+/// The longest a code waits between two attempts, however often it failed.
+const MAX_BACKOFF: u64 = 24 * 60 * 60;
+
+/// A code an output failed to store for a reason that may pass (e.g. an outage), kept in `retries.jsonl` and
+/// submitted again once its backoff is over, whether or not a source still posts it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Retry {
+    /// Failed submissions so far
+    pub attempts: u32,
+    /// Unix timestamp before which the code is not submitted again
+    pub next_at: u64,
+    #[serde(flatten)]
+    pub request: Deferred,
+}
+
+impl Retry {
+    pub fn new(source: &str, request: &InsertCodeRequest, now: u64) -> Self {
+        Self {
+            attempts: 0,
+            next_at: now,
+            request: Deferred::new(source, request),
+        }
+    }
+
+    /// Counts a failed submission and backs off, returning false once `retry.max_attempts` were made.
+    pub fn fail(&mut self, config: &RetryConfig, now: u64) -> bool {
+        self.attempts += 1;
+        self.next_at = now + backoff(config, self.attempts);
+
+        self.attempts < config.max_attempts
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        self.next_at <= now
+    }
+}
+
+/// `retry.backoff_secs`, doubled for every attempt after the first.
+fn backoff(config: &RetryConfig, attempts: u32) -> u64 {
+    config
+        .backoff_secs
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF)
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("retries.jsonl")
+}
+
+pub fn read() -> Vec<Retry> {
+    let contents = match storage::read_to_string(&file()) {
+        Ok(contents) => contents,
+        Err(_) => return vec![],
+    };
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|e| error!("Skipping unreadable line in retries.jsonl: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+pub fn write(retries: &[Retry]) {
+    if retries.is_empty() {
+        if let Err(e) = storage::remove_file(&file()) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                error!("Error removing retries.jsonl: {}", e);
+            }
+        }
+        return;
+    }
+
+    let contents: String = retries
+        .iter()
+        .filter_map(|retry| serde_json::to_string(retry).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    if let Err(e) = storage::write(&file(), contents) {
+        error!("Error writing retries.jsonl: {}", e);
+    }
+}
+
+/// Records another failed submission of the code, returning its attempts once they ran out, in which case it is no
+/// longer retried.
+pub fn failed(
+    config: &RetryConfig,
+    source: &str,
+    request: &InsertCodeRequest,
+    now: u64,
+) -> Option<u32> {
+    let mut retries = read();
+    let index = match retries.iter().position(|r| r.request.code == request.code) {
+        Some(index) => index,
+        None => {
+            retries.push(Retry::new(source, request, now));
+            retries.len() - 1
+        }
+    };
+
+    let exhausted = match retries[index].fail(config, now) {
+        true => None,
+        false => Some(retries.remove(index).attempts),
+    };
+    write(&retries);
+
+    exhausted
+}
+
+/// Forgets the code, once every output stored it or one rejected it for good.
+pub fn forget(code: &str) {
+    let mut retries = read();
+    let before = retries.len();
+    retries.retain(|r| r.request.code != code);
+
+    if retries.len() != before {
+        write(&retries);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use licc::write::SourceLookup;
+
+    #[test]
+    fn test_backoff() {
+        let config = RetryConfig {
+            max_attempts: 3,
+            backoff_secs: 300,
+        };
+        let request = InsertCodeRequest {
+            code: "ABCD-EFGH-IJKL".to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        };
+        let now = 1706000000;
+
+        let mut retry = Retry::new("discord.default", &request, now);
+        assert!(retry.is_due(now));
+
+        assert!(retry.fail(&config, now));
+        assert_eq!(retry.next_at, now + 300);
+        assert!(!retry.is_due(now + 299));
+
+        assert!(retry.fail(&config, now + 300));
+        assert_eq!(retry.next_at, now + 900);
+
+        assert!(!retry.fail(&config, now + 900));
+        assert_eq!(retry.attempts, 3);
+
+        assert_eq!(backoff(&config, 40), MAX_BACKOFF);
+    }
+}
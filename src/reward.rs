@@ -0,0 +1,87 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Emoji the official and community channels use for rewards, overridden and extended by `[rewards]`.
+const DEFAULTS: [(&str, &str); 9] = [
+    ("silverchest", "Silver Chest"),
+    ("goldchest", "Gold Chest"),
+    ("electrumchest", "Electrum Chest"),
+    ("modronchest", "Modron Chest"),
+    ("eventchest", "Event Chest"),
+    ("blacksmithcontract", "Blacksmithing Contract"),
+    ("bountycontract", "Bounty Contract"),
+    ("timegatepiece", "Time Gate Piece"),
+    ("gems", "Gems"),
+];
+
+/// `:electrumchest:` as typed, or `<:electrumchest:1234>` / `<a:electrumchest:1234>` as Discord sends custom emoji.
+static EMOJI: Lazy<Regex> = Lazy::new(|| Regex::new(r"<a?:(\w+):\d+>|:(\w+):").unwrap());
+
+/// Turns reward lines such as `1x :electrumchest:` into `1x Electrum Chest`.
+pub struct Rewards {
+    names: HashMap<String, String>,
+}
+
+impl Rewards {
+    pub fn new(configured: &HashMap<String, String>) -> Self {
+        let mut names: HashMap<String, String> = DEFAULTS
+            .iter()
+            .map(|(emoji, name)| (emoji.to_string(), name.to_string()))
+            .collect();
+
+        names.extend(
+            configured
+                .iter()
+                .map(|(emoji, name)| (emoji.to_lowercase(), name.clone())),
+        );
+
+        Self { names }
+    }
+
+    /// Replaces every emoji with its name, unknown emoji with their bare name. `None` if nothing is left.
+    pub fn describe(&self, line: &str) -> Option<String> {
+        let described = EMOJI.replace_all(line, |caps: &regex::Captures| {
+            let emoji = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+
+            self.names
+                .get(&emoji.to_lowercase())
+                .cloned()
+                .unwrap_or_else(|| emoji.to_string())
+        });
+
+        let described = described.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        match described.is_empty() {
+            true => None,
+            false => Some(described),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let rewards = Rewards::new(&HashMap::from([
+            ("goldchest".to_string(), "Gold Chests!".to_string()),
+            ("Dragon".to_string(), "Dragon Egg".to_string()),
+        ]));
+
+        assert_eq!(
+            rewards.describe("1x :electrumchest:").as_deref(),
+            Some("1x Electrum Chest")
+        );
+        assert_eq!(
+            rewards.describe("3x <:goldchest:123456789>").as_deref(),
+            Some("3x Gold Chests!")
+        );
+        assert_eq!(
+            rewards.describe("1x <a:dragon:1> 2x :mystery:").as_deref(),
+            Some("1x Dragon Egg 2x mystery")
+        );
+        assert_eq!(rewards.describe("   "), None);
+    }
+}
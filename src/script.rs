@@ -0,0 +1,254 @@
+#[cfg(feature = "scripting")]
+use crate::handler::Failure;
+use crate::handler::Handled;
+#[cfg(feature = "scripting")]
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::PathBuf;
+#[cfg(feature = "scripting")]
+use std::sync::Mutex;
+
+/// Operations a script may run per code before it is stopped, so a runaway loop cannot hang a run.
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// The compiled script per source, only set by [`configure`].
+#[cfg(feature = "scripting")]
+static SCRIPTS: Lazy<Mutex<HashMap<String, rhai::AST>>> = Lazy::new(Default::default);
+
+#[cfg(feature = "scripting")]
+fn engine() -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    engine
+}
+
+/// Compiles `[scripts]`, called whenever the config is (re)read. Scripts that fail to compile are left out.
+#[cfg(feature = "scripting")]
+pub fn configure(scripts: &HashMap<String, PathBuf>) {
+    let engine = engine();
+    let compiled = scripts
+        .iter()
+        .filter_map(|(source, path)| match engine.compile_file(path.clone()) {
+            Ok(ast) => Some((source.clone(), ast)),
+            Err(e) => {
+                error!(
+                    "Error compiling the script of {} ({}), not filtering it: {}",
+                    source,
+                    path.display(),
+                    e
+                );
+                None
+            }
+        })
+        .collect();
+
+    *SCRIPTS.lock().unwrap_or_else(|e| e.into_inner()) = compiled;
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn configure(scripts: &HashMap<String, PathBuf>) {
+    if !scripts.is_empty() {
+        error!("[scripts] are configured, but liccrawler was built without the scripting feature");
+    }
+}
+
+/// Runs the `filter` function of the script of `source` on every code it found, which returns `false` to drop the
+/// code, or a map with the `expires_at`, `creator_name` or `creator_url` to submit it with instead.
+///
+/// Besides the code, the script sees the `source`, and the `message` it was found in and its `author` (both empty
+/// when the source has none), so it can reject messages by what they say or who posted them.
+#[cfg(feature = "scripting")]
+pub fn apply(source: &str, handled: &mut Handled) {
+    let scripts = SCRIPTS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(ast) = scripts.get(source) else {
+        return;
+    };
+
+    let engine = engine();
+    let mut failures = vec![];
+
+    handled.codes.retain_mut(|request| {
+        let mut metadata = handled.metadata.get_mut(&request.code);
+        let mut code = rhai::Map::new();
+        code.insert("source".into(), source.to_string().into());
+        code.insert("code".into(), request.code.clone().into());
+        code.insert("expires_at".into(), (request.expires_at as i64).into());
+        code.insert("creator_name".into(), request.creator.name.clone().into());
+        code.insert("creator_url".into(), request.creator.url.clone().into());
+        code.insert(
+            "guessed".into(),
+            handled.guessed.contains(&request.code).into(),
+        );
+        let message = metadata.as_ref().and_then(|m| m.message.clone());
+        code.insert("message".into(), message.unwrap_or_default().into());
+        let author = request.submitter.as_ref().map(|s| s.name.clone());
+        code.insert("author".into(), author.unwrap_or_default().into());
+        if let Some(metadata) = &metadata {
+            for (key, value) in [
+                ("reward", &metadata.reward),
+                ("platform", &metadata.platform),
+            ] {
+                if let Some(value) = value {
+                    code.insert(key.into(), value.clone().into());
+                }
+            }
+        }
+
+        let verdict: rhai::Dynamic =
+            match engine.call_fn(&mut rhai::Scope::new(), ast, "filter", (code,)) {
+                Ok(verdict) => verdict,
+                Err(e) => {
+                    error!(
+                        "Error running the script of {} on '{}', keeping it: {}",
+                        source, request.code, e
+                    );
+                    return true;
+                }
+            };
+
+        if verdict.as_bool() == Ok(false) {
+            failures.push(Failure {
+                location: source.to_string(),
+                reason: "rejected by script".to_string(),
+                content: request.code.clone(),
+            });
+            return false;
+        }
+
+        let Some(changes) = verdict.try_cast::<rhai::Map>() else {
+            return true;
+        };
+        if let Some(expires_at) = changes.get("expires_at").and_then(|v| v.as_int().ok()) {
+            request.expires_at = expires_at.max(0) as u64;
+            handled.guessed.remove(&request.code);
+        }
+        let text = |key: &str| changes.get(key).and_then(|v| v.clone().into_string().ok());
+        if let Some(name) = text("creator_name") {
+            request.creator.name = name.clone();
+            if let Some(attribution) = metadata.as_mut().and_then(|m| m.attribution.as_mut()) {
+                attribution.name = name;
+            }
+        }
+        if let Some(url) = text("creator_url") {
+            request.creator.url = url.clone();
+            if let Some(attribution) = metadata.as_mut().and_then(|m| m.attribution.as_mut()) {
+                attribution.url = url;
+            }
+        }
+
+        true
+    });
+
+    handled.failures.extend(failures);
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn apply(_source: &str, _handled: &mut Handled) {}
+
+#[cfg(all(test, feature = "scripting"))]
+mod test {
+    use super::*;
+    use crate::output::Metadata;
+    use licc::write::{InsertCodeRequest, SourceLookup};
+
+    fn request(code: &str) -> InsertCodeRequest {
+        InsertCodeRequest {
+            code: code.to_string(),
+            expires_at: 1706227200,
+            creator: SourceLookup {
+                name: "foo".to_string(),
+                url: "https://www.twitch.tv/foo".to_string(),
+            },
+            submitter: None,
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let ast = engine()
+            .compile(
+                r#"
+                fn filter(code) {
+                    if code.code.starts_with("TEST") { return false; }
+                    if code.creator_name == "foo" { return #{ creator_name: "Foo", expires_at: code.expires_at + 3600 }; }
+                    true
+                }
+                "#,
+            )
+            .unwrap();
+        SCRIPTS
+            .lock()
+            .unwrap()
+            .insert("discord.script".to_string(), ast);
+
+        let mut handled = Handled {
+            codes: vec![request("TEST-AAAA-BBBB"), request("ABCD-EFGH-IJKL")],
+            ..Default::default()
+        };
+        handled.guessed.insert("ABCD-EFGH-IJKL".to_string());
+        apply("discord.script", &mut handled);
+
+        assert_eq!(handled.codes.len(), 1);
+        assert_eq!(handled.codes[0].creator.name, "Foo");
+        assert_eq!(handled.codes[0].expires_at, 1706227200 + 3600);
+        assert!(handled.guessed.is_empty());
+        assert_eq!(handled.failures[0].reason, "rejected by script");
+
+        apply("discord.other", &mut handled);
+        assert_eq!(handled.codes.len(), 1);
+    }
+
+    #[test]
+    fn test_apply_message() {
+        let ast = engine()
+            .compile(
+                r#"
+                fn filter(code) {
+                    if code.source != "discord.message" { return true; }
+                    if code.author == "spammer" { return false; }
+                    !code.message.to_lower().contains("giveaway")
+                }
+                "#,
+            )
+            .unwrap();
+        SCRIPTS
+            .lock()
+            .unwrap()
+            .insert("discord.message".to_string(), ast);
+
+        let mut spam = request("SPAM-AAAA-BBBB");
+        spam.submitter = Some(SourceLookup {
+            name: "spammer".to_string(),
+            url: "https://discord.com/users/1".to_string(),
+        });
+        let mut handled = Handled {
+            codes: vec![
+                request("ABCD-EFGH-IJKL"),
+                request("GIVE-AAAA-BBBB"),
+                spam,
+                request("NONE-AAAA-BBBB"),
+            ],
+            ..Default::default()
+        };
+        for (code, message) in [
+            ("ABCD-EFGH-IJKL", "ABCD-EFGH-IJKL\nExpires Jan 26th"),
+            ("GIVE-AAAA-BBBB", "Join our GIVEAWAY for GIVE-AAAA-BBBB"),
+            ("SPAM-AAAA-BBBB", "SPAM-AAAA-BBBB"),
+        ] {
+            handled.metadata.insert(
+                code.to_string(),
+                Metadata {
+                    message: Some(message.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+        apply("discord.message", &mut handled);
+
+        let kept: Vec<&str> = handled.codes.iter().map(|r| r.code.as_str()).collect();
+        assert_eq!(kept, vec!["ABCD-EFGH-IJKL", "NONE-AAAA-BBBB"]);
+        assert_eq!(handled.failures.len(), 2);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::cache::Cache;
+use crate::state::State;
+use crate::{cache, calendar, dlq, metrics, pending, retry, state};
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Codes listed by `/recent-codes`.
+const RECENT_CODES: usize = 50;
+
+/// A deliberately tiny HTTP server for the daemon, serving read-only state from the data directory.
+///
+/// `sources` are every configured source with whether it is enabled, see `Config::sources`, kept up to date by the
+/// daemon as the config is reloaded.
+pub async fn listen(addr: String, sources: Arc<RwLock<Vec<(String, bool)>>>) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Error listening on {}: {}", addr, e);
+            return;
+        }
+    };
+
+    info!("Serving on http://{}", addr);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(respond(stream, sources.clone()));
+            }
+            Err(e) => warn!("Error accepting connection: {}", e),
+        }
+    }
+}
+
+async fn respond(stream: TcpStream, sources: Arc<RwLock<Vec<(String, bool)>>>) {
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+
+    if stream.read_line(&mut request_line).await.is_err() {
+        return;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/calendar.ics") => ("200 OK", "text/calendar", calendar::render(&cache::peek())),
+        ("GET", "/metrics") => ("200 OK", "application/json", metrics::render()),
+        ("GET", "/sources") => (
+            "200 OK",
+            "application/json",
+            sources_json(
+                &sources.read().unwrap_or_else(|e| e.into_inner()),
+                &state::read(),
+            ),
+        ),
+        ("GET", "/recent-codes") => ("200 OK", "application/json", recent_json(&cache::peek())),
+        ("GET", "/queue") => ("200 OK", "application/json", queue_json()),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not Found".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+
+    if let Err(e) = stream.get_mut().write_all(response.as_bytes()).await {
+        debug!("Error writing response: {}", e);
+    }
+}
+
+/// Every source with whether it is enabled and paused, and when it was last polled and last yielded a code.
+fn sources_json(sources: &[(String, bool)], state: &State) -> String {
+    let sources: Vec<_> = sources
+        .iter()
+        .map(|(source, enabled)| {
+            json!({
+                "source": source,
+                "enabled": enabled,
+                "paused": state.is_paused(source),
+                "last_polled": state.last_polled.get(source),
+                "last_code": state.last_code.get(source),
+            })
+        })
+        .collect();
+
+    json!(sources).to_string()
+}
+
+/// The most recently submitted codes, newest first, with their outcome per output.
+fn recent_json(cache: &Cache) -> String {
+    let mut entries: Vec<_> = cache.items.iter().collect();
+    entries.sort_by(|a, b| b.1.submitted_at.cmp(&a.1.submitted_at).then(a.0.cmp(b.0)));
+
+    let codes: Vec<_> = entries
+        .into_iter()
+        .take(RECENT_CODES)
+        .map(|(code, entry)| {
+            json!({
+                "code": code,
+                "source": entry.source,
+                "submitted_at": entry.submitted_at,
+                "expires_at": entry.expires_at,
+                "stored": entry.is_stored(),
+                "remotes": entry.remotes,
+            })
+        })
+        .collect();
+
+    json!(codes).to_string()
+}
+
+/// Codes waiting to be submitted: left over by a run out of time, backing off after failing, or rejected for good.
+fn queue_json() -> String {
+    json!({
+        "pending": pending::read(),
+        "retries": retry::read(),
+        "dead_letters": dlq::read(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sources_json() {
+        let mut state = State::default();
+        state.paused.insert("github.default".to_string());
+        state
+            .last_polled
+            .insert("discord.default".to_string(), 1706227200);
+
+        let sources = vec![
+            ("discord.default".to_string(), true),
+            ("github.default".to_string(), true),
+        ];
+        let json: serde_json::Value =
+            serde_json::from_str(&sources_json(&sources, &state)).unwrap();
+
+        assert_eq!(json[0]["last_polled"], 1706227200);
+        assert_eq!(json[0]["paused"], false);
+        assert_eq!(json[1]["paused"], true);
+        assert!(json[1]["last_code"].is_null());
+    }
+}
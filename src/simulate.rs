@@ -0,0 +1,166 @@
+use crate::config::Config;
+#[cfg(feature = "discord")]
+use crate::handler::discord;
+use crate::handler::{exec, game_api, github, Handled};
+use crate::output::Payload;
+use crate::parse::TimeParser;
+#[cfg(feature = "discord")]
+use crate::reward::Rewards;
+use std::path::Path;
+
+/// Runs the contents of `file` through the parser of `source`, e.g. `discord.default`, as if it was posted now,
+/// and prints what would be submitted. Nothing is fetched or submitted.
+pub async fn run(config: &Config, source: &str, file: &Path) {
+    let contents = match std::fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!("Error reading {}: {}", file.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+    let handled = match parse(config, source, &contents, now).await {
+        Ok(handled) => handled,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    print(&handled);
+
+    if !handled.failures.is_empty() || handled.codes.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+async fn parse(config: &Config, source: &str, contents: &str, now: u64) -> Result<Handled, String> {
+    let unknown = || format!("'{}' is not a configured source", source);
+    let (kind, name) = source.split_once('.').ok_or_else(unknown)?;
+
+    match kind {
+        #[cfg(feature = "discord")]
+        "discord" => {
+            let cfg = config.discord.get(name).ok_or_else(unknown)?;
+            let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+            let content = match config.fuzzy_codes {
+                true => discord::normalize_first_line(contents),
+                false => contents.to_string(),
+            };
+            let submitter = licc::write::SourceLookup {
+                name: "test-parse".to_string(),
+                url: format!(
+                    "https://discord.com/channels/{}/{}",
+                    cfg.guild_id, cfg.channel_id
+                ),
+            };
+
+            let mut handled = Handled::default();
+            if let Err(reason) = discord::parse_message(
+                &content,
+                now,
+                cfg.format,
+                &timeparser,
+                &Rewards::new(&config.rewards),
+                &submitter,
+                &mut handled,
+            )
+            .await
+            {
+                handled.failures.push(crate::handler::Failure {
+                    location: source.to_string(),
+                    reason: reason.to_string(),
+                    content,
+                });
+            }
+
+            Ok(handled)
+        }
+        "github" => {
+            let cfg = config.github.get(name).ok_or_else(unknown)?;
+            let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+
+            Ok(github::parse(cfg, contents, &timeparser, now).await)
+        }
+        "game_api" => {
+            let cfg = config.game_api.get(name).ok_or_else(unknown)?;
+            let timeparser = TimeParser::from_config(&config.parser).locale(cfg.locale);
+
+            Ok(game_api::parse(cfg, contents, &timeparser, now).await)
+        }
+        "exec" => {
+            let cfg = config.exec.get(name).ok_or_else(unknown)?;
+            let timeparser = TimeParser::from_config(&config.parser);
+
+            Ok(exec::parse(cfg, contents, &timeparser, now).await)
+        }
+        _ => Err(unknown()),
+    }
+}
+
+fn print(handled: &Handled) {
+    for request in &handled.codes {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&Payload::from(request)).unwrap_or_default()
+        );
+
+        if handled.guessed.contains(&request.code) {
+            println!("  expiry not found, guessed a week from now");
+        }
+        if let Some(metadata) = handled.metadata.get(&request.code) {
+            if let Some(reward) = &metadata.reward {
+                println!("  reward: {}", reward);
+            }
+            if let Some(platform) = &metadata.platform {
+                println!("  platform: {} only", platform);
+            }
+        }
+    }
+
+    for failure in &handled.failures {
+        println!("Could not parse {}: {}", failure.location, failure.reason);
+    }
+
+    if handled.codes.is_empty() && handled.failures.is_empty() {
+        println!("No codes found.");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_parse_unknown_source() {
+        let config = Config::default();
+
+        assert!(parse(&config, "discord", "", 0).await.is_err());
+        assert!(parse(&config, "github.missing", "", 0).await.is_err());
+        assert!(parse(&config, "mastodon.default", "", 0).await.is_err());
+    }
+
+    #[cfg(feature = "discord")]
+    #[tokio::test]
+    async fn test_parse_discord() {
+        let config = Config::default();
+        let message =
+            "WXYZ-1234-EFGH\nA Streamer\nhttps://www.twitch.tv/astreamer\n1x :chest:\nExpires 1/20 11:59 PM";
+
+        let handled = parse(&config, "discord.default", message, 1705276800)
+            .await
+            .unwrap();
+        assert_eq!(handled.codes.len(), 1);
+        assert_eq!(handled.codes[0].code, "WXYZ-1234-EFGH");
+        assert_eq!(handled.codes[0].expires_at, 1705708800);
+        assert!(handled.failures.is_empty());
+
+        let handled = parse(&config, "discord.default", "no code here", 0)
+            .await
+            .unwrap();
+        assert!(handled.codes.is_empty());
+        assert_eq!(handled.failures.len(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use crate::config::Config;
+use crate::crawl::describe_time;
+use crate::state::{self, State};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, clap::Subcommand)]
+pub enum Action {
+    /// Print every source, whether it is polled, when it last was and when it last yielded a code
+    List,
+    /// Poll a source again that was disabled with `source disable`
+    Enable { name: String },
+    /// Stop polling a source until it is enabled again, without editing the config, e.g. `discord.default`
+    Disable { name: String },
+}
+
+pub fn run(config: &Config, action: Action) {
+    let sources = config.sources();
+
+    match action {
+        Action::List => {
+            let state = state::read();
+            let now = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+            for (source, enabled) in sources {
+                let status = match (enabled, state.is_paused(&source)) {
+                    (false, _) => "not enabled in config",
+                    (true, true) => "disabled",
+                    (true, false) => "enabled",
+                };
+                let when = |ts: Option<&u64>| match ts {
+                    Some(ts) => describe_time(*ts, now),
+                    None => "never".to_string(),
+                };
+
+                println!(
+                    "{}: {}, last polled {}, last code {}",
+                    source,
+                    status,
+                    when(state.last_polled.get(&source)),
+                    when(state.last_code.get(&source))
+                );
+            }
+        }
+        Action::Enable { name } | Action::Disable { name }
+            if !sources.iter().any(|(source, _)| source == &name) =>
+        {
+            error!(
+                "'{}' is not a configured source, see `liccrawler source list`",
+                name
+            );
+            std::process::exit(1);
+        }
+        Action::Enable { name } => toggle(&name, false),
+        Action::Disable { name } => toggle(&name, true),
+    }
+}
+
+/// Sources given with `--source` and whether `--only-new` was, narrowing a one-off run down.
+#[derive(Debug, Default)]
+struct Selection {
+    names: Vec<String>,
+    only_new: bool,
+}
+
+static SELECTION: Lazy<Mutex<Selection>> = Lazy::new(Default::default);
+
+/// Only polls the sources in `names` (every enabled one if empty) from now on, and with `only_new` only those not
+/// polled within `daemon.interval_secs`. Exits if a name is not a configured source.
+pub fn select(config: &Config, names: Vec<String>, only_new: bool) {
+    let sources = config.sources();
+
+    for name in &names {
+        if !sources.iter().any(|(source, _)| source == name) {
+            error!(
+                "'{}' is not a configured source, see `liccrawler source list`",
+                name
+            );
+            std::process::exit(1);
+        }
+    }
+
+    *SELECTION.lock().unwrap_or_else(|e| e.into_inner()) = Selection { names, only_new };
+}
+
+/// Whether `source` was named with `--source`, which polls it even if disabled with `source disable`.
+pub fn named(source: &str) -> bool {
+    SELECTION
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .names
+        .iter()
+        .any(|name| name == source)
+}
+
+/// Why `source` is left out of this run by `--source` or `--only-new`, if it is.
+pub fn skipped(config: &Config, state: &State, source: &str, now: u64) -> Option<String> {
+    let selection = SELECTION.lock().unwrap_or_else(|e| e.into_inner());
+
+    if !selection.names.is_empty() && !selection.names.iter().any(|name| name == source) {
+        return Some("not selected with --source".to_string());
+    }
+
+    match state.last_polled.get(source) {
+        Some(&polled)
+            if selection.only_new && now.saturating_sub(polled) < config.daemon.interval_secs =>
+        {
+            Some(format!(
+                "polled {} (--only-new)",
+                describe_time(polled, now)
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn toggle(source: &str, paused: bool) {
+    let mut state = state::read();
+    let changed = state.set_paused(source, paused);
+    state::write(&state);
+
+    match (changed, paused) {
+        (true, true) => println!("Disabled {}, it is skipped until enabled again.", source),
+        (true, false) => println!("Enabled {}.", source),
+        (false, true) => println!("{} was already disabled.", source),
+        (false, false) => println!("{} was not disabled.", source),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_skipped() {
+        let config = Config::default();
+        let mut state = State::default();
+        let now = 1705276800;
+        state
+            .last_polled
+            .insert("github.default".to_string(), now - 60);
+
+        *SELECTION.lock().unwrap() = Selection {
+            names: vec!["discord.default".to_string()],
+            only_new: false,
+        };
+        assert_eq!(skipped(&config, &state, "discord.default", now), None);
+        assert!(skipped(&config, &state, "github.default", now).is_some());
+        assert!(named("discord.default"));
+
+        *SELECTION.lock().unwrap() = Selection {
+            names: vec![],
+            only_new: true,
+        };
+        assert_eq!(skipped(&config, &state, "discord.default", now), None);
+        assert!(skipped(&config, &state, "github.default", now)
+            .unwrap()
+            .contains("--only-new"));
+        assert!(!named("discord.default"));
+
+        *SELECTION.lock().unwrap() = Selection::default();
+    }
+}
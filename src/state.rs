@@ -0,0 +1,97 @@
+use crate::config::dir;
+use crate::storage;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Operational state that outlives a single run, as opposed to configuration.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    /// Sources that are skipped until resumed, e.g. `discord.default`
+    #[serde(default)]
+    pub paused: BTreeSet<String>,
+    /// Unix timestamp of the end of the last crawl
+    pub last_run: Option<u64>,
+    /// Codes already handled per source, for sources that diff their contents between runs, e.g. `github.default`
+    #[serde(default)]
+    pub seen: BTreeMap<String, BTreeSet<String>>,
+    /// Unix timestamp a source last yielded a code, or was first watched, per source
+    #[serde(default)]
+    pub last_code: BTreeMap<String, u64>,
+    /// Sources a silence notification was sent for, until they yield a code again
+    #[serde(default)]
+    pub silent: BTreeSet<String>,
+    /// Unix timestamp parse failures were last logged at warn, by `Failure::key`
+    #[serde(default)]
+    pub failures_logged: BTreeMap<String, u64>,
+    /// Unix timestamp each source was last polled at
+    #[serde(default)]
+    pub last_polled: BTreeMap<String, u64>,
+    /// `ETag` and `Last-Modified` per source that fetches over HTTP, e.g. `github.default`, to poll conditionally
+    #[serde(default)]
+    pub validators: BTreeMap<String, crate::http::Validators>,
+    /// How much of what each source posted parsed in its recent runs
+    #[serde(default)]
+    pub parse_rates: BTreeMap<String, crate::anomaly::ParseHistory>,
+    /// Hash of the topic of each channel scanned for codes and the unix timestamp it was first seen at, by channel ID
+    #[serde(default)]
+    pub topics: BTreeMap<String, (String, u64)>,
+}
+
+fn file() -> std::path::PathBuf {
+    dir().join("state.toml")
+}
+
+pub fn read() -> State {
+    match storage::read_to_string(&file()) {
+        Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+            error!("Error reading state.toml, starting afresh: {}", e);
+            State::default()
+        }),
+        Err(_) => State::default(),
+    }
+}
+
+pub fn write(state: &State) {
+    let written = toml::to_string(state)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| storage::write(&file(), contents).map_err(|e| e.to_string()));
+
+    match written {
+        Ok(()) => debug!("State written to disk"),
+        Err(e) => error!("Error writing state.toml: {}", e),
+    }
+}
+
+impl State {
+    pub fn is_paused(&self, source: &str) -> bool {
+        self.paused.contains(source)
+    }
+
+    /// Pauses or resumes `source`, returning whether that changed anything.
+    pub fn set_paused(&mut self, source: &str, paused: bool) -> bool {
+        match paused {
+            true => self.paused.insert(source.to_string()),
+            false => self.paused.remove(source),
+        }
+    }
+
+    /// Records that `source` yielded a code at `now`.
+    pub fn heard(&mut self, source: &str, now: u64) {
+        self.last_code.insert(source.to_string(), now);
+        self.silent.remove(source);
+    }
+
+    /// Records `topic` as the topic of `channel`, returning when it was first seen: `now` unless it is unchanged.
+    pub fn topic_seen(&mut self, channel: u64, topic: &str, now: u64) -> u64 {
+        let hash = format!("{:016x}", crate::audit::fnv1a(topic.as_bytes()));
+        let seen = self
+            .topics
+            .entry(channel.to_string())
+            .or_insert_with(|| (hash.clone(), now));
+
+        if seen.0 != hash {
+            *seen = (hash, now);
+        }
+
+        seen.1
+    }
+}
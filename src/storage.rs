@@ -0,0 +1,111 @@
+//! Reading and writing the data files in [`config::dir`](crate::config::dir), e.g. `state.toml` and the queues.
+//!
+//! With `--memory-cache` they are kept in memory like the cache, so nothing is written to disk and every file starts
+//! out missing.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The data files by path while they are kept in memory, see [`keep_in_memory`].
+static MEMORY: Lazy<Mutex<Option<HashMap<PathBuf, String>>>> = Lazy::new(Default::default);
+
+/// Never touches the data files on disk, they last as long as the process, see `--memory-cache`.
+pub fn keep_in_memory() {
+    MEMORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new);
+}
+
+/// Goes back to reading and writing the data files on disk, forgetting those kept in memory.
+pub fn keep_on_disk() {
+    *MEMORY.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub fn in_memory() -> bool {
+    MEMORY.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+fn not_found(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} is not in memory", path.display()),
+    )
+}
+
+/// [`std::fs::read_to_string`]
+pub fn read_to_string(path: &Path) -> std::io::Result<String> {
+    match MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(files) => files.get(path).cloned().ok_or_else(|| not_found(path)),
+        None => std::fs::read_to_string(path),
+    }
+}
+
+/// [`std::fs::write`]
+pub fn write(path: &Path, contents: impl AsRef<str>) -> std::io::Result<()> {
+    match MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        Some(files) => {
+            files.insert(path.to_path_buf(), contents.as_ref().to_string());
+            Ok(())
+        }
+        None => std::fs::write(path, contents.as_ref()),
+    }
+}
+
+/// Appends `contents` to the file, creating it if it does not exist yet.
+pub fn append(path: &Path, contents: &str) -> std::io::Result<()> {
+    match MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        Some(files) => {
+            files
+                .entry(path.to_path_buf())
+                .or_default()
+                .push_str(contents);
+            Ok(())
+        }
+        None => std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(contents.as_bytes())),
+    }
+}
+
+/// [`std::fs::remove_file`]
+pub fn remove_file(path: &Path) -> std::io::Result<()> {
+    match MEMORY.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        Some(files) => files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path)),
+        None => std::fs::remove_file(path),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory() {
+        keep_in_memory();
+        let path = Path::new("/nonexistent/liccrawler/test.jsonl");
+
+        assert_eq!(
+            read_to_string(path).unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        append(path, "a\n").unwrap();
+        append(path, "b\n").unwrap();
+        assert_eq!(read_to_string(path).unwrap(), "a\nb\n");
+        write(path, "c\n").unwrap();
+        assert_eq!(read_to_string(path).unwrap(), "c\n");
+        remove_file(path).unwrap();
+        assert!(read_to_string(path).is_err());
+
+        keep_on_disk();
+        assert!(!in_memory());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::config::{Config, TelemetryConfig};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Span kinds as OTLP numbers them.
+const KIND_INTERNAL: u8 = 1;
+const KIND_CLIENT: u8 = 3;
+
+/// The OTLP status of a span that failed.
+const STATUS_ERROR: u8 = 2;
+
+/// A unit of work in a run, exported when [`enable`] was called with an endpoint and dropped otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    trace_id: String,
+    span_id: String,
+    parent_id: Option<String>,
+    name: String,
+    kind: u8,
+    start_ns: u128,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Finished {
+    span: Span,
+    end_ns: u128,
+}
+
+/// The trace of one run: a root span, the span of each source polled and every span finished so far.
+struct Trace {
+    run: Span,
+    sources: HashMap<String, Span>,
+    finished: Vec<Finished>,
+}
+
+/// Only set by [`enable`], spans are not recorded without it.
+static TRACE: Lazy<Mutex<Option<Trace>>> = Lazy::new(Default::default);
+
+static IDS: AtomicU64 = AtomicU64::new(0);
+
+impl Span {
+    fn new(trace_id: String, parent_id: Option<String>, name: &str, kind: u8) -> Self {
+        Self {
+            trace_id,
+            span_id: format!("{:016x}", id()),
+            parent_id,
+            name: name.to_string(),
+            kind,
+            start_ns: now_ns(),
+            attributes: vec![],
+            error: None,
+        }
+    }
+
+    fn root(name: &str) -> Self {
+        Self::new(
+            format!("{:016x}{:016x}", id(), id()),
+            None,
+            name,
+            KIND_INTERNAL,
+        )
+    }
+
+    /// A span for work done as part of this one.
+    pub fn child(&self, name: &str) -> Span {
+        Span::new(
+            self.trace_id.clone(),
+            Some(self.span_id.clone()),
+            name,
+            KIND_INTERNAL,
+        )
+    }
+
+    /// A span for a call to another service, which should continue the trace from [`traceparent`](Span::traceparent).
+    pub fn client(&self, name: &str) -> Span {
+        Span {
+            kind: KIND_CLIENT,
+            ..self.child(name)
+        }
+    }
+
+    pub fn attribute(&mut self, key: &str, value: impl ToString) {
+        self.attributes.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn fail(&mut self, reason: impl ToString) {
+        self.error = Some(reason.to_string());
+    }
+
+    /// The W3C trace context header that makes a downstream service's spans children of this one.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_id, self.span_id)
+    }
+
+    pub fn end(self) {
+        let mut trace = TRACE.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(trace) = trace.as_mut() {
+            trace.finished.push(Finished {
+                span: self,
+                end_ns: now_ns(),
+            });
+        }
+    }
+}
+
+fn now_ns() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Not cryptographically random, only needs to be unlikely to collide; never zero, which OTLP treats as unset.
+fn id() -> u64 {
+    let n = IDS.fetch_add(1, Ordering::Relaxed);
+
+    crate::audit::fnv1a(format!("{}\n{}\n{}", now_ns(), std::process::id(), n).as_bytes()).max(1)
+}
+
+/// Starts the trace of a run, if `telemetry.endpoint` is set; [`flush`] exports it.
+pub fn enable(cfg: &TelemetryConfig) {
+    let mut trace = TRACE.lock().unwrap_or_else(|e| e.into_inner());
+
+    *trace = cfg.endpoint.as_ref().map(|_| Trace {
+        run: Span::root("crawl"),
+        sources: HashMap::new(),
+        finished: vec![],
+    });
+}
+
+/// Whether spans are recorded, i.e. the trace context is worth passing on to other services.
+pub fn enabled() -> bool {
+    TRACE.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// The trace context of the run, for clients that can only pass one on for all their requests rather than per request.
+pub fn run_traceparent() -> Option<String> {
+    TRACE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|trace| trace.run.traceparent())
+}
+
+/// The span of polling `source`, fetching and parsing; spans of the codes it found are its children.
+pub fn source(source: &str) -> Span {
+    let mut trace = TRACE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(trace) = trace.as_mut() else {
+        return Span::root(source);
+    };
+
+    let mut span = trace.run.child(&format!("poll {}", source));
+    span.attribute("liccrawler.source", source);
+    trace.sources.insert(source.to_string(), span.clone());
+
+    span
+}
+
+/// The span of submitting `code` found by `from`, a child of the span of polling `from` if it was polled this run.
+pub fn code(from: &str, code: &str) -> Span {
+    let trace = TRACE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(trace) = trace.as_ref() else {
+        return Span::root(code);
+    };
+
+    let mut span = trace.sources.get(from).unwrap_or(&trace.run).child("code");
+    span.attribute("liccrawler.code", code);
+    span.attribute("liccrawler.source", from);
+
+    span
+}
+
+/// Ends the trace of the run and sends it to `telemetry.endpoint`, failures are only logged.
+pub async fn flush(config: &Config) {
+    let Some(trace) = TRACE.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let Some(endpoint) = &config.telemetry.endpoint else {
+        return;
+    };
+
+    let mut finished = trace.finished;
+    finished.push(Finished {
+        span: trace.run,
+        end_ns: now_ns(),
+    });
+
+    let mut request = crate::http::client(&config.http)
+        .post(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .json(&payload(&config.telemetry.service_name, &finished));
+    for (name, value) in &config.telemetry.headers {
+        request = request.header(name, value);
+    }
+
+    match request.send().await.and_then(|r| r.error_for_status()) {
+        Ok(_) => debug!("Exported {} spans to {}", finished.len(), endpoint),
+        Err(e) => warn!("Error exporting traces to {}: {}", endpoint, e),
+    }
+}
+
+/// An OTLP/HTTP JSON export request of `spans`.
+fn payload(service_name: &str, spans: &[Finished]) -> Value {
+    let attributes = |attributes: &[(String, String)]| -> Vec<Value> {
+        attributes
+            .iter()
+            .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+            .collect()
+    };
+
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|Finished { span, end_ns }| {
+            let mut value = json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "parentSpanId": span.parent_id.clone().unwrap_or_default(),
+                "name": span.name,
+                "kind": span.kind,
+                "startTimeUnixNano": span.start_ns.to_string(),
+                "endTimeUnixNano": end_ns.to_string(),
+                "attributes": attributes(&span.attributes),
+            });
+            if let Some(reason) = &span.error {
+                value["status"] = json!({"code": STATUS_ERROR, "message": reason});
+            }
+
+            value
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": attributes(&[("service.name".to_string(), service_name.to_string())]),
+            },
+            "scopeSpans": [{
+                "scope": {"name": "liccrawler", "version": env!("CARGO_PKG_VERSION")},
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_traceparent() {
+        let root = Span::root("crawl");
+        let child = root.child("code");
+        let traceparent = child.traceparent();
+
+        assert_eq!(traceparent.len(), 55);
+        assert!(traceparent.starts_with(&format!("00-{}-", root.trace_id)));
+        assert!(traceparent.ends_with(&format!("-{}-01", child.span_id)));
+        assert_eq!(child.parent_id.as_ref(), Some(&root.span_id));
+        assert_ne!(child.span_id, root.span_id);
+    }
+
+    #[test]
+    fn test_payload() {
+        let root = Span::root("crawl");
+        let mut insert = root.client("insert licc");
+        insert.attribute("liccrawler.code", "ABCD-EFGH-IJKL");
+        insert.fail("503 Service Unavailable");
+
+        let payload = payload(
+            "liccrawler",
+            &[Finished {
+                span: insert.clone(),
+                end_ns: insert.start_ns + 1000,
+            }],
+        );
+        let span = &payload["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+
+        assert_eq!(
+            payload["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "liccrawler"
+        );
+        assert_eq!(span["traceId"], root.trace_id);
+        assert_eq!(span["parentSpanId"], root.span_id);
+        assert_eq!(span["kind"], KIND_CLIENT);
+        assert_eq!(span["attributes"][0]["key"], "liccrawler.code");
+        assert_eq!(span["status"]["code"], STATUS_ERROR);
+    }
+}
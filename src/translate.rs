@@ -0,0 +1,520 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// How long the translation command may take, parsing the message waits for it.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the command is skipped after it timed out, so a hung translator costs one timeout and not one per message.
+const COMMAND_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// A language expiries are posted in, with just enough vocabulary to recognise and translate them.
+pub struct Language {
+    pub name: &'static str,
+    months: [&'static str; 12],
+    /// Phrases and their English equivalent as `TimeParser` understands it
+    phrases: &'static [(&'static str, &'static str)],
+}
+
+pub static LANGUAGES: [Language; 6] = [
+    Language {
+        name: "german",
+        months: [
+            "januar",
+            "februar",
+            "märz",
+            "april",
+            "mai",
+            "juni",
+            "juli",
+            "august",
+            "september",
+            "oktober",
+            "november",
+            "dezember",
+        ],
+        phrases: &[
+            ("gültig bis", "expires"),
+            ("läuft ab", "expires"),
+            ("nächste woche", "next week"),
+            ("ende des monats", "end of the month"),
+            ("ende des tages", "end of day"),
+            ("solange der vorrat reicht", "while supplies last"),
+        ],
+    },
+    Language {
+        name: "french",
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        phrases: &[
+            ("valable jusqu'au", "expires"),
+            ("expire le", "expires"),
+            ("la semaine prochaine", "next week"),
+            ("fin du mois", "end of the month"),
+            ("fin de journée", "end of day"),
+            ("jusqu'à épuisement des stocks", "while supplies last"),
+        ],
+    },
+    Language {
+        name: "spanish",
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        phrases: &[
+            ("válido hasta el", "expires"),
+            ("válido hasta", "expires"),
+            ("vence el", "expires"),
+            ("la próxima semana", "next week"),
+            ("fin de mes", "end of the month"),
+            ("hasta agotar existencias", "while supplies last"),
+        ],
+    },
+    Language {
+        name: "portuguese",
+        months: [
+            "janeiro",
+            "fevereiro",
+            "março",
+            "abril",
+            "maio",
+            "junho",
+            "julho",
+            "agosto",
+            "setembro",
+            "outubro",
+            "novembro",
+            "dezembro",
+        ],
+        phrases: &[
+            ("válido até", "expires"),
+            ("expira em", "expires"),
+            ("semana que vem", "next week"),
+            ("fim do mês", "end of the month"),
+            ("enquanto durarem os estoques", "while supplies last"),
+        ],
+    },
+    Language {
+        name: "italian",
+        months: [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        phrases: &[
+            ("valido fino al", "expires"),
+            ("scade il", "expires"),
+            ("la prossima settimana", "next week"),
+            ("fine del mese", "end of the month"),
+            ("fino a esaurimento scorte", "while supplies last"),
+        ],
+    },
+    Language {
+        name: "dutch",
+        months: [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        phrases: &[
+            ("geldig tot", "expires"),
+            ("verloopt op", "expires"),
+            ("volgende week", "next week"),
+            ("eind van de maand", "end of the month"),
+            ("op = op", "while supplies last"),
+        ],
+    },
+];
+
+/// `26. january 2024`, `26 de enero de 2024`, `1er janvier`, the day before the month as most of Europe writes it.
+static DAY_MONTH: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"\b(\d{{1,2}})(?:\.|er|º)?\s+(?:de\s+)?({})\b(?:\s+(?:de\s+)?(\d{{4}}))?",
+        MONTHS.join("|")
+    ))
+    .unwrap()
+});
+
+/// The language called `name` in `LANGUAGES`, e.g. `german`.
+pub fn language(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.name == name)
+}
+
+impl Language {
+    /// The number (1-12) of `month` in this language, written out or shortened to its first three letters or more,
+    /// with umlauts spelled out ("maerz") or not.
+    pub fn month_number(&self, month: &str) -> Option<u8> {
+        let month = month
+            .to_lowercase()
+            .replace("ae", "ä")
+            .replace("oe", "ö")
+            .replace("ue", "ü");
+        if month.chars().count() < 3 {
+            return None;
+        }
+
+        self.months
+            .iter()
+            .position(|name| name.starts_with(&month))
+            .map(|i| i as u8 + 1)
+    }
+
+    /// Words and phrases of this language found in `text`, skipping those spelled the same in English.
+    fn hits(&self, text: &str) -> usize {
+        let months = self
+            .months
+            .iter()
+            .zip(MONTHS)
+            .filter(|(month, english)| *month != english)
+            .map(|(month, _)| *month);
+
+        self.phrases
+            .iter()
+            .map(|(phrase, _)| *phrase)
+            .chain(months)
+            .filter(|word| contains_word(text, word))
+            .count()
+    }
+
+    /// Replaces the phrases and month names this language is known to use with English ones.
+    fn to_english(&self, text: &str) -> String {
+        let mut text = text.to_string();
+
+        for (phrase, english) in self.phrases {
+            text = replace_word(&text, phrase, english);
+        }
+
+        for (month, english) in self.months.iter().zip(MONTHS) {
+            text = replace_word(&text, month, english);
+        }
+
+        DAY_MONTH
+            .replace_all(&text, |caps: &regex::Captures| match caps.get(3) {
+                Some(year) => format!("{} {}, {}", &caps[2], &caps[1], year.as_str()),
+                None => format!("{} {}", &caps[2], &caps[1]),
+            })
+            .to_string()
+    }
+}
+
+/// The language of a lowercase `text`, if it is one of `LANGUAGES` rather than English.
+pub fn detect(text: &str) -> Option<&'static Language> {
+    if contains_word(text, "expires") || contains_word(text, "expiry") {
+        return None;
+    }
+
+    LANGUAGES
+        .iter()
+        .map(|language| (language.hits(text), language))
+        .filter(|(hits, _)| *hits > 0)
+        .max_by_key(|(hits, _)| *hits)
+        .map(|(_, language)| language)
+}
+
+/// Translates expiries posted in other languages to English before they are parsed.
+///
+/// `command` is run with the text on stdin and should print its English translation within `COMMAND_TIMEOUT`,
+/// the built-in vocabulary of `LANGUAGES` is used if it is empty, fails or is too slow.
+#[derive(Debug, Default)]
+pub struct Translator {
+    pub command: Vec<String>,
+    /// Until when the command is skipped, after it timed out
+    skip_until: Mutex<Option<Instant>>,
+}
+
+impl Translator {
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            command,
+            skip_until: Mutex::new(None),
+        }
+    }
+
+    /// The English translation of a lowercase `text`, or `None` if it does not need one.
+    pub async fn translate(&self, text: &str) -> Option<String> {
+        let language = detect(text)?;
+
+        debug!("Translating expiry from {}: {}", language.name, text);
+
+        if let Some(translated) = self.run_command(text).await {
+            return Some(translated.to_lowercase());
+        }
+
+        Some(language.to_english(text))
+    }
+
+    async fn run_command(&self, text: &str) -> Option<String> {
+        let (program, args) = self.command.split_first()?;
+
+        // Not held across the wait below, the guard goes with this statement.
+        let skipped = self
+            .skip_until
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some_and(|until| Instant::now() < until);
+        if skipped {
+            return None;
+        }
+
+        // Killed when dropped, which is what happens to it once it takes too long.
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                error!("Error running translation command: {}", e);
+                return None;
+            }
+        };
+
+        let run = async {
+            // Dropping stdin closes it, the command reads until then.
+            if let Some(mut stdin) = child.stdin.take() {
+                if let Err(e) = stdin.write_all(text.as_bytes()).await {
+                    debug!("Error writing to translation command: {}", e);
+                }
+            }
+
+            child.wait_with_output().await
+        };
+
+        let output = match tokio::time::timeout(COMMAND_TIMEOUT, run).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(e)) => {
+                error!("Error waiting for translation command: {}", e);
+                return None;
+            }
+            Err(_) => {
+                error!(
+                    "Translation command took longer than {}s, using the built-in vocabulary for {}m",
+                    COMMAND_TIMEOUT.as_secs(),
+                    COMMAND_COOLDOWN.as_secs() / 60
+                );
+                *self.skip_until.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(Instant::now() + COMMAND_COOLDOWN);
+                return None;
+            }
+        };
+
+        if !output.status.success() {
+            error!("Translation command exited with {}", output.status);
+            return None;
+        }
+
+        match String::from_utf8(output.stdout) {
+            Ok(output) => Some(output.trim().to_string()),
+            Err(e) => {
+                error!("Error reading translation command output: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Every phrase and month of `LANGUAGES` as a whole-word pattern, compiled once as every message is checked.
+static WORDS: Lazy<HashMap<&'static str, Regex>> = Lazy::new(|| {
+    LANGUAGES
+        .iter()
+        .flat_map(|language| {
+            language
+                .phrases
+                .iter()
+                .map(|(phrase, _)| *phrase)
+                .chain(language.months)
+        })
+        .chain(["expires", "expiry"])
+        .filter_map(|word| word_pattern(word).map(|re| (word, re)))
+        .collect()
+});
+
+fn word_pattern(word: &str) -> Option<Regex> {
+    Regex::new(&format!(r"\b{}\b", regex::escape(word))).ok()
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    match WORDS.get(word) {
+        Some(re) => re.is_match(text),
+        None => word_pattern(word).is_some_and(|re| re.is_match(text)),
+    }
+}
+
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    match WORDS.get(word).cloned().or_else(|| word_pattern(word)) {
+        Some(re) => re.replace_all(text, replacement).to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect("gültig bis 26. januar").map(|l| l.name),
+            Some("german")
+        );
+        assert_eq!(
+            detect("valable jusqu'au 1er février").map(|l| l.name),
+            Some("french")
+        );
+        assert_eq!(
+            detect("válido hasta el 26 de enero").map(|l| l.name),
+            Some("spanish")
+        );
+        assert!(detect("expires jan 26th").is_none());
+        assert!(detect("april 5th").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_timeout() {
+        let translator = Translator::new(vec!["sleep".to_string(), "30".to_string()]);
+        let started = Instant::now();
+
+        assert_eq!(
+            translator
+                .translate("gültig bis 26. januar")
+                .await
+                .as_deref(),
+            Some("expires january 26")
+        );
+        assert!(started.elapsed() < COMMAND_TIMEOUT + Duration::from_secs(5));
+
+        // skipped while cooling down
+        let started = Instant::now();
+        assert!(translator
+            .run_command("gültig bis 26. januar")
+            .await
+            .is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command() {
+        let translator = Translator::new(vec!["echo".to_string(), "Expires Jan 26".to_string()]);
+
+        assert_eq!(
+            translator
+                .translate("gültig bis 26. januar")
+                .await
+                .as_deref(),
+            Some("expires jan 26")
+        );
+    }
+
+    #[test]
+    fn test_month_number() {
+        let german = language("german").unwrap();
+
+        assert_eq!(german.month_number("januar"), Some(1));
+        assert_eq!(german.month_number("März"), Some(3));
+        assert_eq!(german.month_number("maerz"), Some(3));
+        assert_eq!(german.month_number("mär"), Some(3));
+        assert_eq!(german.month_number("okt"), Some(10));
+        assert_eq!(german.month_number("dez"), Some(12));
+        assert_eq!(german.month_number("ju"), None);
+        assert_eq!(german.month_number("january"), None);
+    }
+
+    #[tokio::test]
+    async fn test_translate() {
+        let translator = Translator::default();
+
+        assert_eq!(
+            translator
+                .translate("gültig bis 26. januar 2024")
+                .await
+                .as_deref(),
+            Some("expires january 26, 2024")
+        );
+        assert_eq!(
+            translator
+                .translate("expire le 1er février")
+                .await
+                .as_deref(),
+            Some("expires february 1")
+        );
+        assert_eq!(
+            translator
+                .translate("válido hasta el 26 de enero de 2024")
+                .await
+                .as_deref(),
+            Some("expires january 26, 2024")
+        );
+        assert_eq!(
+            translator
+                .translate("geldig tot volgende week")
+                .await
+                .as_deref(),
+            Some("expires next week")
+        );
+        assert_eq!(translator.translate("expires jan 26th").await, None);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::ValidationConfig;
+use crate::handler::{Failure, Handled};
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+/// Decides whether something that looks like a code is one, e.g. by its length or characters.
+///
+/// A checksum would be another implementation, should codes ever get one.
+pub trait CodeValidator: Send + Sync {
+    /// Why `code` is not valid, if it is not.
+    fn check(&self, code: &str) -> Result<(), String>;
+}
+
+/// Codes of one of these lengths, not counting dashes.
+pub struct Length(pub Vec<usize>);
+
+impl Default for Length {
+    /// 12 or 16 characters, as every code handed out so far.
+    fn default() -> Self {
+        Self(vec![12, 16])
+    }
+}
+
+impl CodeValidator for Length {
+    fn check(&self, code: &str) -> Result<(), String> {
+        let length = code.replace('-', "").chars().count();
+
+        match self.0.contains(&length) {
+            true => Ok(()),
+            false => Err(format!("'{}' has {} characters", code, length)),
+        }
+    }
+}
+
+/// Codes of only letters and digits, rejecting the symbols (`!@#$%^&*`) some codes contain.
+pub struct Alphanumeric;
+
+impl CodeValidator for Alphanumeric {
+    fn check(&self, code: &str) -> Result<(), String> {
+        match code
+            .chars()
+            .all(|c| c == '-' || c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            true => Ok(()),
+            false => Err(format!("'{}' contains symbols", code)),
+        }
+    }
+}
+
+/// The validators that apply to a source, a code has to pass every one of them.
+pub struct Validators(Vec<Box<dyn CodeValidator>>);
+
+impl CodeValidator for Validators {
+    fn check(&self, code: &str) -> Result<(), String> {
+        self.0
+            .iter()
+            .try_for_each(|validator| validator.check(code))
+    }
+}
+
+/// Only set by [`configure`], the defaults apply without it.
+static RULES: Lazy<Mutex<Option<ValidationConfig>>> = Lazy::new(Default::default);
+
+/// Applies `[validation]`, called whenever the config is (re)read.
+pub fn configure(cfg: &ValidationConfig) {
+    *RULES.lock().unwrap_or_else(|e| e.into_inner()) = Some(cfg.clone());
+}
+
+/// The validators configured for `source`, e.g. `discord.default`: `[validation]` with its overrides.
+pub fn for_source(source: &str) -> Validators {
+    let rules = RULES.lock().unwrap_or_else(|e| e.into_inner());
+    let default = ValidationConfig::default();
+    let cfg = rules.as_ref().unwrap_or(&default);
+    let source = cfg.sources.get(source);
+
+    let lengths = source
+        .and_then(|source| source.lengths.clone())
+        .unwrap_or_else(|| cfg.lengths.clone());
+    let alphanumeric = source
+        .and_then(|source| source.alphanumeric)
+        .unwrap_or(cfg.alphanumeric);
+
+    let mut validators: Vec<Box<dyn CodeValidator>> = vec![Box::new(Length(lengths))];
+    if alphanumeric {
+        validators.push(Box::new(Alphanumeric));
+    }
+
+    Validators(validators)
+}
+
+/// Whether `code` is valid for any source, which is as much as parsers can tell without knowing the source;
+/// [`retain`] applies the validators of the source afterwards.
+pub fn any_source(code: &str) -> bool {
+    let rules = RULES.lock().unwrap_or_else(|e| e.into_inner());
+    let lengths: Vec<usize> = match rules.as_ref() {
+        Some(cfg) => cfg
+            .sources
+            .values()
+            .filter_map(|source| source.lengths.clone())
+            .flatten()
+            .chain(cfg.lengths.iter().copied())
+            .collect(),
+        None => Length::default().0,
+    };
+
+    Length(lengths).check(code).is_ok()
+}
+
+/// Drops the codes `source` found that its validators reject, as failures.
+pub fn retain(source: &str, handled: &mut Handled) {
+    let validators = for_source(source);
+
+    handled
+        .codes
+        .retain(|request| match validators.check(&request.code) {
+            Ok(()) => true,
+            Err(reason) => {
+                handled.failures.push(Failure {
+                    location: source.to_string(),
+                    reason: format!("invalid code, {}", reason),
+                    content: request.code.clone(),
+                });
+                false
+            }
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_length() {
+        let length = Length::default();
+
+        assert!(length.check("1234-5678-1234-5678").is_ok());
+        assert!(length.check("ABCDEFGHIJKL").is_ok());
+        assert!(length.check("1234-5678-1234-567").is_err());
+        assert!(length.check("ABCD-EFGH-IJKL-MNOP-QRST").is_err());
+        assert!(Length(vec![20]).check("ABCD-EFGH-IJKL-MNOP-QRST").is_ok());
+    }
+
+    #[test]
+    fn test_validators() {
+        let validators = Validators(vec![Box::new(Length::default()), Box::new(Alphanumeric)]);
+
+        assert!(validators.check("ABCD-EFGH-IJKL").is_ok());
+        assert_eq!(
+            validators.check("S#PR-1SE!-C0DE"),
+            Err("'S#PR-1SE!-C0DE' contains symbols".to_string())
+        );
+        assert_eq!(
+            validators.check("ABCD-EFGH"),
+            Err("'ABCD-EFGH' has 8 characters".to_string())
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::config::Config;
+use crate::{http, notify};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const LATEST_RELEASE: &str =
+    "https://api.github.com/repos/Liefland/idle_champions_codes_crawler/releases/latest";
+
+#[derive(Debug, serde::Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+}
+
+/// Prints the running version and, with `check`, notifies if a newer release is available.
+///
+/// Message formats change, and the fix only reaches deployments that update.
+pub async fn run(config: &Config, check: bool) {
+    println!("liccrawler {}", VERSION);
+
+    if !check {
+        return;
+    }
+
+    let release = http::client(&config.http)
+        .get(LATEST_RELEASE)
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+    let release: Release = match release {
+        Ok(response) => match response.json().await {
+            Ok(release) => release,
+            Err(e) => {
+                error!("Error reading the latest release: {}", e);
+                return;
+            }
+        },
+        Err(e) => {
+            error!("Error fetching the latest release: {}", e);
+            return;
+        }
+    };
+
+    match is_newer(&release.tag_name, VERSION) {
+        true => {
+            notify::send(
+                config,
+                &format!(
+                    "liccrawler {} is available, running {}: {}",
+                    release.tag_name.trim_start_matches('v'),
+                    VERSION,
+                    release.html_url
+                ),
+            )
+            .await
+        }
+        false => info!("liccrawler {} is the latest release", VERSION),
+    }
+}
+
+/// Compares `major.minor.patch`, ignoring a leading `v` and anything after a `-` or `+`.
+fn is_newer(latest: &str, running: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|n| n.parse().unwrap_or(0))
+            .collect()
+    }
+
+    parse(latest) > parse(running)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("v0.3.0", "0.2.9"));
+        assert!(is_newer("1.0.0", "0.10.0"));
+        assert!(is_newer("v0.2.10", "0.2.9"));
+        assert!(!is_newer("v0.2.9", "0.2.9"));
+        assert!(!is_newer("0.2.8", "0.2.9"));
+        assert!(!is_newer("v0.2.9-rc.1", "0.2.9"));
+    }
+}
@@ -0,0 +1,249 @@
+#![cfg(feature = "discord")]
+
+use liccrawler::cache::{self, Cache};
+use liccrawler::config::{self, ClientConfig, Config, DiscordConfig, DryRun};
+use liccrawler::crawl;
+use liccrawler::parse::{next_week, week_after};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::{Mutex, MutexGuard};
+use wiremock::http::Method;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const GUILD_ID: u64 = 100;
+const CHANNEL_ID: u64 = 200;
+// 2024-01-15, the timestamp of every message
+const MESSAGE_TS: u64 = 1705276800;
+
+/// The data directory is process wide, so tests that crawl take turns.
+static DATA_DIR: Mutex<()> = Mutex::const_new(());
+
+/// An empty data directory of its own for the duration of a test.
+struct DataDir {
+    _dir: tempfile::TempDir,
+    _turn: MutexGuard<'static, ()>,
+}
+
+async fn data_dir() -> DataDir {
+    let turn = DATA_DIR.lock().await;
+    let dir = tempfile::tempdir().unwrap();
+    config::set_dir(dir.path().to_path_buf());
+
+    DataDir {
+        _dir: dir,
+        _turn: turn,
+    }
+}
+
+fn user(id: &str, name: &str) -> Value {
+    json!({
+        "id": id,
+        "username": name,
+        "global_name": name,
+        "discriminator": "0",
+        "avatar": null,
+        "bot": false,
+    })
+}
+
+fn message(id: &str, content: &str) -> Value {
+    json!({
+        "id": id,
+        "channel_id": CHANNEL_ID.to_string(),
+        "guild_id": GUILD_ID.to_string(),
+        "author": user("300", "submitter"),
+        "content": content,
+        "timestamp": "2024-01-15T00:00:00.000000+00:00",
+        "edited_timestamp": null,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "reactions": [],
+        "pinned": false,
+        "type": 0,
+    })
+}
+
+async fn discord_server(messages: Vec<Value>) -> MockServer {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/v10/users/@me"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(user("1", "liccrawler")))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/api/v10/channels/{CHANNEL_ID}/messages")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(Value::Array(messages)))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+async fn licc_server() -> MockServer {
+    let server = MockServer::start().await;
+
+    Mock::given(method("PUT"))
+        .and(path("/codes"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("1"))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn config(discord: &MockServer, licc: &MockServer, dry_run: DryRun) -> Config {
+    let mut sources = HashMap::new();
+    sources.insert(
+        "default".to_string(),
+        DiscordConfig {
+            enabled: true,
+            bot_token: "Bot test".to_string(),
+            guild_id: GUILD_ID,
+            channel_id: CHANNEL_ID,
+            api_base: Some(discord.uri()),
+            ..Default::default()
+        },
+    );
+
+    Config {
+        dry_run,
+        submit_expired: true,
+        client: ClientConfig {
+            remote_host: Some(licc.uri()),
+            api_key: "test".to_string(),
+            ..Default::default()
+        },
+        discord: sources,
+        ..Default::default()
+    }
+}
+
+fn empty_cache() -> Cache {
+    cache::init();
+
+    Cache::default()
+}
+
+async fn inserts(licc: &MockServer) -> Vec<Value> {
+    licc.received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.method == Method::Put && r.url.path() == "/codes")
+        .map(|r| serde_json::from_slice(&r.body).unwrap())
+        .collect()
+}
+
+async fn reactions(discord: &MockServer) -> Vec<String> {
+    discord
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.method == Method::Put && r.url.path().contains("/reactions/"))
+        .map(|r| r.url.path().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_crawl_submits_parsed_codes() {
+    let _data_dir = data_dir().await;
+    let discord = discord_server(vec![
+        message(
+            "400",
+            "CODE-AAAA-BBBB-CCCC\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week",
+        ),
+        message("401", "not a code at all"),
+    ])
+    .await;
+    let licc = licc_server().await;
+    let mut cache = empty_cache();
+
+    crawl::run(&config(&discord, &licc, DryRun::Off), &mut cache).await;
+
+    assert_eq!(
+        inserts(&licc).await,
+        vec![json!({
+            "code": "CODE-AAAA-BBBB-CCCC",
+            "expires_at": week_after(MESSAGE_TS),
+            "creator_name": "foo",
+            "creator_url": "https://www.twitch.tv/foo",
+            "submitter_name": "submitter",
+            "submitter_url": format!("https://discord.com/channels/{GUILD_ID}/{CHANNEL_ID}/400"),
+        })]
+    );
+    assert!(cache.has("CODE-AAAA-BBBB-CCCC"));
+}
+
+#[tokio::test]
+async fn test_crawl_skips_cached_codes() {
+    let _data_dir = data_dir().await;
+    let discord = discord_server(vec![message(
+        "400",
+        "CODE-AAAA-BBBB-CCCC\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week",
+    )])
+    .await;
+    let licc = licc_server().await;
+    let mut cache = empty_cache();
+    cache.insert(
+        "CODE-AAAA-BBBB-CCCC".to_string(),
+        "discord",
+        next_week(),
+        None,
+    );
+
+    crawl::run(&config(&discord, &licc, DryRun::Off), &mut cache).await;
+
+    assert!(inserts(&licc).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_crawl_skips_expired_codes() {
+    let _data_dir = data_dir().await;
+    let discord = discord_server(vec![message(
+        "400",
+        "CODE-AAAA-BBBB-CCCC\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week",
+    )])
+    .await;
+    let licc = licc_server().await;
+    let mut cache = empty_cache();
+
+    let mut config = config(&discord, &licc, DryRun::Off);
+    config.submit_expired = false;
+
+    crawl::run(&config, &mut cache).await;
+
+    assert!(inserts(&licc).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_crawl_dry_run_does_not_submit() {
+    let _data_dir = data_dir().await;
+    let discord = discord_server(vec![message(
+        "400",
+        "CODE-AAAA-BBBB-CCCC\nTest Input\nhttps://www.twitch.tv/foo\n1x :bar:\nExpires Next Week",
+    )])
+    .await;
+    let licc = licc_server().await;
+    let mut cache = empty_cache();
+
+    let mut config = config(&discord, &licc, DryRun::On);
+    config.discord.get_mut("default").unwrap().acknowledge = true;
+
+    let responses = crawl::run(&config, &mut cache).await;
+
+    assert!(inserts(&licc).await.is_empty());
+    assert!(reactions(&discord).await.is_empty());
+    assert_eq!(
+        responses.get("CODE-AAAA-BBBB-CCCC").map(|r| r.id),
+        Some(None)
+    );
+    assert!(!cache.has("CODE-AAAA-BBBB-CCCC"));
+}
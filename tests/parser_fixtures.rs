@@ -0,0 +1,95 @@
+#![cfg(feature = "discord")]
+
+//! Runs the Discord message parser over every message in `tests/fixtures/discord`, comparing the result to the
+//! `.golden.json` next to it. Run with `UPDATE_GOLDEN=1` to rewrite the golden files after an intended change,
+//! and review the diff.
+
+use futures::executor::block_on;
+use liccrawler::handler::discord;
+use liccrawler::parse::TimeParser;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(serde::Deserialize)]
+struct Fixture {
+    timestamp: u64,
+    content: String,
+    /// Not parsed yet, kept so fixtures capture messages as Discord sends them
+    #[allow(dead_code)]
+    #[serde(default)]
+    embeds: Vec<Value>,
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/discord");
+
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension().is_some_and(|ext| ext == "json")
+                && !path.to_string_lossy().ends_with(".golden.json")
+        })
+        .collect();
+    paths.sort();
+
+    paths
+}
+
+fn outcome(fixture: Fixture) -> Value {
+    match block_on(discord::parse(
+        fixture.content,
+        fixture.timestamp,
+        &TimeParser::new(),
+    )) {
+        Ok((code, expires_at, creator_name, creator_url)) => json!({
+            "code": code,
+            "expires_at": expires_at,
+            "creator_name": creator_name,
+            "creator_url": creator_url,
+        }),
+        Err(error) => json!({ "error": error }),
+    }
+}
+
+#[test]
+fn test_fixtures_match_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = vec![];
+
+    for path in fixtures() {
+        let fixture: Fixture = serde_json::from_str(&std::fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|e| panic!("Invalid fixture {}: {}", path.display(), e));
+        let actual = outcome(fixture);
+        let golden = path.with_extension("golden.json");
+
+        if update {
+            std::fs::write(
+                &golden,
+                serde_json::to_string_pretty(&actual).unwrap() + "\n",
+            )
+            .unwrap();
+            continue;
+        }
+
+        let expected: Value = std::fs::read_to_string(&golden)
+            .map(|s| serde_json::from_str(&s).unwrap())
+            .unwrap_or_else(|_| panic!("Missing {}, run with UPDATE_GOLDEN=1", golden.display()));
+
+        if actual != expected {
+            mismatches.push(format!(
+                "{}:\n  expected {}\n  actual   {}",
+                path.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn test_fixtures_exist() {
+    assert!(fixtures().len() >= 10);
+}
@@ -0,0 +1,129 @@
+//! Property tests for `TimeParser`: dates written in formats it supports must come back as the same day,
+//! and nothing it returns may exceed the safety net.
+
+use futures::executor::block_on;
+use liccrawler::parse::{next_week, TimeParser};
+use proptest::prelude::*;
+use time::{Date, Month};
+
+// 2024-01-15, dates are parsed relative to this as if posted then
+const ANCHOR: u64 = 1705276800;
+/// `TimeParser::safety_net` allows up to 32 days past next week.
+const SAFETY_NET: u64 = 32 * 24 * 60 * 60;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn midnight(year: i32, month: u8, day: u8) -> u64 {
+    Date::from_calendar_date(year, Month::try_from(month).unwrap(), day)
+        .unwrap()
+        .midnight()
+        .assume_utc()
+        .unix_timestamp() as u64
+}
+
+fn suffix(day: u8) -> &'static str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th",
+    }
+}
+
+// Nothing to wait on without a translation command, so no runtime is needed.
+fn parse(input: String) -> Option<u64> {
+    block_on(TimeParser::new().parse_at(input, false, ANCHOR))
+}
+
+proptest! {
+    #[test]
+    fn test_iso_dates_round_trip(
+        year in 2023i32..=2026,
+        month in 1u8..=12,
+        day in 1u8..=28,
+        separator in prop::sample::select(vec!["-", "/"]),
+        padded in any::<bool>(),
+    ) {
+        let input = match padded {
+            true => format!("Expires {year}{separator}{month:02}{separator}{day:02}"),
+            false => format!("Expires {year}{separator}{month}{separator}{day}"),
+        };
+
+        prop_assert_eq!(parse(input), Some(midnight(year, month, day)));
+    }
+
+    #[test]
+    fn test_american_dates_round_trip(
+        year in 2023i32..=2026,
+        month in 1u8..=12,
+        day in 1u8..=28,
+        two_digit_year in any::<bool>(),
+    ) {
+        let input = match two_digit_year {
+            true => format!("Expires {month}/{day}/{:02}", year % 100),
+            false => format!("Expires {month}/{day}/{year}"),
+        };
+
+        prop_assert_eq!(parse(input), Some(midnight(year, month, day)));
+    }
+
+    #[test]
+    fn test_english_dates_round_trip(
+        year in 2023i32..=2026,
+        month in 1u8..=12,
+        day in 1u8..=28,
+        short in any::<bool>(),
+    ) {
+        let name = MONTHS[month as usize - 1];
+        let name = match short {
+            true => &name[..3],
+            false => name,
+        };
+
+        let input = format!("Expires {name} {day}{}, {year}", suffix(day));
+
+        prop_assert_eq!(parse(input), Some(midnight(year, month, day)));
+    }
+
+    #[test]
+    fn test_numeric_dates_are_valid_or_rejected(first in 0u8..=99, second in 0u8..=99) {
+        // e.g. 2/30 or 13/13, which must not turn into some other date by overflowing
+        if let Some(ts) = parse(format!("Expires {first}/{second}")) {
+            prop_assert_eq!(ts % 86400, 0);
+
+            let date = time::OffsetDateTime::from_unix_timestamp(ts as i64).unwrap();
+            let (month, day) = (date.month() as u8, date.day());
+            prop_assert!(
+                (month, day) == (first, second) || (month, day) == (second, first),
+                "{}/{} parsed as {}", first, second, date.date()
+            );
+        }
+    }
+
+    #[test]
+    fn test_never_exceeds_safety_net(input in "[a-zA-Z0-9/ ,:-]{0,40}") {
+        if let Some(ts) = block_on(TimeParser::new().parse(input, true)) {
+            prop_assert!(ts <= next_week() + SAFETY_NET);
+        }
+    }
+}
+
+#[test]
+fn test_impossible_dates() {
+    assert_eq!(parse("Expires 2/30".to_string()), None);
+    assert_eq!(parse("Expires 13/13".to_string()), None);
+    assert_eq!(parse("Expires 0/0".to_string()), None);
+}